@@ -1,122 +1,523 @@
-use nom::{digit, rest_s};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use nom::types::CompleteStr;
+use nom::{digit, rest, ErrorKind, IResult};
+use std::borrow::Cow;
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Endpoint<'a> {
     Fixed(usize),
     Moment(usize),
-    Search(&'a str),
+    /// `/query/`, with `\/` standing for a literal slash, or `/query/i` ignoring case
+    Search(Cow<'a, str>, bool),
+    /// `?query?`, searching towards older messages, with `\?` standing for a literal `?`
+    SearchBackward(Cow<'a, str>),
+    /// `{date}`, the first message sent at or after a time
+    After(DateTime<Utc>),
+    /// `<{date}`, the last message sent before a time
+    Before(DateTime<Utc>),
+    /// `@name`, the newest message by a user
+    Author(&'a str),
+    /// `~/pattern/`, a regular expression escaped like `Search`
+    Regex(Cow<'a, str>),
+    /// `$`, the last message
+    Last,
+    /// `.`, the current message
+    Current,
+    /// `+n`/`-n`, offset from the current message, a bare sign meaning one
+    Relative(isize),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Range<'a> {
     Single(Endpoint<'a>),
     DoubledEnded(Endpoint<'a>, Endpoint<'a>),
+    /// `a;b`, the left endpoint becoming the current message before the right one is resolved
+    Anchored(Endpoint<'a>, Endpoint<'a>),
     PastToPresent(Endpoint<'a>),
+    /// `,endpoint`, everything from the start up to the endpoint
+    PresentToPast(Endpoint<'a>),
+    /// `%`, every loaded message
+    All,
+}
+
+impl<'a> Range<'a> {
+    /// Resolves to an inclusive `(start, end)` pair of indices with `start <= end`, however the
+    /// endpoints were written. `endpoint` resolves an endpoint relative to a current index,
+    /// `current` being the current index and `last` the highest one.
+    pub fn resolve<F, E>(
+        &self,
+        current: usize,
+        last: usize,
+        mut endpoint: F,
+    ) -> Result<(usize, usize), E>
+    where
+        F: FnMut(&Endpoint<'a>, usize) -> Result<usize, E>,
+    {
+        match *self {
+            Range::Single(ref single) => {
+                let index = endpoint(single, current)?;
+                Ok((index, index))
+            }
+            Range::DoubledEnded(ref left, ref right) => {
+                let left = endpoint(left, current)?;
+                let right = endpoint(right, current)?;
+                Ok((left.min(right), left.max(right)))
+            }
+            Range::Anchored(ref left, ref right) => {
+                let left = endpoint(left, current)?;
+                let right = endpoint(right, left)?;
+                Ok((left.min(right), left.max(right)))
+            }
+            Range::PastToPresent(ref start) => {
+                let index = endpoint(start, current)?;
+                Ok((index.min(last), last))
+            }
+            Range::PresentToPast(ref end) => Ok((0, endpoint(end, current)?)),
+            Range::All => Ok((0, last)),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Command<'a> {
     pub range: Option<Range<'a>>,
+    /// Everything after the range, as typed
     pub command: &'a str,
+    /// First word of `command`
+    pub name: &'a str,
+    /// Words after the name, `"quoted text"` counting as one
+    pub args: Vec<&'a str>,
+    /// Text after the name, for commands taking free text
+    pub rest: &'a str,
+    /// Whether the name had ed's `p` suffix, to print the current message afterwards
+    pub print: bool,
+}
+
+/// Splits ed's `p` suffix off a one letter command name like `dp`, longer names being left
+/// alone so that `grep` stays `grep`
+fn print_suffix(name: &str) -> (&str, bool) {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(letter), Some('p'), None) if letter.is_ascii_alphabetic() => (&name[..1], true),
+        _ => (name, false),
+    }
 }
 
 impl<'a> Command<'a> {
     pub fn new(range: Option<Range<'a>>, command: &'a str) -> Command<'a> {
-        Command { range, command }
+        // ed's substitute is written without a space, its pattern being left whole in `rest`
+        if command.starts_with("s/") {
+            return Command {
+                range,
+                command,
+                name: "s",
+                args: Vec::new(),
+                rest: command[1..].trim(),
+                print: false,
+            };
+        }
+        let words = words(command);
+        let (name, rest) = match words.first() {
+            Some(&(end, name)) => (name, command[end..].trim()),
+            None => ("", ""),
+        };
+        let (name, print) = print_suffix(name);
+        let args = words.iter().skip(1).map(|&(_, word)| word).collect();
+        Command {
+            range,
+            command,
+            name,
+            args,
+            rest,
+            print,
+        }
     }
 }
 
-named!(pub number(&str) -> usize, map_res!(digit, FromStr::from_str));
+/// Splits on whitespace, keeping `"quoted text"` together without its quotes, each word paired
+/// with the offset just past it
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, ch)) = chars.peek() {
+        if ch.is_whitespace() {
+            chars.next();
+        } else if ch == '"' {
+            chars.next();
+            match chars.by_ref().find(|&(_, ch)| ch == '"') {
+                Some((end, _)) => words.push((end + 1, &text[start + 1..end])),
+                None => words.push((text.len(), &text[start + 1..])),
+            }
+        } else {
+            let end = chars
+                .by_ref()
+                .find(|&(_, ch)| ch.is_whitespace())
+                .map(|(end, _)| end)
+                .unwrap_or_else(|| text.len());
+            words.push((end, &text[start..end]));
+        }
+    }
+    words
+}
+
+/// Why a command was rejected, with the part of it that could not be parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError<'a> {
+    pub input: &'a str,
+    pub message: &'static str,
+}
 
-named!(fixed(&str) -> Endpoint,
+impl<'a> ParseError<'a> {
+    pub fn new(input: &'a str, message: &'static str) -> ParseError<'a> {
+        ParseError { input, message }
+    }
+}
+
+impl<'a> fmt::Display for ParseError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.message, self.input)
+    }
+}
+
+named!(pub number(CompleteStr) -> usize,
+    map_res!(digit, |digits: CompleteStr| FromStr::from_str(digits.0))
+);
+
+named!(fixed(CompleteStr) -> Endpoint,
     do_parse!(
         number: number >>
         (Endpoint::Fixed(number))
     )
 );
 
-named!(moment(&str) -> Endpoint,
+named!(moment(CompleteStr) -> Endpoint,
     do_parse!(
         char!('#') >>
-        moments: map_res!(digit, FromStr::from_str) >>
+        moments: number >>
         (Endpoint::Moment(moments))
     )
 );
 
-named!(search(&str) -> Endpoint,
+/// Query up to the next unescaped `delimiter`, unescaping `\` followed by the delimiter
+fn delimited_query(input: CompleteStr, delimiter: char) -> IResult<CompleteStr, Cow<str>> {
+    let mut escaped = false;
+    for (i, ch) in input.0.char_indices() {
+        match ch {
+            '\\' if !escaped => escaped = true,
+            ch if ch == delimiter && !escaped => {
+                let query = &input.0[..i];
+                let escape = format!("\\{}", delimiter);
+                let query = if query.contains(&escape[..]) {
+                    Cow::Owned(query.replace(&escape[..], &delimiter.to_string()))
+                } else {
+                    Cow::Borrowed(query)
+                };
+                return Ok((CompleteStr(&input.0[i..]), query));
+            }
+            _ => escaped = false,
+        }
+    }
+    Err(::nom::Err::Error(error_position!(
+        input,
+        ErrorKind::TakeUntil
+    )))
+}
+
+/// `/pattern/replacement/flags` of ed's substitute, escaped like a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct Substitution<'a> {
+    pub pattern: Cow<'a, str>,
+    pub replacement: Cow<'a, str>,
+    /// `g`, replacing every match instead of the first
+    pub global: bool,
+    /// `i`
+    pub ignore_case: bool,
+}
+
+/// Parses what follows the `s` of a substitute, the closing `/` being optional without flags
+pub fn parse_substitution(text: &str) -> Result<Substitution, ParseError> {
+    let invalid = || ParseError::new(text, "expected s/pattern/replacement/");
+    if !text.starts_with('/') {
+        return Err(invalid());
+    }
+    let (after, pattern) = search_query(CompleteStr(&text[1..])).map_err(|_| invalid())?;
+    if pattern.is_empty() {
+        return Err(ParseError::new(text, "empty pattern"));
+    }
+    let after = &after.0[1..];
+    let (replacement, flags) = match search_query(CompleteStr(after)) {
+        Ok((flags, replacement)) => (replacement, &flags.0[1..]),
+        Err(_) => (Cow::Owned(after.replace("\\/", "/")), ""),
+    };
+    if !flags.chars().all(|flag| flag == 'g' || flag == 'i') {
+        return Err(ParseError::new(flags, "unknown flag, expected g or i"));
+    }
+    Ok(Substitution {
+        pattern,
+        replacement,
+        global: flags.contains('g'),
+        ignore_case: flags.contains('i'),
+    })
+}
+
+/// Search query up to the next unescaped `/`
+fn search_query(input: CompleteStr) -> IResult<CompleteStr, Cow<str>> {
+    delimited_query(input, '/')
+}
+
+/// Backward search query up to the next unescaped `?`
+fn backward_query(input: CompleteStr) -> IResult<CompleteStr, Cow<str>> {
+    delimited_query(input, '?')
+}
+
+named!(search(CompleteStr) -> Endpoint,
     do_parse!(
         query: delimited!(
             char!('/'),
-            take_until!("/"),
+            search_query,
             char!('/')
         ) >>
-        (Endpoint::Search(query))
+        ignore_case: opt!(char!('i')) >>
+        (Endpoint::Search(query, ignore_case.is_some()))
     )
 );
 
-named!(endpoint(&str) -> Endpoint, alt!(fixed | moment | search));
+named!(search_backward(CompleteStr) -> Endpoint,
+    do_parse!(
+        query: delimited!(
+            char!('?'),
+            backward_query,
+            char!('?')
+        ) >>
+        (Endpoint::SearchBackward(query))
+    )
+);
 
-named!(single(&str) -> Range, do_parse!(
+named!(last(CompleteStr) -> Endpoint,
+    do_parse!(
+        char!('$') >>
+        (Endpoint::Last)
+    )
+);
+
+named!(current(CompleteStr) -> Endpoint,
+    do_parse!(
+        char!('.') >>
+        (Endpoint::Current)
+    )
+);
+
+named!(relative(CompleteStr) -> Endpoint,
+    do_parse!(
+        sign: one_of!("+-") >>
+        count: opt!(number) >>
+        (Endpoint::Relative(if sign == '-' {
+            -(count.unwrap_or(1) as isize)
+        } else {
+            count.unwrap_or(1) as isize
+        }))
+    )
+);
+
+named!(author(CompleteStr) -> Endpoint,
+    do_parse!(
+        char!('@') >>
+        name: take_while1!(|ch: char| !ch.is_whitespace() && ch != ',' && ch != ';') >>
+        (Endpoint::Author(name.0))
+    )
+);
+
+/// Parses `2021-03-01`, `2021-03-01 14:30`, `2021-03-01T14:30:05` or an RFC 3339 time, times
+/// without an offset being local and dates alone meaning midnight
+pub fn parse_date(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.with_timezone(&Utc));
+    }
+    let naive = [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ]
+    .iter()
+    .filter_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+    .next()
+    .or_else(|| {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d")
+            .ok()
+            .map(|date| date.and_hms(0, 0, 0))
+    })?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+named!(date(CompleteStr) -> DateTime<Utc>,
+    map_opt!(
+        delimited!(
+            char!('{'),
+            take_until!("}"),
+            char!('}')
+        ),
+        |text: CompleteStr| parse_date(text.0)
+    )
+);
+
+named!(after(CompleteStr) -> Endpoint,
+    do_parse!(
+        time: date >>
+        (Endpoint::After(time))
+    )
+);
+
+named!(before(CompleteStr) -> Endpoint,
+    do_parse!(
+        char!('<') >>
+        time: date >>
+        (Endpoint::Before(time))
+    )
+);
+
+named!(regex(CompleteStr) -> Endpoint,
+    do_parse!(
+        char!('~') >>
+        pattern: delimited!(
+            char!('/'),
+            search_query,
+            char!('/')
+        ) >>
+        (Endpoint::Regex(pattern))
+    )
+);
+
+named!(endpoint(CompleteStr) -> Endpoint,
+    alt!(
+        fixed | moment | search | search_backward | regex | author | after | before | last
+            | current | relative
+    )
+);
+
+named!(single(CompleteStr) -> Range, do_parse!(
     endpoint: endpoint >>
     (Range::Single(endpoint))
 ));
 
-named!(double_ended(&str) -> Range, do_parse!(
+named!(double_ended(CompleteStr) -> Range, do_parse!(
     left_endpoint: endpoint >>
     char!(',') >>
     right_endpoint: endpoint >>
     (Range::DoubledEnded(left_endpoint, right_endpoint))
 ));
 
-named!(past_to_present(&str) -> Range, do_parse!(
+named!(anchored(CompleteStr) -> Range, do_parse!(
+    left_endpoint: endpoint >>
+    char!(';') >>
+    right_endpoint: endpoint >>
+    (Range::Anchored(left_endpoint, right_endpoint))
+));
+
+named!(past_to_present(CompleteStr) -> Range, do_parse!(
     endpoint: endpoint >>
     char!(',') >>
     (Range::PastToPresent(endpoint))
 ));
 
-named!(range(&str) -> Range, alt!(double_ended | past_to_present | single));
+named!(present_to_past(CompleteStr) -> Range, do_parse!(
+    char!(',') >>
+    endpoint: endpoint >>
+    (Range::PresentToPast(endpoint))
+));
+
+named!(all(CompleteStr) -> Range, do_parse!(
+    char!('%') >>
+    (Range::All)
+));
+
+named!(range(CompleteStr) -> Range,
+    alt!(double_ended | anchored | past_to_present | present_to_past | single | all)
+);
 
 named!(
-    command(&str) -> Command,
+    command(CompleteStr) -> Command,
     do_parse!(
         range: opt!(call!(range)) >>
-        command: call!(rest_s) >>
-        (Command {range, command})
+        command: call!(rest) >>
+        (Command::new(range, command.0))
     )
 );
 
-pub fn parse_cmd(cmd: &str) -> Option<Command> {
-    match command(cmd) {
-        Ok((_, cmd)) => Some(cmd),
-        _ => None,
+/// Rejects command text that starts like an address but could not be parsed as one
+fn check_address(cmd: Command) -> Result<Command, ParseError> {
+    let text = cmd.command;
+    if text.starts_with('/') && !text[1..].contains('/') {
+        return Err(ParseError::new(
+            text,
+            "unterminated search: missing closing '/'",
+        ));
+    }
+    // A lone `?` is a command of its own rather than the start of a search
+    if text.starts_with('?') && cmd.name != "?" && !text[1..].contains('?') {
+        return Err(ParseError::new(
+            text,
+            "unterminated search: missing closing '?'",
+        ));
+    }
+    if text.starts_with("~/") && !text[2..].contains('/') {
+        return Err(ParseError::new(
+            text,
+            "unterminated pattern: missing closing '/'",
+        ));
+    }
+    if text.starts_with('{') || text.starts_with("<{") {
+        return Err(ParseError::new(
+            text,
+            "invalid date: expected {YYYY-MM-DD} or {YYYY-MM-DD HH:MM}",
+        ));
+    }
+    if text.starts_with('#') && !text[1..].starts_with(|ch: char| ch.is_ascii_digit()) {
+        return Err(ParseError::new(text, "expected a number after '#'"));
+    }
+    Ok(cmd)
+}
+
+pub fn parse_cmd(cmd: &str) -> Result<Command, ParseError> {
+    match command(CompleteStr(cmd)) {
+        Ok((_, parsed)) => check_address(parsed),
+        _ => Err(ParseError::new(cmd, "invalid command")),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::command;
-    use super::Command;
+    use super::parse_cmd;
     use super::Endpoint::*;
     use super::Range::*;
+    use super::{parse_date, parse_substitution, Command, Endpoint, ParseError, Substitution};
+    use chrono::{DateTime, Local, TimeZone, Utc};
+    use nom::types::CompleteStr;
 
     #[test]
     fn cmd_no_range() {
-        let result = command("d").unwrap().1;
+        let result = command(CompleteStr("d")).unwrap().1;
 
         assert_eq!(result, Command::new(None, "d"));
     }
 
     #[test]
     fn past_to_present_cmd() {
-        let result = command("5,d").unwrap().1;
+        let result = command(CompleteStr("5,d")).unwrap().1;
 
         assert_eq!(result, Command::new(Some(PastToPresent(Fixed(5))), "d"));
     }
 
     #[test]
     fn double_ended_cmd() {
-        let result = command("10,5d").unwrap().1;
+        let result = command(CompleteStr("10,5d")).unwrap().1;
 
         assert_eq!(
             result,
@@ -124,58 +525,551 @@ mod tests {
         );
     }
 
+    #[test]
+    fn anchored_cmd() {
+        let result = command(CompleteStr("10;5d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(Anchored(Fixed(10), Fixed(5))), "d")
+        );
+    }
+
+    #[test]
+    fn anchored_relative_cmd() {
+        let result = command(CompleteStr("/foo/;+2d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(
+                Some(Anchored(Search("foo".into(), false), Relative(2))),
+                "d"
+            )
+        );
+    }
+
     #[test]
     fn past_to_present_moment_cmd() {
-        let result = command("#5,d").unwrap().1;
+        let result = command(CompleteStr("#5,d")).unwrap().1;
 
         assert_eq!(result, Command::new(Some(PastToPresent(Moment(5))), "d"));
     }
 
     #[test]
-    fn invalid_moment() {
-        let result = command(",#5d").unwrap().1;
+    fn last_to_present_cmd() {
+        let result = command(CompleteStr("$,d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(PastToPresent(Last)), "d"));
+    }
+
+    #[test]
+    fn search_to_last_cmd() {
+        let result = command(CompleteStr("/foo/,$d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(DoubledEnded(Search("foo".into(), false), Last)), "d")
+        );
+    }
+
+    #[test]
+    fn current_cmd() {
+        let result = command(CompleteStr(".d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(Single(Current)), "d"));
+    }
+
+    #[test]
+    fn current_to_last_cmd() {
+        let result = command(CompleteStr(".,$d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(DoubledEnded(Current, Last)), "d"));
+    }
+
+    #[test]
+    fn first_to_current_cmd() {
+        let result = command(CompleteStr("1,.d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(DoubledEnded(Fixed(1), Current)), "d")
+        );
+    }
+
+    #[test]
+    fn relative_cmd() {
+        let result = command(CompleteStr("+3d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(Single(Relative(3))), "d"));
+    }
+
+    #[test]
+    fn relative_range_cmd() {
+        let result = command(CompleteStr("-2,+2d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(DoubledEnded(Relative(-2), Relative(2))), "d")
+        );
+    }
+
+    #[test]
+    fn bare_sign_cmd() {
+        let result = command(CompleteStr("-d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(Single(Relative(-1))), "d"));
+    }
+
+    #[test]
+    fn present_to_past_cmd() {
+        let result = command(CompleteStr(",10d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(PresentToPast(Fixed(10))), "d"));
+    }
+
+    #[test]
+    fn present_to_past_moment_cmd() {
+        let result = command(CompleteStr(",#5d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(PresentToPast(Moment(5))), "d"));
+    }
+
+    #[test]
+    fn bare_comma() {
+        let result = command(CompleteStr(",d")).unwrap().1;
+
+        assert_eq!(result, Command::new(None, ",d"));
+    }
+
+    #[test]
+    fn percent_range() {
+        let result = command(CompleteStr("%d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(All), "d"));
+
+        let result = command(CompleteStr("%p")).unwrap().1;
 
-        assert_eq!(result, Command::new(None, ",#5d"));
+        assert_eq!(result, Command::new(Some(All), "p"));
     }
 
     #[test]
     fn search_cmd() {
-        let result = command("/foo/d").unwrap().1;
+        let result = command(CompleteStr("/foo/d")).unwrap().1;
 
-        assert_eq!(result, Command::new(Some(Single(Search("foo"))), "d"));
+        assert_eq!(
+            result,
+            Command::new(Some(Single(Search("foo".into(), false))), "d")
+        );
+    }
+
+    #[test]
+    fn search_ignoring_case_cmd() {
+        let sensitive = command(CompleteStr("/Foo/d")).unwrap().1;
+        let insensitive = command(CompleteStr("/Foo/i d")).unwrap().1;
+
+        assert_eq!(sensitive.range, Some(Single(Search("Foo".into(), false))));
+        assert_eq!(insensitive.range, Some(Single(Search("Foo".into(), true))));
+        assert_eq!(insensitive.command, " d");
+        assert_eq!(insensitive.name, "d");
+    }
+
+    #[test]
+    fn search_ignoring_case_range_cmd() {
+        let result = command(CompleteStr("/foo/i,$d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(DoubledEnded(Search("foo".into(), true), Last)), "d")
+        );
     }
 
     #[test]
     fn search_to_present_cmd() {
-        let result = command("/bar/,d").unwrap().1;
+        let result = command(CompleteStr("/bar/,d")).unwrap().1;
 
         assert_eq!(
             result,
-            Command::new(Some(PastToPresent(Search("bar"))), "d")
+            Command::new(Some(PastToPresent(Search("bar".into(), false))), "d")
         );
     }
 
     #[test]
     fn double_ended_search_cmd() {
-        let result = command("/foo/,/bar/d").unwrap().1;
+        let result = command(CompleteStr("/foo/,/bar/d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(
+                Some(DoubledEnded(
+                    Search("foo".into(), false),
+                    Search("bar".into(), false)
+                )),
+                "d"
+            )
+        );
+    }
+
+    fn local_midnight(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Local
+            .ymd(year, month, day)
+            .and_hms(0, 0, 0)
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn after_date_cmd() {
+        let result = command(CompleteStr("{2021-03-01},d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(PastToPresent(After(local_midnight(2021, 3, 1)))), "d")
+        );
+    }
+
+    #[test]
+    fn before_date_cmd() {
+        let result = command(CompleteStr(",<{2021-03-01}d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(PresentToPast(Before(local_midnight(2021, 3, 1)))), "d")
+        );
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(parse_date("2021-03-01"), Some(local_midnight(2021, 3, 1)));
+        assert_eq!(
+            parse_date("2021-03-01 14:30"),
+            Some(Local.ymd(2021, 3, 1).and_hms(14, 30, 0).with_timezone(&Utc))
+        );
+        assert_eq!(
+            parse_date("2021-03-01T14:30:00+02:00"),
+            Some(Utc.ymd(2021, 3, 1).and_hms(12, 30, 0))
+        );
+        assert_eq!(parse_date("yesterday"), None);
+    }
+
+    #[test]
+    fn invalid_date() {
+        assert_eq!(
+            parse_cmd("{2021-13-01},d"),
+            Err(ParseError::new(
+                "{2021-13-01},d",
+                "invalid date: expected {YYYY-MM-DD} or {YYYY-MM-DD HH:MM}"
+            ))
+        );
+    }
+
+    #[test]
+    fn author_cmd() {
+        let result = command(CompleteStr("@bob d")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(Single(Author("bob"))), " d"));
+        assert_eq!(result.name, "d");
+    }
+
+    #[test]
+    fn author_to_last_cmd() {
+        let result = command(CompleteStr("@bob,$d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(DoubledEnded(Author("bob"), Last)), "d")
+        );
+    }
+
+    #[test]
+    fn no_range_cmd_with_at() {
+        let result = parse_cmd("send hi @bob, see you").unwrap();
+
+        assert_eq!(result.range, None);
+        assert_eq!(result.name, "send");
+    }
+
+    #[test]
+    fn anchored_search_cmd() {
+        let result = command(CompleteStr("/foo/;/bar/d")).unwrap().1;
 
         assert_eq!(
             result,
-            Command::new(Some(DoubledEnded(Search("foo"), Search("bar"))), "d")
+            Command::new(
+                Some(Anchored(
+                    Search("foo".into(), false),
+                    Search("bar".into(), false)
+                )),
+                "d"
+            )
         );
     }
 
     #[test]
     fn no_range_cmd() {
-        let result = command("d foo bar").unwrap().1;
+        let result = command(CompleteStr("d foo bar")).unwrap().1;
 
         assert_eq!(result, Command::new(None, "d foo bar"));
     }
 
+    #[test]
+    fn name_and_args() {
+        let result = command(CompleteStr("5,nick bob  smith")).unwrap().1;
+
+        assert_eq!(result.name, "nick");
+        assert_eq!(result.args, vec!["bob", "smith"]);
+        assert_eq!(result.rest, "bob  smith");
+    }
+
+    #[test]
+    fn quoted_args() {
+        let result = command(CompleteStr("nick \"two words\" other")).unwrap().1;
+
+        assert_eq!(result.name, "nick");
+        assert_eq!(result.args, vec!["two words", "other"]);
+
+        let result = command(CompleteStr("nick \"unterminated quote")).unwrap().1;
+        assert_eq!(result.args, vec!["unterminated quote"]);
+
+        let result = command(CompleteStr("")).unwrap().1;
+        assert_eq!(result.name, "");
+        assert!(result.args.is_empty());
+    }
+
+    #[test]
+    fn escaped_slash_search_cmd() {
+        let result = command(CompleteStr("/a\\/b/d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(Single(Search("a/b".into(), false))), "d")
+        );
+    }
+
+    #[test]
+    fn print_suffix_cmd() {
+        let result = parse_cmd("5dp").unwrap();
+
+        assert_eq!(result.range, Some(Single(Fixed(5))));
+        assert_eq!(result.name, "d");
+        assert!(result.print);
+    }
+
+    #[test]
+    fn p_starting_cmd_is_not_a_suffix() {
+        let ping = parse_cmd("5ping").unwrap();
+        assert_eq!(ping.name, "ping");
+        assert!(!ping.print);
+
+        let grep = parse_cmd("grep /p/").unwrap();
+        assert_eq!(grep.name, "grep");
+        assert!(!grep.print);
+
+        let bare = parse_cmd("p").unwrap();
+        assert_eq!(bare.name, "p");
+        assert!(!bare.print);
+    }
+
+    fn fixed_index(endpoint: &Endpoint, current: usize) -> Result<usize, ()> {
+        match *endpoint {
+            Fixed(n) => Ok(n - 1),
+            Relative(offset) => Ok((current as isize + offset) as usize),
+            _ => Err(()),
+        }
+    }
+
+    #[test]
+    fn reversed_range_is_ordered() {
+        let forwards = parse_cmd("5,10d").unwrap().range.unwrap();
+        let backwards = parse_cmd("10,5d").unwrap().range.unwrap();
+
+        assert_eq!(forwards.resolve(0, 20, fixed_index), Ok((4, 9)));
+        assert_eq!(backwards.resolve(0, 20, fixed_index), Ok((4, 9)));
+    }
+
+    #[test]
+    fn resolve_open_ranges() {
+        assert_eq!(Single(Fixed(3)).resolve(0, 20, fixed_index), Ok((2, 2)));
+        assert_eq!(
+            PastToPresent(Fixed(3)).resolve(0, 20, fixed_index),
+            Ok((2, 20))
+        );
+        assert_eq!(
+            PresentToPast(Fixed(3)).resolve(0, 20, fixed_index),
+            Ok((0, 2))
+        );
+        assert_eq!(All.resolve(0, 20, fixed_index), Ok((0, 20)));
+        assert_eq!(Single(Last).resolve(0, 20, fixed_index), Err(()));
+    }
+
+    #[test]
+    fn anchored_resolves_right_from_left() {
+        // `,` counts both endpoints from the current message, `;` the right one from the left
+        assert_eq!(
+            DoubledEnded(Fixed(5), Relative(2)).resolve(10, 20, fixed_index),
+            Ok((4, 12))
+        );
+        assert_eq!(
+            Anchored(Fixed(5), Relative(2)).resolve(10, 20, fixed_index),
+            Ok((4, 6))
+        );
+    }
+
+    #[test]
+    fn search_backward_cmd() {
+        let result = command(CompleteStr("?foo?d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(Single(SearchBackward("foo".into()))), "d")
+        );
+    }
+
+    #[test]
+    fn search_backward_to_current_cmd() {
+        let result = command(CompleteStr("?foo?,.d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(
+                Some(DoubledEnded(SearchBackward("foo".into()), Current)),
+                "d"
+            )
+        );
+    }
+
+    #[test]
+    fn search_backward_escaped_cmd() {
+        let result = command(CompleteStr("?why\\??d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(Single(SearchBackward("why?".into()))), "d")
+        );
+    }
+
+    #[test]
+    fn question_mark_cmd() {
+        let result = parse_cmd("? nick").unwrap();
+
+        assert_eq!(result.range, None);
+        assert_eq!(result.name, "?");
+        assert_eq!(result.args, vec!["nick"]);
+    }
+
+    #[test]
+    fn no_range_cmd_with_question_marks() {
+        let result = parse_cmd("grep why? not?").unwrap();
+
+        assert_eq!(result.range, None);
+        assert_eq!(result.args, vec!["why?", "not?"]);
+    }
+
+    #[test]
+    fn regex_cmd() {
+        let result = command(CompleteStr("~/fo+ ba[rz]/d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(Single(Regex("fo+ ba[rz]".into()))), "d")
+        );
+    }
+
+    #[test]
+    fn regex_to_last_cmd() {
+        let result = command(CompleteStr("~/^a\\/b$/,$d")).unwrap().1;
+
+        assert_eq!(
+            result,
+            Command::new(Some(DoubledEnded(Regex("^a/b$".into()), Last)), "d")
+        );
+    }
+
     #[test]
     fn no_range_cmd_with_slashes() {
-        let result = command("d b/ar/").unwrap().1;
+        let result = command(CompleteStr("d b/ar/")).unwrap().1;
 
         assert_eq!(result, Command::new(None, "d b/ar/"));
     }
+
+    #[test]
+    fn unterminated_search() {
+        assert_eq!(
+            parse_cmd("/foo d"),
+            Err(ParseError::new(
+                "/foo d",
+                "unterminated search: missing closing '/'"
+            ))
+        );
+        assert_eq!(
+            parse_cmd("5,/foo"),
+            Err(ParseError::new(
+                "/foo",
+                "unterminated search: missing closing '/'"
+            ))
+        );
+    }
+
+    #[test]
+    fn moment_without_number() {
+        assert_eq!(
+            parse_cmd("#d"),
+            Err(ParseError::new("#d", "expected a number after '#'"))
+        );
+        assert!(parse_cmd("#5d").is_ok());
+    }
+
+    #[test]
+    fn substitute_cmd() {
+        let result = parse_cmd("3s/a b/c/g").unwrap();
+
+        assert_eq!(result.range, Some(Single(Fixed(3))));
+        assert_eq!(result.name, "s");
+        assert_eq!(result.rest, "/a b/c/g");
+        // Other commands starting with `s` are left alone
+        assert_eq!(parse_cmd("send hi").unwrap().name, "send");
+    }
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(
+            parse_substitution("/foo/bar/"),
+            Ok(Substitution {
+                pattern: "foo".into(),
+                replacement: "bar".into(),
+                global: false,
+                ignore_case: false,
+            })
+        );
+        let flagged = parse_substitution("/a\\/b/c\\/d/gi").unwrap();
+        assert_eq!((&*flagged.pattern, &*flagged.replacement), ("a/b", "c/d"));
+        assert!(flagged.global && flagged.ignore_case);
+        // Like ed, the closing delimiter can be left out
+        let open = parse_substitution("/foo/bar").unwrap();
+        assert_eq!((&*open.replacement, open.global), ("bar", false));
+        assert_eq!(&*parse_substitution("/foo/").unwrap().replacement, "");
+    }
+
+    #[test]
+    fn invalid_substitutions() {
+        let expected = "expected s/pattern/replacement/";
+        assert_eq!(parse_substitution(""), Err(ParseError::new("", expected)));
+        assert_eq!(
+            parse_substitution("/foo"),
+            Err(ParseError::new("/foo", expected))
+        );
+        assert_eq!(
+            parse_substitution("//bar/"),
+            Err(ParseError::new("//bar/", "empty pattern"))
+        );
+        assert_eq!(
+            parse_substitution("/a/b/x"),
+            Err(ParseError::new("x", "unknown flag, expected g or i"))
+        );
+    }
+
+    #[test]
+    fn bare_range() {
+        let result = command(CompleteStr("5")).unwrap().1;
+
+        assert_eq!(result, Command::new(Some(Single(Fixed(5))), ""));
+    }
 }
@@ -1,8 +1,9 @@
+extern crate chrono;
 #[macro_use]
 extern crate nom;
 
 pub mod cmd;
-pub use cmd::parse_cmd;
+pub use cmd::{parse_cmd, ParseError};
 
 pub mod markdown;
 pub use markdown::parse_msg;
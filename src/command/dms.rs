@@ -0,0 +1,194 @@
+//! Listing direct message channels for the `dms` command
+
+use serenity::model::channel::{Message, PrivateChannel};
+use serenity::model::id::{ChannelId, MessageId};
+use serenity::CACHE;
+
+use model::{ChannelBuffers, MessageItem};
+
+/// Longest preview of the latest message shown in the listing
+const PREVIEW_LEN: usize = 40;
+/// Most channels whose latest message is fetched for a preview, the most recently active ones
+const FETCHED_PREVIEWS: usize = 10;
+
+/// A direct message channel as listed by `dms`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DmEntry {
+    pub channel_id: ChannelId,
+    pub recipient: String,
+    pub last_message_id: Option<MessageId>,
+    /// Latest message, when it is loaded or could be fetched
+    pub preview: Option<String>,
+}
+
+fn preview(msg: &Message) -> String {
+    let content = msg.content.lines().next().unwrap_or_default();
+    if content.chars().count() > PREVIEW_LEN {
+        content.chars().take(PREVIEW_LEN - 1).collect::<String>() + "…"
+    } else {
+        content.to_owned()
+    }
+}
+
+/// Latest message of a channel that is already loaded, in the open channel's `loaded` messages
+/// or in those received in the background
+fn loaded_latest<'a>(
+    channel_id: ChannelId,
+    loaded: &'a [MessageItem],
+    buffers: &'a ChannelBuffers,
+) -> Option<&'a Message> {
+    loaded
+        .iter()
+        .chain(buffers.latest(channel_id))
+        .filter_map(|item| match item {
+            MessageItem::DiscordMessage(msg) if msg.channel_id == channel_id => Some(&**msg),
+            MessageItem::DiscordMessage(_) => None,
+        })
+        .max_by_key(|msg| msg.id)
+}
+
+pub fn entry(
+    channel: &PrivateChannel,
+    loaded: &[MessageItem],
+    buffers: &ChannelBuffers,
+) -> DmEntry {
+    DmEntry {
+        channel_id: channel.id,
+        recipient: channel.recipient.read().name.clone(),
+        last_message_id: channel.last_message_id,
+        preview: loaded_latest(channel.id, loaded, buffers).map(preview),
+    }
+}
+
+/// Previews the most recently active entries that have none with their latest message from
+/// `fetch`. Older channels are left without one rather than fetching every channel's.
+pub fn fetch_previews<F>(entries: &mut [DmEntry], mut fetch: F)
+where
+    F: FnMut(ChannelId, MessageId) -> Option<Message>,
+{
+    let missing = entries
+        .iter_mut()
+        .take(FETCHED_PREVIEWS)
+        .filter(|entry| entry.preview.is_none());
+    for entry in missing {
+        if let Some(last) = entry.last_message_id {
+            entry.preview = fetch(entry.channel_id, last).as_ref().map(preview);
+        }
+    }
+}
+
+/// Cached direct message channels, most recently active first, previewed from the `loaded`
+/// messages of the open channel, background buffers or a request for the latest message
+pub fn cached_dms(loaded: &[MessageItem], buffers: &ChannelBuffers) -> Vec<DmEntry> {
+    let mut entries: Vec<_> = CACHE
+        .read()
+        .private_channels
+        .values()
+        .map(|channel| entry(&channel.read(), loaded, buffers))
+        .collect();
+    entries.sort_by(|a, b| b.last_message_id.cmp(&a.last_message_id));
+    fetch_previews(&mut entries, |channel, message| {
+        match channel.message(message) {
+            Ok(msg) => Some(msg),
+            Err(err) => {
+                warn!("Unable to fetch the latest message of {}: {}", channel, err);
+                None
+            }
+        }
+    });
+    entries
+}
+
+/// Numbered lines for the overlay, the numbers being what `dms <n>` takes
+pub fn listing(entries: &[DmEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| match entry.preview {
+            Some(ref preview) => format!("{}: {}: {}", i + 1, entry.recipient, preview),
+            None => format!("{}: {}", i + 1, entry.recipient),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{entry, fetch_previews, listing, FETCHED_PREVIEWS};
+    use helpers::testing::{message, message_from_value, message_value};
+    use model::{ChannelBuffers, MessageItem};
+    use serde_json;
+    use serenity::model::channel::PrivateChannel;
+    use serenity::model::id::{ChannelId, MessageId};
+
+    fn private_channel(id: u64, user_id: u64, name: &str) -> PrivateChannel {
+        serde_json::from_value(json!({
+            "id": id.to_string(),
+            "last_message_id": "30",
+            "last_pin_timestamp": null,
+            "type": 1,
+            "recipients": [{
+                "id": user_id.to_string(),
+                "username": name,
+                "discriminator": "0001",
+                "avatar": null,
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn dm_previews() {
+        let mut buffers = ChannelBuffers::new();
+        let mut value = message_value(30, 11, "see you tomorrow\nbye");
+        value["channel_id"] = json!("5");
+        buffers.push(
+            MessageItem::DiscordMessage(Box::new(message_from_value(value))),
+            false,
+        );
+
+        // The open channel, its messages are loaded in the view rather than buffered
+        let mut value = message_value(29, 13, "on my way");
+        value["channel_id"] = json!("7");
+        let loaded = vec![MessageItem::DiscordMessage(Box::new(message_from_value(
+            value,
+        )))];
+
+        let entries = vec![
+            entry(&private_channel(5, 11, "alice"), &loaded, &buffers),
+            entry(&private_channel(6, 12, "bob"), &loaded, &buffers),
+            entry(&private_channel(7, 13, "carol"), &loaded, &buffers),
+        ];
+        assert_eq!(entries[0].channel_id, ChannelId(5));
+        assert_eq!(entries[0].last_message_id, Some(MessageId(30)));
+        assert_eq!(
+            listing(&entries),
+            vec![
+                "1: alice: see you tomorrow".to_owned(),
+                "2: bob".to_owned(),
+                "3: carol: on my way".to_owned(),
+            ]
+        );
+    }
+
+    #[test]
+    fn fetched_previews() {
+        let buffers = ChannelBuffers::new();
+        let mut entries: Vec<_> = (0..FETCHED_PREVIEWS as u64 + 2)
+            .map(|id| entry(&private_channel(id, 11, "alice"), &[], &buffers))
+            .collect();
+        entries[1].preview = Some("loaded".to_owned());
+
+        let mut fetched = Vec::new();
+        fetch_previews(&mut entries, |channel, last| {
+            fetched.push(channel);
+            assert_eq!(last, MessageId(30));
+            Some(message(30, 11, "fetched"))
+        });
+        // Only the most recent channels without a preview are fetched
+        assert_eq!(fetched.len(), FETCHED_PREVIEWS - 1);
+        assert!(!fetched.contains(&ChannelId(1)));
+        assert_eq!(entries[0].preview, Some("fetched".to_owned()));
+        assert_eq!(entries[1].preview, Some("loaded".to_owned()));
+        assert_eq!(entries[FETCHED_PREVIEWS].preview, None);
+    }
+}
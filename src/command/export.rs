@@ -0,0 +1,88 @@
+//! Writing messages to files for the `wjson` command
+
+use failure::Error;
+use serde_json;
+use serenity::model::id::{MessageId, UserId};
+
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use model::MessageItem;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ExportedMessage {
+    pub id: MessageId,
+    pub author_id: UserId,
+    pub author: String,
+    pub timestamp: String,
+    pub content: String,
+    pub attachments: Vec<String>,
+}
+
+impl<'a> From<&'a MessageItem> for ExportedMessage {
+    fn from(item: &'a MessageItem) -> ExportedMessage {
+        match item {
+            MessageItem::DiscordMessage(msg) => ExportedMessage {
+                id: msg.id,
+                author_id: msg.author.id,
+                author: msg.author.name.clone(),
+                timestamp: msg.timestamp.to_rfc3339(),
+                content: msg.content.clone(),
+                attachments: msg
+                    .attachments
+                    .iter()
+                    .map(|attachment| attachment.url.clone())
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Expands a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    if path.starts_with("~/") {
+        if let Some(home_dir) = env::home_dir() {
+            return home_dir.join(&path[2..]);
+        }
+    }
+    PathBuf::from(path)
+}
+
+pub fn write_json(path: &Path, messages: &[MessageItem]) -> Result<(), Error> {
+    let exported = messages
+        .iter()
+        .map(ExportedMessage::from)
+        .collect::<Vec<_>>();
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, &exported)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::ExportedMessage;
+    use helpers::testing::message_item;
+    use serde_json;
+    use serenity::model::id::{MessageId, UserId};
+
+    #[test]
+    fn json_round_trip() {
+        let messages = vec![message_item(1, 10, "hello"), message_item(2, 11, "world")];
+        let exported = messages
+            .iter()
+            .map(ExportedMessage::from)
+            .collect::<Vec<_>>();
+
+        let json = serde_json::to_string(&exported).unwrap();
+        let loaded: Vec<ExportedMessage> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded, exported);
+        assert_eq!(loaded[1].id, MessageId(2));
+        assert_eq!(loaded[1].author_id, UserId(11));
+        assert_eq!(loaded[1].author, "user11");
+        assert_eq!(loaded[1].content, "world");
+        assert_eq!(loaded[1].timestamp, "2018-06-01T12:00:00+00:00");
+        assert!(loaded[1].attachments.is_empty());
+    }
+}
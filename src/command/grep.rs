@@ -0,0 +1,71 @@
+use chrono::FixedOffset;
+
+use helpers::timezone;
+use model::MessageItem;
+use regex::Regex;
+
+/// Lists the messages matching a regex, numbered like the range resolver
+/// so a result can be jumped to by typing its number. Times are shown in `timezone`.
+pub fn results(
+    messages: &[MessageItem],
+    offset: usize,
+    regex: &Regex,
+    timezone: Option<FixedOffset>,
+) -> Vec<String> {
+    messages
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| match item {
+            MessageItem::DiscordMessage(msg) => {
+                let line = msg.content.lines().find(|line| regex.is_match(line))?;
+                Some(format!(
+                    "{}: {} {} {}",
+                    offset + i + 1,
+                    timezone::format(msg.timestamp, timezone, "%H:%M"),
+                    msg.author.name,
+                    line.trim()
+                ))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::results;
+    use chrono::FixedOffset;
+    use helpers::testing::message_item;
+    use regex::Regex;
+
+    #[test]
+    fn lists_matches() {
+        let messages = vec![
+            message_item(1, 10, "hello"),
+            message_item(2, 11, "first\n  needle here"),
+            message_item(3, 10, "nothing"),
+            message_item(4, 12, "Needle"),
+        ];
+
+        let utc = Some(FixedOffset::east(0));
+
+        assert_eq!(
+            results(&messages, 0, &Regex::new("needle").unwrap(), utc),
+            vec!["2: 12:00 user11 needle here"]
+        );
+        assert_eq!(
+            results(&messages, 5, &Regex::new("(?i)needle").unwrap(), utc),
+            vec!["7: 12:00 user11 needle here", "9: 12:00 user12 Needle"]
+        );
+        assert!(results(&messages, 0, &Regex::new("absent").unwrap(), utc).is_empty());
+        // Times are shown as in the message view
+        assert_eq!(
+            results(
+                &messages,
+                0,
+                &Regex::new("needle").unwrap(),
+                Some(FixedOffset::west(8 * 3600))
+            ),
+            vec!["2: 04:00 user11 needle here"]
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Descriptions of every command, shown by `help`
+
+use command::names;
+
+/// Every command with its usage and what it does, `[range]` marking commands that take an address
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("quit", "quit", "Exit dex"),
+    (
+        "nick",
+        "nick <name>",
+        "Change your nickname in the current guild",
+    ),
+    (
+        "clearnick",
+        "clearnick",
+        "Remove your nickname in the current guild",
+    ),
+    ("setchannel", "setchannel <id>", "Switch to a channel by id"),
+    (
+        "togglesidebar",
+        "togglesidebar",
+        "Show or hide the guild sidebar",
+    ),
+    (
+        "save-session",
+        "save-session <name>",
+        "Save the current channel and layout",
+    ),
+    (
+        "load-session",
+        "load-session <name>",
+        "Restore a saved session",
+    ),
+    (
+        "activity",
+        "activity playing|listening <text>, activity clear",
+        "Set or clear your activity",
+    ),
+    ("ping", "ping", "Show gateway and REST latency"),
+    (
+        "filter",
+        "filter /pattern/, filter off",
+        "Highlight text matching a pattern",
+    ),
+    (
+        "grep",
+        "[range]grep /pattern/",
+        "List messages matching a pattern",
+    ),
+    (
+        "move",
+        "[range]move <channel>",
+        "Repost messages in another channel and delete them",
+    ),
+    (
+        "transfer",
+        "[range]transfer <channel>",
+        "Repost messages in another channel",
+    ),
+    (
+        "quoteblock",
+        "[range]quoteblock",
+        "Copy messages to the clipboard as a quote",
+    ),
+    (
+        "channellink",
+        "channellink",
+        "Copy a link to the current channel",
+    ),
+    (
+        "lastfile",
+        "lastfile",
+        "Jump to the newest message with an attachment, again for older",
+    ),
+    (
+        "dms",
+        "dms [n]",
+        "List direct messages, or open the nth one",
+    ),
+    ("accounts", "accounts", "List the configured accounts"),
+    ("switch", "switch <n>", "Log in as the nth account"),
+    (
+        "raw",
+        "[range]raw",
+        "Show a message's content with escapes visible",
+    ),
+    (
+        "unread",
+        "unread",
+        "Switch to the next channel with unread messages, mentions first",
+    ),
+    (
+        "reading",
+        "reading [off]",
+        "Only show messages addressed to you",
+    ),
+    (
+        "selfpurge",
+        "[range]selfpurge",
+        "Delete your own messages, leaving everyone else's",
+    ),
+    ("delete", "[range]delete", "Delete messages"),
+    (
+        "undo",
+        "undo",
+        "Put the messages you last deleted back in view, they stay deleted on Discord",
+    ),
+    (
+        "history",
+        "history [clear]",
+        "List or clear previous commands",
+    ),
+    (
+        "avatar",
+        "avatar @user [open]",
+        "Show a user's avatar url, optionally opening it",
+    ),
+    (
+        "reply_to_current",
+        "reply_to_current on|off",
+        "Send messages as replies to `.`",
+    ),
+    (
+        "send",
+        "send <text>",
+        "Send a message to the current channel",
+    ),
+    (
+        "reply",
+        "[endpoint]reply <text>",
+        "Send a message replying to the addressed message",
+    ),
+    (
+        "react",
+        "[endpoint]react <emoji>",
+        "React to the addressed message",
+    ),
+    (
+        "s",
+        "[endpoint]s/pattern/replacement/[gi]",
+        "Edit your message, replacing a pattern",
+    ),
+    (
+        "compose-edit-external",
+        "[endpoint]compose-edit-external",
+        "Write a message in $EDITOR, or edit your message at an endpoint, also Ctrl-e",
+    ),
+    ("pin", "[endpoint]pin", "Pin the addressed message"),
+    ("unpin", "[endpoint]unpin", "Unpin the addressed message"),
+    (
+        "resend",
+        "resend",
+        "Retry the last message that failed to send",
+    ),
+    (
+        "split-send",
+        "split-send",
+        "Send a message that was too long in several parts",
+    ),
+    (
+        "tz",
+        "tz",
+        "Show or hide the configured timezone in the footer",
+    ),
+    (
+        "back",
+        "back",
+        "Return to the position before the last jump",
+    ),
+    (
+        "peek",
+        "peek <message link or id>",
+        "Show a single message without switching channels",
+    ),
+    (
+        "width",
+        "width <n>|auto",
+        "Limit the width of the message area",
+    ),
+    (
+        "rolecolors",
+        "rolecolors on|off",
+        "Draw nicknames in their role color",
+    ),
+    (
+        "numbers",
+        "numbers on|off",
+        "Show each message's address in the gutter",
+    ),
+    (
+        "now",
+        "now <time>|auto",
+        "Measure relative timestamps from a fixed time, for screenshots",
+    ),
+    (
+        "highlight_self",
+        "highlight_self on|off",
+        "Draw your own messages in their own style",
+    ),
+    (
+        "spoilers",
+        "spoilers on|off|auto",
+        "Show or hide every spoiler",
+    ),
+    (
+        "reveal",
+        "reveal",
+        "Reveal or hide the spoilers of the current message",
+    ),
+    (
+        "theme",
+        "theme export <file>",
+        "Write the current theme to a file",
+    ),
+    (
+        "topreactions",
+        "topreactions [emoji|messages]",
+        "Rank reactions in loaded messages",
+    ),
+    (
+        "pinuser",
+        "pinuser @user|off",
+        "Keep a user's latest message pinned above the input",
+    ),
+    (
+        "colortest",
+        "colortest",
+        "Compare truecolor and 256 colour output",
+    ),
+    (
+        "wjson",
+        "[range]wjson <file>",
+        "Write messages to a file as JSON",
+    ),
+    (
+        "wipe",
+        "wipe",
+        "Delete local state and history, asking for confirmation",
+    ),
+    (
+        "help",
+        "help [command]",
+        "List commands, or show how to use one",
+    ),
+];
+
+/// A command with its aliases, e.g. `delete, d`
+fn names_of(command: &str) -> String {
+    let mut names = vec![command];
+    names.extend(names::aliases(command));
+    names.join(", ")
+}
+
+/// One line per command with its aliases and what it does
+pub fn listing() -> Vec<String> {
+    COMMANDS
+        .iter()
+        .map(|&(command, _, description)| format!("{:<20} {}", names_of(command), description))
+        .collect()
+}
+
+/// Usage and aliases of a single command, which is a canonical name as `names::resolve` returns
+pub fn details(command: &str) -> Option<Vec<String>> {
+    let &(_, usage, description) = COMMANDS.iter().find(|&&(known, _, _)| known == command)?;
+    let mut lines = vec![
+        description.to_owned(),
+        String::new(),
+        format!("Usage: {}", usage),
+    ];
+    let aliases = names::aliases(command);
+    if !aliases.is_empty() {
+        lines.push(format!("Aliases: {}", aliases.join(", ")));
+    }
+    Some(lines)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{details, listing};
+    use command::names;
+
+    #[test]
+    fn every_command_has_help() {
+        for command in names::commands() {
+            assert!(details(command).is_some(), "No help for {}", command);
+        }
+    }
+
+    #[test]
+    fn listing_shows_aliases() {
+        let listing = listing();
+
+        assert!(listing[0].starts_with("quit, q "));
+        assert!(listing.iter().any(|line| line.starts_with("help, ? ")));
+    }
+
+    #[test]
+    fn command_details() {
+        assert_eq!(
+            details("delete"),
+            Some(vec![
+                "Delete messages".to_owned(),
+                String::new(),
+                "Usage: [range]delete".to_owned(),
+                "Aliases: d".to_owned(),
+            ])
+        );
+        assert_eq!(details("ping").unwrap().len(), 3);
+        assert_eq!(details("nope"), None);
+    }
+}
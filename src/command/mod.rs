@@ -1,60 +1,1231 @@
+mod dms;
+mod export;
+mod grep;
+mod help;
+mod names;
+mod peek;
+mod purge;
+mod quote;
+mod range;
+mod raw;
+mod react;
+mod reactions;
+mod substitute;
+mod transfer;
+mod wipe;
+
+use discord::utils;
+use error::RangeError;
+use helpers::clipboard::{Clipboard, SystemClipboard};
+use helpers::clock::{Clock, SystemClock};
+use helpers::cooldown;
+use helpers::opener::{Opener, SystemOpener};
+use model::history;
 use model::Application;
 use model::Event;
+use model::{CommandHistory, MessageItem, Session, State};
+use parsing::cmd::{parse_date, parse_substitution, Range};
 use parsing::parse_cmd;
+use regex::Regex;
+use serenity::http;
+use serenity::model::channel::{Message, ReactionType};
+use serenity::model::gateway::{Game, GameType};
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+use view::{self, Span};
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Characters that are removed from commands and escaped by `raw`
+fn is_unprintable(ch: char) -> bool {
+    ch != '\n' && ch.is_control()
+}
+
+/// Cells in the gradient drawn by `colortest`
+const RAMP_STEPS: usize = 32;
+
+/// Seconds to wait between nickname changes in a guild, to stay clear of Discord's rate limit
+const NICK_COOLDOWN_SECS: u64 = 30;
+
+/// Time left before the nickname can be changed again after a change at `last`
+fn nick_cooldown(last: Option<Instant>, now: Instant) -> Option<Duration> {
+    last.and_then(|last| cooldown::remaining(last, now, Duration::from_secs(NICK_COOLDOWN_SECS)))
+}
+
+/// Removes control characters from a command, keeping newlines for multi-line text
+fn sanitize(cmd: &str) -> String {
+    cmd.chars().filter(|&ch| !is_unprintable(ch)).collect()
+}
 
-pub struct CommandHandler {}
+/// One line description of a message, numbered like the range resolver
+fn summarize(index: usize, item: &MessageItem) -> String {
+    match item {
+        MessageItem::DiscordMessage(msg) => format!(
+            "{}: {}: {}",
+            index + 1,
+            msg.author.name,
+            msg.content.lines().next().unwrap_or_default()
+        ),
+    }
+}
+
+/// Index of the newest message before `before` that has attachments
+fn newest_with_attachment(messages: &[MessageItem], before: usize) -> Option<usize> {
+    messages[..before.min(messages.len())]
+        .iter()
+        .rposition(|item| match item {
+            MessageItem::DiscordMessage(msg) => !msg.attachments.is_empty(),
+        })
+}
+
+/// Extracts the pattern from a `/pattern/` argument
+fn delimited_pattern(arg: &str) -> Option<&str> {
+    if arg.len() >= 2 && arg.starts_with('/') && arg.ends_with('/') {
+        Some(&arg[1..arg.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn format_duration(duration: Option<Duration>) -> String {
+    match duration {
+        Some(duration) => format!(
+            "{}ms",
+            duration.as_secs() * 1000 + u64::from(duration.subsec_nanos()) / 1_000_000
+        ),
+        None => "n/a".to_owned(),
+    }
+}
+
+fn format_latency(gateway: Option<Duration>, rest: Option<Duration>) -> String {
+    format!(
+        "Gateway: {}, REST: {}",
+        format_duration(gateway),
+        format_duration(rest)
+    )
+}
+
+/// Maps the keyword given to `activity` to a presence type
+fn activity_type(keyword: &str) -> Option<GameType> {
+    match keyword {
+        "playing" => Some(GameType::Playing),
+        "listening" => Some(GameType::Listening),
+        _ => None,
+    }
+}
+
+pub struct CommandHandler {
+    /// Message found by the last `lastfile`, so repeating it continues further back
+    last_file: Cell<Option<MessageId>>,
+    /// Set by a first `wipe`, which only runs when repeated
+    wipe_pending: Cell<bool>,
+    /// When the nickname was last changed in each guild
+    nick_changed: RefCell<HashMap<GuildId, Instant>>,
+}
 
 impl CommandHandler {
     pub fn new() -> CommandHandler {
-        CommandHandler {}
+        CommandHandler {
+            last_file: Cell::new(None),
+            wipe_pending: Cell::new(false),
+            nick_changed: RefCell::new(HashMap::new()),
+        }
     }
 
-    // Todo: Add feedback when no arguments are provided
     pub fn execute(&self, app: &Application, cmd: &str) {
         debug!("Running command: {}", cmd);
-        if let Some(cmd) = parse_cmd(cmd) {
-            let split_cmd: Vec<_> = cmd.command.split_whitespace().collect();
-            match split_cmd.get(0).cloned().unwrap_or_default() {
-                "quit" | "q" => app
-                    .context
-                    .read()
-                    .event_channel
-                    .send(Event::ShutdownAll)
-                    .unwrap(),
-                "nick" => {
-                    // Nick
-                    if let Some(new_nick) = split_cmd.get(1) {
-                        debug!("Setting nickname to: {}", new_nick);
+        let cmd = sanitize(cmd);
+        let cmd = match parse_cmd(&cmd) {
+            Ok(cmd) => cmd,
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        // A bare address selects the message it points to
+        if let Some(ref range) = cmd.range {
+            if cmd.command.trim().is_empty() {
+                return self.select(app, range);
+            }
+        }
+        if cmd.name.is_empty() {
+            return;
+        }
+        let name = match names::resolve(cmd.name) {
+            Ok(name) => name,
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        if name != "wipe" {
+            self.wipe_pending.set(false);
+        }
+        // Resolved up front since the command may change the buffer
+        let printed = if cmd.print {
+            self.resolve(app, cmd.range.as_ref())
+                .ok()
+                .map(|span| self.printed_message(app, name == "delete", span))
+        } else {
+            None
+        };
+        match name {
+            "quit" => app
+                .context
+                .read()
+                .event_channel
+                .send(Event::ShutdownAll)
+                .unwrap(),
+            "nick" => match cmd.args.get(0) {
+                Some(new_nick) => {
+                    debug!("Setting nickname to: {}", new_nick);
+                    self.set_nick(app, Some(new_nick));
+                }
+                None => app.send_status("Usage: nick <name>, clearnick to remove it"),
+            },
+            "clearnick" => self.set_nick(app, None),
+            "setchannel" => match cmd.args.get(0) {
+                Some(new_chan) => {
+                    if let Ok(new_chan_id) = new_chan.parse() {
                         app.context
                             .read()
-                            .guild
-                            .map(|guild| guild.edit_nickname(Some(new_nick)));
+                            .event_channel
+                            .send(Event::SetChannel(new_chan_id))
+                            .unwrap()
+                    } else {
+                        app.send_status(format!("Invalid channel id: {}", new_chan))
                     }
                 }
-                "clearnick" | "cnick" => {
-                    app.context
-                        .read()
-                        .guild
-                        .map(|guild| guild.edit_nickname(None));
+                None => app.send_status("Usage: setchannel <id>"),
+            },
+            "togglesidebar" => {
+                // Toggles the preference, the sidebar may be hidden on a narrow terminal anyway
+                let new_state = !app.context.read().guild_sidebar_visible;
+                app.context.write().guild_sidebar_visible = new_state;
+                app.view.message_view.set_show_sidebar(new_state);
+            }
+            "save-session" => match cmd.args.get(0) {
+                Some(name) => {
+                    let anchor = app.view.message_view.anchor();
+                    let session = app.context.read().get_session(anchor);
+                    match session.save(name) {
+                        Ok(()) => app.send_status(format!("Saved session {}", name)),
+                        Err(err) => {
+                            app.send_status(format!("Unable to save session {}: {}", name, err))
+                        }
+                    }
                 }
-                "setchannel" | "schan" => if let Some(new_chan) = split_cmd.get(1) {
-                    if let Ok(new_chan_id) = new_chan.parse() {
+                None => app.send_status("Usage: save-session <name>"),
+            },
+            "load-session" => match cmd.args.get(0) {
+                Some(name) => match Session::load(name) {
+                    Ok(session) => {
+                        self.apply_session(app, session);
+                        app.send_status(format!("Loaded session {}", name));
+                    }
+                    Err(err) => {
+                        app.send_status(format!("Unable to load session {}: {}", name, err))
+                    }
+                },
+                None => app.send_status("Usage: load-session <name>"),
+            },
+            "activity" => match cmd.args.get(0).cloned() {
+                Some("clear") => self.set_activity(app, None),
+                Some(keyword) => match activity_type(keyword) {
+                    Some(kind) => {
+                        let name = cmd.args[1..].join(" ");
+                        if name.is_empty() {
+                            app.send_status(format!("Usage: activity {} <text>", keyword));
+                        } else {
+                            self.set_activity(
+                                app,
+                                Some(Game {
+                                    kind,
+                                    name,
+                                    url: None,
+                                }),
+                            );
+                        }
+                    }
+                    None => app.send_status(format!("Unknown activity type: {}", keyword)),
+                },
+                None => app.send_status("Usage: activity playing|listening <text>, activity clear"),
+            },
+            "ping" => {
+                let gateway = app.discord_client.latency();
+                let event_channel = app.context.read().event_channel.clone();
+                // The REST round trip blocks, so measure it off the main thread
+                thread::spawn(move || {
+                    let start = Instant::now();
+                    let rest = http::get_current_user().ok().map(|_| start.elapsed());
+                    let _ = event_channel.send(Event::Status(format_latency(gateway, rest)));
+                });
+            }
+            "filter" => {
+                if cmd.rest == "off" {
+                    app.view.message_view.set_highlight(None);
+                    app.send_status("Filter off");
+                } else if let Some(pattern) = delimited_pattern(cmd.rest) {
+                    match Regex::new(pattern) {
+                        Ok(regex) => app.view.message_view.set_highlight(Some(regex)),
+                        Err(err) => app.send_status(format!("Invalid filter: {}", err)),
+                    }
+                } else {
+                    app.send_status("Usage: filter /pattern/, filter off");
+                }
+            }
+            "grep" => match delimited_pattern(cmd.rest).map(Regex::new) {
+                Some(Ok(regex)) => match self.resolve_or_all(app, cmd.range.as_ref()) {
+                    Ok((start, end)) => {
+                        let messages = app.view.message_view.messages.borrow();
+                        let timezone = app.context.read().timezone;
+                        let lines = grep::results(&messages[start..=end], start, &regex, timezone);
+                        if lines.is_empty() {
+                            app.send_status(format!("No matches for /{}/", regex));
+                        } else {
+                            app.view.overlay.show(format!("grep /{}/", regex), lines);
+                        }
+                    }
+                    Err(err) => app.send_status(err.to_string()),
+                },
+                Some(Err(err)) => app.send_status(format!("Invalid pattern: {}", err)),
+                None => app.send_status("Usage: grep /pattern/"),
+            },
+            "move" => self.transfer(app, cmd.range.as_ref(), cmd.args.get(0).cloned(), true),
+            "transfer" => self.transfer(app, cmd.range.as_ref(), cmd.args.get(0).cloned(), false),
+            "channellink" => {
+                let channel = app.context.read().channel;
+                match channel {
+                    Some(channel) => {
+                        let link = utils::channel_link(utils::channel_guild_id(channel), channel);
+                        match SystemClipboard.copy(&link) {
+                            Ok(()) => app.send_status(format!("Copied {}", link)),
+                            Err(err) => app.send_status(format!("Unable to copy: {}", err)),
+                        }
+                    }
+                    None => app.send_status("No channel open"),
+                }
+            }
+            "quoteblock" => match self.resolve(app, cmd.range.as_ref()) {
+                Ok((start, end)) => {
+                    let text = {
+                        let messages = app.view.message_view.messages.borrow();
+                        let context = app.context.read();
+                        quote::quote_block(
+                            &messages[start..=end],
+                            &context.timestamp_fmt,
+                            context.timezone,
+                        )
+                    };
+                    match SystemClipboard.copy(&text) {
+                        Ok(()) => app.send_status(format!(
+                            "Copied {} message(s) as a quote",
+                            end - start + 1
+                        )),
+                        Err(err) => app.send_status(format!("Unable to copy: {}", err)),
+                    }
+                }
+                Err(err) => app.send_status(err.to_string()),
+            },
+            "reading" => {
+                if cmd.args.get(0) == Some(&"off") {
+                    app.view.message_view.set_reading(None);
+                } else {
+                    let user = app.context.read().current_user.as_ref().map(|user| user.id);
+                    match user {
+                        Some(user) => app.view.message_view.set_reading(Some(user)),
+                        None => app.send_status("Not logged in yet"),
+                    }
+                }
+            }
+            "lastfile" => self.last_file(app),
+            "dms" => {
+                let entries = dms::cached_dms(
+                    &app.view.message_view.messages.borrow(),
+                    &app.view.message_view.background.borrow(),
+                );
+                match cmd.args.get(0) {
+                    Some(number) => {
+                        let index = number.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+                        match index.and_then(|index| entries.get(index)) {
+                            Some(entry) => {
+                                app.view.overlay.hide();
+                                app.context
+                                    .read()
+                                    .event_channel
+                                    .send(Event::SetChannel(entry.channel_id))
+                                    .unwrap()
+                            }
+                            None => app.send_status(format!("No direct message {}", number)),
+                        }
+                    }
+                    None if entries.is_empty() => app.send_status("No direct messages"),
+                    None => app
+                        .view
+                        .overlay
+                        .show("Direct messages".to_owned(), dms::listing(&entries)),
+                }
+            }
+            "accounts" => {
+                let listing = app.context.read().accounts.listing();
+                app.view.overlay.show("Accounts".to_owned(), listing)
+            }
+            "switch" => {
+                let number = match cmd.args.get(0) {
+                    Some(number) => number,
+                    None => return app.send_status("Usage: switch <n>"),
+                };
+                let index = number.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+                let (target, active) = {
+                    let accounts = &app.context.read().accounts;
+                    let target = index.and_then(|index| accounts.get(index).cloned());
+                    (target, accounts.active_index())
+                };
+                match (index, target) {
+                    (Some(index), Some(ref account)) if index == active => {
+                        app.send_status(format!("Already using {}", account.name))
+                    }
+                    (Some(index), Some(_)) => {
+                        app.view.overlay.hide();
                         app.context
                             .read()
                             .event_channel
-                            .send(Event::SetChannel(new_chan_id))
+                            .send(Event::SwitchAccount(index))
                             .unwrap()
-                    } else {
-                        // Invalid id
                     }
+                    _ => app.send_status(format!("No account {}", number)),
+                }
+            }
+            "peek" => match cmd.args.get(0).map(|arg| peek::parse_target(arg)) {
+                Some(Ok((channel, message))) => {
+                    let channel = match channel.or(app.context.read().channel) {
+                        Some(channel) => channel,
+                        None => return app.send_status("No channel selected"),
+                    };
+                    match channel.message(message) {
+                        Ok(msg) => {
+                            let lines = {
+                                let context = app.context.read();
+                                peek::lines(&msg, &context.timestamp_fmt, context.timezone)
+                            };
+                            app.view
+                                .overlay
+                                .show(format!("#{}", utils::channel_name(channel)), lines);
+                        }
+                        Err(err) => app.send_status(format!("Unable to fetch message: {}", err)),
+                    }
+                }
+                Some(Err(err)) => app.send_status(err.to_string()),
+                None => app.send_status("Usage: peek <message link or id>"),
+            },
+            "topreactions" => {
+                let messages = app.view.message_view.messages.borrow();
+                let (title, lines) = match cmd.args.get(0).cloned() {
+                    Some("messages") => (
+                        "Most reacted messages",
+                        reactions::message_leaderboard(&messages),
+                    ),
+                    Some("emoji") | None => (
+                        "Most used reactions",
+                        reactions::emoji_leaderboard(&messages),
+                    ),
+                    Some(_) => return app.send_status("Usage: topreactions [emoji|messages]"),
+                };
+                if lines.is_empty() {
+                    app.send_status("No reactions in loaded messages");
+                } else {
+                    app.view.overlay.show(title.to_owned(), lines);
+                }
+            }
+            "raw" => match self.resolve(app, cmd.range.as_ref()) {
+                Ok((start, end)) if start == end => {
+                    let messages = app.view.message_view.messages.borrow();
+                    match messages[start] {
+                        MessageItem::DiscordMessage(ref msg) => app
+                            .view
+                            .overlay
+                            .show(format!("Raw {}", msg.id), raw::raw_lines(&msg.content)),
+                    }
+                }
+                Ok(_) => app.send_status("raw shows a single message"),
+                Err(err) => app.send_status(err.to_string()),
+            },
+            "send" => {
+                if cmd.rest.is_empty() {
+                    app.send_status("Usage: send <text>");
+                } else {
+                    app.context
+                        .read()
+                        .event_channel
+                        .send(Event::UserMessage(cmd.rest.to_owned()))
+                        .unwrap();
+                }
+            }
+            "reply" => self.reply(app, cmd.range.as_ref(), cmd.rest),
+            "react" => self.react(app, cmd.range.as_ref(), cmd.args.get(0)),
+            "s" => self.substitute(app, cmd.range.as_ref(), cmd.rest),
+            "compose-edit-external" => self.edit_external(app, cmd.range.as_ref()),
+            "pin" => self.set_pinned(app, cmd.range.as_ref(), true),
+            "unpin" => self.set_pinned(app, cmd.range.as_ref(), false),
+            "wipe" => self.wipe(app),
+            "help" => match cmd.args.get(0) {
+                Some(name) => match names::resolve(name) {
+                    Ok(command) => match help::details(command) {
+                        Some(lines) => app.view.overlay.show(format!("help {}", command), lines),
+                        None => app.send_status(format!("No help for {}", command)),
+                    },
+                    Err(err) => app.send_status(err.to_string()),
+                },
+                None => app
+                    .view
+                    .overlay
+                    .show("Commands".to_owned(), help::listing()),
+            },
+            "unread" => {
+                let next = app
+                    .view
+                    .message_view
+                    .background
+                    .borrow()
+                    .unread_channels()
+                    .first()
+                    .map(|&(channel_id, _, _)| channel_id);
+                match next {
+                    Some(channel_id) => app
+                        .context
+                        .read()
+                        .event_channel
+                        .send(Event::SetChannel(channel_id))
+                        .unwrap(),
+                    None => app.send_status("No unread channels"),
+                }
+            }
+            "selfpurge" => self.self_purge(app, cmd.range.as_ref()),
+            "delete" => self.delete(app, cmd.range.as_ref()),
+            "history" => {
+                if cmd.args.get(0) == Some(&"clear") {
+                    app.command_history.borrow_mut().clear();
+                    app.send_status("History cleared");
+                } else {
+                    let lines = history::listing(app.command_history.borrow().entries());
+                    app.view.overlay.show("History".to_owned(), lines);
+                }
+            }
+            "avatar" => {
+                if let Some(arg) = cmd.args.get(0) {
+                    let guild = app.context.read().current_guild();
+                    match utils::find_user(guild, arg) {
+                        Some(user) => {
+                            let url = utils::avatar_url(
+                                user.id,
+                                user.avatar.as_ref().map(String::as_str),
+                                user.discriminator,
+                            );
+                            if cmd.args.get(1) == Some(&"open") {
+                                if let Err(err) = SystemOpener.open(&url) {
+                                    return app.send_status(format!(
+                                        "Unable to open avatar {}: {}",
+                                        url, err
+                                    ));
+                                }
+                            }
+                            app.send_status(url);
+                        }
+                        None => app.send_status(format!("Unknown user: {}", arg)),
+                    }
+                } else {
+                    app.send_status("Usage: avatar <user> [open]");
+                }
+            }
+            "reply_to_current" => match cmd.args.get(0).cloned() {
+                Some("on") => {
+                    app.context.write().reply_to_current = true;
+                    app.send_status("Replying to the current message");
+                }
+                Some("off") => {
+                    app.context.write().reply_to_current = false;
+                    app.send_status("Not replying to the current message");
+                }
+                _ => app.send_status("Usage: reply_to_current on|off"),
+            },
+            "resend" => app
+                .context
+                .read()
+                .event_channel
+                .send(Event::Resend)
+                .unwrap(),
+            "split-send" => app
+                .context
+                .read()
+                .event_channel
+                .send(Event::SplitSend)
+                .unwrap(),
+            "tz" => {
+                if app.context.read().timezone_label.is_some() {
+                    app.view.footer.toggle_timezone();
+                } else {
+                    app.send_status("Timestamps are in local time");
+                }
+            }
+            "back" => {
+                if !app.view.message_view.jump_back() {
+                    app.send_status("No previous position");
+                }
+            }
+            "undo" => match app.view.message_view.undo_delete() {
+                0 => app.send_status("Nothing to undo"),
+                1 => app.send_status("Restored 1 message in view"),
+                count => app.send_status(format!("Restored {} messages in view", count)),
+            },
+            "width" => match cmd.args.get(0).cloned() {
+                Some("auto") => app.view.set_width_override(None),
+                Some(width) => match width.parse() {
+                    Ok(width) => app.view.set_width_override(Some(width)),
+                    Err(_) => app.send_status(format!("Invalid width: {}", width)),
                 },
-                "togglesidebar" | "tbar" => {
-                    let new_state = !app.view.message_view.showing_sidebar();
-                    app.context.write().guild_sidebar_visible = new_state;
-                    app.view.message_view.set_show_sidebar(new_state);
+                None => app.send_status("Usage: width <n>|auto"),
+            },
+            "highlight_self" => match cmd.args.get(0).cloned() {
+                Some(setting @ "on") | Some(setting @ "off") => {
+                    app.context.write().highlight_self = setting == "on";
+                    app.view.message_view.mark_dirty();
+                }
+                _ => app.send_status("Usage: highlight_self on|off"),
+            },
+            "now" => match cmd.args.get(0).cloned() {
+                Some("auto") => {
+                    app.context.write().clock = None;
+                    app.view.message_view.mark_dirty();
+                }
+                Some(_) => match parse_date(cmd.rest) {
+                    Some(time) => {
+                        app.context.write().clock = Some(time);
+                        app.view.message_view.mark_dirty();
+                    }
+                    None => app.send_status(format!("Invalid time: {}", cmd.rest)),
+                },
+                None => app.send_status("Usage: now <time>|auto"),
+            },
+            "numbers" => match cmd.args.get(0).cloned() {
+                Some(setting @ "on") | Some(setting @ "off") => {
+                    app.view.message_view.set_numbers(setting == "on")
+                }
+                _ => app.send_status("Usage: numbers on|off"),
+            },
+            "rolecolors" => match cmd.args.get(0).cloned() {
+                Some(setting @ "on") | Some(setting @ "off") => {
+                    app.context.write().role_colors = setting == "on"
+                }
+                _ => app.send_status("Usage: rolecolors on|off"),
+            },
+            "spoilers" => match cmd.args.get(0).cloned() {
+                Some("on") => app.view.message_view.set_spoilers(Some(true)),
+                Some("off") => app.view.message_view.set_spoilers(Some(false)),
+                Some("auto") => app.view.message_view.set_spoilers(None),
+                _ => app.send_status("Usage: spoilers on|off|auto"),
+            },
+            "reveal" => app.view.message_view.toggle_reveal(),
+            "theme" => match (cmd.args.get(0).cloned(), cmd.args.get(1)) {
+                (Some("export"), Some(path)) => {
+                    let path = export::expand_home(path);
+                    let theme = app.context.read().theme.clone();
+                    match theme.export(&path) {
+                        Ok(()) => app.send_status(format!("Wrote theme to {}", path.display())),
+                        Err(err) => app.send_status(format!(
+                            "Unable to write theme to {}: {}",
+                            path.display(),
+                            err
+                        )),
+                    }
+                }
+                _ => app.send_status("Usage: theme export <file>"),
+            },
+            "pinuser" => match cmd.args.get(0).cloned() {
+                Some("off") => app.view.pinned.set_user(None),
+                Some(arg) => {
+                    let guild = app.context.read().current_guild();
+                    match utils::find_user(guild, arg) {
+                        Some(user) => app.view.pinned.set_user(Some(user.id)),
+                        None => app.send_status(format!("Unknown user: {}", arg)),
+                    }
+                }
+                None => app.send_status("Usage: pinuser @user|off"),
+            },
+            "colortest" => {
+                let sample = {
+                    let context = app.context.read();
+                    match (context.current_guild(), context.current_user.as_ref()) {
+                        (Some(guild), Some(user)) => utils::member_colour(guild, user.id)
+                            .map(|colour| view::colour_samples(&user.name, colour)),
+                        _ => None,
+                    }
+                };
+                let detected = if app.view.message_view.truecolor() {
+                    "Drawing in truecolor, set DEX_TRUECOLOR=0 if the top line looks wrong"
+                } else {
+                    "Drawing in 256 colours, set DEX_TRUECOLOR=1 if the top line looks smooth"
+                };
+                let mut lines = vec![vec![Span::plain(detected.to_owned())]];
+                lines.extend(view::colour_ramp(RAMP_STEPS));
+                lines.extend(sample);
+                app.view.overlay.show_spans("Color test".to_owned(), lines);
+            }
+            "wjson" => match cmd.args.get(0) {
+                Some(path) => {
+                    let path = export::expand_home(path);
+                    match self.resolve_or_all(app, cmd.range.as_ref()) {
+                        Ok((start, end)) => {
+                            let messages = app.view.message_view.messages.borrow();
+                            match export::write_json(&path, &messages[start..=end]) {
+                                Ok(()) => app.send_status(format!(
+                                    "Wrote {} message(s) to {}",
+                                    end - start + 1,
+                                    path.display()
+                                )),
+                                Err(err) => app.send_status(format!(
+                                    "Unable to write {}: {}",
+                                    path.display(),
+                                    err
+                                )),
+                            }
+                        }
+                        Err(err) => app.send_status(err.to_string()),
+                    }
+                }
+                None => app.send_status("Usage: wjson <file>"),
+            },
+            _ => {}
+        }
+        if let Some(printed) = printed {
+            self.print_current(app, printed);
+        }
+    }
+
+    /// The message that will be current after a command on `span` with ed's `p` suffix
+    fn printed_message(
+        &self,
+        app: &Application,
+        deleted: bool,
+        span: (usize, usize),
+    ) -> Option<MessageId> {
+        let messages = app.view.message_view.messages.borrow();
+        range::current_after(deleted, span, messages.len()).map(|index| match messages[index] {
+            MessageItem::DiscordMessage(ref msg) => msg.id,
+        })
+    }
+
+    /// Selects and shows the message current after a command with ed's `p` suffix
+    fn print_current(&self, app: &Application, printed: Option<MessageId>) {
+        let message_view = &app.view.message_view;
+        let messages = message_view.messages.borrow();
+        match printed.and_then(|id| message_view.index_of(id)) {
+            Some(index) => {
+                message_view.set_current(index);
+                message_view.scroll_to(index);
+                app.send_status(summarize(index, &messages[index]));
+            }
+            None => app.send_status("No current message"),
+        }
+    }
+
+    /// Resolves a command's range, defaulting to the whole buffer like ed's `w`
+    fn resolve_or_all(
+        &self,
+        app: &Application,
+        range: Option<&Range>,
+    ) -> Result<(usize, usize), RangeError> {
+        if range.is_some() {
+            return self.resolve(app, range);
+        }
+        match app.view.message_view.messages.borrow().len() {
+            0 => Err(RangeError::Empty),
+            len => Ok((0, len - 1)),
+        }
+    }
+
+    /// Resolves a command's range, defaulting to the current message like ed
+    fn resolve(
+        &self,
+        app: &Application,
+        range: Option<&Range>,
+    ) -> Result<(usize, usize), RangeError> {
+        let message_view = &app.view.message_view;
+        let current = message_view.current_index();
+        let messages = message_view.messages.borrow();
+        match range {
+            Some(range) => {
+                range::resolve_range(range, &messages, current, &message_view.nicknames())
+            }
+            None if messages.is_empty() => Err(RangeError::Empty),
+            None => Ok((current, current)),
+        }
+    }
+
+    /// Resolves a `#name`, `<#id>` or id argument to a channel
+    fn resolve_channel(&self, app: &Application, arg: &str) -> Option<ChannelId> {
+        match transfer::parse_target(arg)? {
+            transfer::ChannelTarget::Id(channel_id) => Some(channel_id),
+            transfer::ChannelTarget::Name(name) => {
+                let guild = app.context.read().current_guild()?;
+                utils::find_channel(guild, name)
+            }
+        }
+    }
+
+    fn transfer(
+        &self,
+        app: &Application,
+        range: Option<&Range>,
+        target: Option<&str>,
+        delete_source: bool,
+    ) {
+        let target = match target.and_then(|target| self.resolve_channel(app, target)) {
+            Some(target) => target,
+            None => return app.send_status("Unknown target channel"),
+        };
+        let steps = match self.resolve(app, range) {
+            Ok((start, end)) => {
+                let messages = app.view.message_view.messages.borrow();
+                transfer::plan(&messages[start..=end], target, delete_source)
+            }
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        let report = transfer::run(steps);
+        app.send_status(report.summary(if delete_source { "Moved" } else { "Copied" }));
+    }
+
+    /// Jumps to the newest message with attachments, or the next older one when repeated
+    fn last_file(&self, app: &Application) {
+        let message_view = &app.view.message_view;
+        let current = message_view.current_message();
+        let before = if current.is_some() && current == self.last_file.get() {
+            message_view.current_index()
+        } else {
+            message_view.messages.borrow().len()
+        };
+        let found = newest_with_attachment(&message_view.messages.borrow(), before);
+        match found {
+            Some(index) => {
+                message_view.select(index);
+                self.last_file.set(message_view.current_message());
+                app.send_status(summarize(index, &message_view.messages.borrow()[index]));
+            }
+            None => app.send_status("No older messages with attachments"),
+        }
+    }
+
+    /// Lists the local state `wipe` removes, removing it when repeated right after
+    fn wipe(&self, app: &Application) {
+        let plan = match (State::path(), CommandHistory::path()) {
+            (Ok(state_file), Ok(history_file)) => wipe::plan(state_file, history_file),
+            (Err(err), _) | (_, Err(err)) => return app.send_status(err.to_string()),
+        };
+        if !self.wipe_pending.replace(true) {
+            let lines = plan.iter().map(ToString::to_string).collect();
+            app.view.overlay.show("Wipe local state".to_owned(), lines);
+            return app.send_status("Run wipe again to confirm");
+        }
+        self.wipe_pending.set(false);
+        app.view.overlay.hide();
+        for step in plan {
+            match step {
+                wipe::LocalState::MessageBuffers => app.view.message_view.clear(),
+                wipe::LocalState::StateFile(path) => {
+                    app.context.write().persist_state = false;
+                    match fs::remove_file(&path) {
+                        Err(ref err) if err.kind() != io::ErrorKind::NotFound => {
+                            return app.send_status(format!(
+                                "Unable to remove {}: {}",
+                                path.display(),
+                                err
+                            ))
+                        }
+                        _ => {}
+                    }
+                }
+                step => wipe::Memory {
+                    history: &mut app.command_history.borrow_mut(),
+                    outbox: &mut app.outbox.borrow_mut(),
+                    anchors: &mut app.context.write().anchors,
+                }
+                .clear(&step),
+            }
+        }
+        app.send_status("Wiped local state");
+    }
+
+    /// Deletes messages, in bulk in the channels `can_bulk` allows, dropping them from the buffer
+    /// right away. Returns how many were deleted and the errors for the rest.
+    fn purge<F: Fn(ChannelId) -> bool>(
+        &self,
+        app: &Application,
+        targets: &[(ChannelId, MessageId)],
+        can_bulk: F,
+    ) -> (usize, Vec<String>) {
+        let (deleted, errors) = purge::run(targets, SystemClock.now(), can_bulk);
+        for (channel, messages) in purge::by_channel(&deleted) {
+            app.view.message_view.remove_for_undo(channel, &messages);
+        }
+        (deleted.len(), errors)
+    }
+
+    /// Deletes every message in a range, the current message without one
+    fn delete(&self, app: &Application, range: Option<&Range>) {
+        let targets = match self.resolve(app, range) {
+            Ok((start, end)) => {
+                let messages = app.view.message_view.messages.borrow();
+                purge::all_messages(&messages[start..=end])
+            }
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        let user = app.context.read().current_user.as_ref().map(|user| user.id);
+        let (deleted, errors) = self.purge(app, &targets, |channel| {
+            user.map_or(false, |user| utils::can_bulk_delete(channel, user))
+        });
+        let mut summary = format!("Deleted {} message(s)", deleted);
+        if !errors.is_empty() {
+            summary += &format!(", {} error(s): {}", errors.len(), errors.join("; "));
+        }
+        app.send_status(summary);
+    }
+
+    /// Deletes the current user's messages in a range, leaving everyone else's
+    fn self_purge(&self, app: &Application, range: Option<&Range>) {
+        let user_id = match app.context.read().current_user {
+            Some(ref user) => user.id,
+            None => return app.send_status("Not logged in yet"),
+        };
+        let (total, targets) = match self.resolve(app, range) {
+            Ok((start, end)) => {
+                let messages = app.view.message_view.messages.borrow();
+                let targets = purge::own_messages(&messages[start..=end], user_id);
+                (end - start + 1, targets)
+            }
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        // Deleting one's own messages needs no permission, bulk deleting them does
+        let (deleted, errors) = self.purge(app, &targets, purge::never_bulk);
+        let mut summary = format!(
+            "Deleted {} message(s), skipped {} from others",
+            deleted,
+            total - targets.len()
+        );
+        if !errors.is_empty() {
+            summary += &format!(", {} error(s): {}", errors.len(), errors.join("; "));
+        }
+        app.send_status(summary);
+    }
+
+    /// Resolves a single endpoint to its message, the current message without one, reporting
+    /// ranges and unresolvable endpoints in the footer
+    fn resolve_single(
+        &self,
+        app: &Application,
+        range: Option<&Range>,
+        command: &str,
+    ) -> Option<Message> {
+        match range {
+            None | Some(Range::Single(_)) => {}
+            Some(_) => {
+                app.send_status(format!("{} takes a single message", command));
+                return None;
+            }
+        }
+        match self.resolve(app, range) {
+            Ok((index, _)) => match app.view.message_view.messages.borrow()[index] {
+                MessageItem::DiscordMessage(ref msg) => Some((**msg).clone()),
+            },
+            Err(err) => {
+                app.send_status(err.to_string());
+                None
+            }
+        }
+    }
+
+    /// Replies to the message at a single endpoint, the current message without one
+    fn reply(&self, app: &Application, range: Option<&Range>, content: &str) {
+        if content.is_empty() {
+            return app.send_status("Usage: [endpoint]reply <text>");
+        }
+        if let Some(msg) = self.resolve_single(app, range, "reply") {
+            app.context
+                .read()
+                .event_channel
+                .send(Event::Reply(msg.channel_id, msg.id, content.to_owned()))
+                .unwrap();
+        }
+    }
+
+    /// Reacts to the message at a single endpoint, the current message without one
+    fn react(&self, app: &Application, range: Option<&Range>, emoji: Option<&&str>) {
+        let emoji = match emoji {
+            Some(emoji) => emoji,
+            None => return app.send_status("Usage: [endpoint]react <emoji>"),
+        };
+        let msg = match self.resolve_single(app, range, "react") {
+            Some(msg) => msg,
+            None => return,
+        };
+        // `:name:` finds the current guild's custom emoji
+        let guild = app
+            .context
+            .read()
+            .current_guild()
+            .and_then(|guild| guild.find());
+        let custom = |name: &str| {
+            let guild = guild.as_ref()?.read();
+            let emoji = guild.emojis.values().find(|emoji| emoji.name == name)?;
+            Some(ReactionType::Custom {
+                animated: emoji.animated,
+                id: emoji.id,
+                name: Some(emoji.name.clone()),
+            })
+        };
+        let reaction = match react::parse_emoji(emoji, custom) {
+            Ok(reaction) => reaction,
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        match msg.channel_id.create_reaction(msg.id, reaction.clone()) {
+            Ok(()) => app.view.message_view.add_reaction(msg.id, reaction),
+            Err(err) => app.send_status(format!("Unable to react: {}", err)),
+        }
+    }
+
+    /// Pins or unpins the message at a single endpoint, the current message without one
+    fn set_pinned(&self, app: &Application, range: Option<&Range>, pinned: bool) {
+        let command = if pinned { "pin" } else { "unpin" };
+        let msg = match self.resolve_single(app, range, command) {
+            Some(msg) => msg,
+            None => return,
+        };
+        let result = if pinned {
+            msg.channel_id.pin(msg.id)
+        } else {
+            msg.channel_id.unpin(msg.id)
+        };
+        match result {
+            Ok(()) => app.view.message_view.set_pinned(msg.id, pinned),
+            // Such as the channel having 50 pins already
+            Err(err) => app.send_status(format!("Unable to {}: {}", command, err)),
+        }
+    }
+
+    /// Edits the current user's message at a single endpoint with ed's `s/pattern/replacement/`
+    fn substitute(&self, app: &Application, range: Option<&Range>, text: &str) {
+        let substitution = match parse_substitution(text) {
+            Ok(substitution) => substitution,
+            Err(err) => return app.send_status(err.to_string()),
+        };
+        let regex = match substitute::compile(&substitution) {
+            Ok(regex) => regex,
+            Err(err) => return app.send_status(format!("Invalid pattern: {}", err)),
+        };
+        let msg = match self.resolve_single(app, range, "s") {
+            Some(msg) => msg,
+            None => return,
+        };
+        let user = app.context.read().current_user.as_ref().map(|user| user.id);
+        if Some(msg.author.id) != user {
+            return app.send_status("Only your own messages can be edited");
+        }
+        let edited = match substitute::apply(&regex, &substitution, &msg.content) {
+            Some(edited) => edited,
+            None => return app.send_status(format!("No match for {}", substitution.pattern)),
+        };
+        match msg.channel_id.edit_message(msg.id, |m| m.content(&edited)) {
+            Ok(msg) => app.view.message_view.apply_edit(&msg),
+            Err(err) => app.send_status(format!("Unable to edit message: {}", err)),
+        }
+    }
+
+    /// Opens `$EDITOR` on the message being composed, or on the user's message at an endpoint
+    fn edit_external(&self, app: &Application, range: Option<&Range>) {
+        let msg = match range {
+            None => None,
+            Some(_) => match self.resolve_single(app, range, "compose-edit-external") {
+                Some(msg) => Some(msg),
+                None => return,
+            },
+        };
+        if let Some(ref msg) = msg {
+            let user = app.context.read().current_user.as_ref().map(|user| user.id);
+            if Some(msg.author.id) != user {
+                return app.send_status("Only your own messages can be edited");
+            }
+        }
+        app.context
+            .read()
+            .event_channel
+            .send(Event::ExternalEdit(msg.map(Box::new)))
+            .unwrap()
+    }
+
+    /// Changes the current user's nickname unless it was changed too recently in this guild
+    fn set_nick(&self, app: &Application, nick: Option<&str>) {
+        let guild = match app.context.read().current_guild() {
+            Some(guild) => guild,
+            None => return app.send_status("No guild for the current channel"),
+        };
+        let now = Instant::now();
+        let last = self.nick_changed.borrow().get(&guild).cloned();
+        if let Some(remaining) = nick_cooldown(last, now) {
+            return app.send_status(format!(
+                "Nickname changed recently, wait {}s",
+                cooldown::seconds_left(remaining)
+            ));
+        }
+        match guild.edit_nickname(nick) {
+            Ok(()) => {
+                self.nick_changed.borrow_mut().insert(guild, now);
+            }
+            Err(err) => app.send_status(format!("Unable to change nickname: {}", err)),
+        }
+    }
+
+    fn set_activity(&self, app: &Application, activity: Option<Game>) {
+        app.context.write().activity = activity.clone();
+        app.discord_client.set_game(activity);
+    }
+
+    fn select(&self, app: &Application, range: &Range) {
+        let message_view = &app.view.message_view;
+        let messages = message_view.messages.borrow();
+        let current = message_view.current_index();
+        match range::resolve_range(range, &messages, current, &message_view.nicknames()) {
+            Ok((_, end)) => {
+                message_view.select(end);
+                app.send_status(summarize(end, &messages[end]));
+            }
+            Err(err) => app.send_status(err.to_string()),
+        }
+    }
+
+    fn apply_session(&self, app: &Application, session: Session) {
+        let current = {
+            let mut context = app.context.write();
+            context.guild = session.guild;
+            context.guild_sidebar_visible = session.guild_sidebar_visible;
+            if let Some(theme) = session.theme {
+                context.theme = theme;
+            }
+            context.channel
+        };
+        app.view
+            .message_view
+            .set_show_sidebar(session.guild_sidebar_visible);
+        match session.channel {
+            // Switching would record the current position over the session's
+            Some(channel) if Some(channel) == current => {
+                if let Some(anchor) = session.anchor {
+                    app.view.message_view.restore_anchor(anchor);
                 }
-                _ => {}
             }
+            Some(channel) => {
+                let mut context = app.context.write();
+                match session.anchor {
+                    Some(anchor) => context.anchors.insert(channel, anchor),
+                    None => context.anchors.remove(&channel),
+                };
+                context
+                    .event_channel
+                    .send(Event::SetChannel(channel))
+                    .unwrap()
+            }
+            None => {}
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        activity_type, delimited_pattern, format_latency, newest_with_attachment, nick_cooldown,
+        sanitize, summarize, NICK_COOLDOWN_SECS,
+    };
+    use helpers::testing::{attachment_value, message_from_value, message_item, message_value};
+    use model::MessageItem;
+    use serenity::model::gateway::GameType;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn activity_keywords() {
+        assert_eq!(activity_type("playing"), Some(GameType::Playing));
+        assert_eq!(activity_type("listening"), Some(GameType::Listening));
+        assert_eq!(activity_type("dancing"), None);
+        assert_eq!(activity_type("clear"), None);
+    }
+
+    #[test]
+    fn latency_formatting() {
+        assert_eq!(
+            format_latency(
+                Some(Duration::from_millis(42)),
+                Some(Duration::new(1, 250_000_000))
+            ),
+            "Gateway: 42ms, REST: 1250ms"
+        );
+        assert_eq!(
+            format_latency(None, Some(Duration::from_millis(7))),
+            "Gateway: n/a, REST: 7ms"
+        );
+    }
+
+    #[test]
+    fn filter_pattern() {
+        assert_eq!(delimited_pattern("/foo bar/"), Some("foo bar"));
+        assert_eq!(delimited_pattern("//"), Some(""));
+        assert_eq!(delimited_pattern("/foo"), None);
+        assert_eq!(delimited_pattern("/"), None);
+    }
+
+    #[test]
+    fn selection_summary() {
+        let item = message_item(5, 10, "hello\nworld");
+
+        assert_eq!(summarize(4, &item), "5: user10: hello");
+    }
+
+    #[test]
+    fn control_characters_are_stripped() {
+        assert_eq!(sanitize("nick\u{1b}[31mbob"), "nick[31mbob");
+        assert_eq!(sanitize("send a\tb\u{7}"), "send ab");
+        assert_eq!(
+            sanitize("send line one\nline two"),
+            "send line one\nline two"
+        );
+        assert_eq!(sanitize("nick bøb"), "nick bøb");
+    }
+
+    #[test]
+    fn newest_attachment() {
+        let with_file = |id| {
+            let mut value = message_value(id, 10, "file");
+            value["attachments"] = json!([attachment_value(id, "a.png")]);
+            MessageItem::DiscordMessage(Box::new(message_from_value(value)))
+        };
+        let messages = vec![
+            with_file(1),
+            message_item(2, 10, "text"),
+            with_file(3),
+            message_item(4, 10, "text"),
+        ];
+
+        assert_eq!(newest_with_attachment(&messages, messages.len()), Some(2));
+        // Repeating continues before the previous result
+        assert_eq!(newest_with_attachment(&messages, 2), Some(0));
+        assert_eq!(newest_with_attachment(&messages, 0), None);
+        assert_eq!(newest_with_attachment(&messages[3..], 1), None);
+    }
+
+    #[test]
+    fn nick_cooldown_after_change() {
+        let changed = Instant::now();
+        let cooldown = Duration::from_secs(NICK_COOLDOWN_SECS);
+
+        assert_eq!(nick_cooldown(None, changed), None);
+        assert_eq!(nick_cooldown(Some(changed), changed), Some(cooldown));
+        assert_eq!(
+            nick_cooldown(Some(changed), changed + Duration::from_secs(10)),
+            Some(cooldown - Duration::from_secs(10))
+        );
+        assert_eq!(nick_cooldown(Some(changed), changed + cooldown), None);
+    }
+}
@@ -0,0 +1,143 @@
+//! Command names, resolving unambiguous prefixes like ed
+
+use error::CommandError;
+
+/// Every name a command can be typed as, with the command it runs
+const NAMES: &[(&str, &str)] = &[
+    ("quit", "quit"),
+    ("q", "quit"),
+    ("nick", "nick"),
+    ("clearnick", "clearnick"),
+    ("cnick", "clearnick"),
+    ("setchannel", "setchannel"),
+    ("schan", "setchannel"),
+    ("togglesidebar", "togglesidebar"),
+    ("tbar", "togglesidebar"),
+    ("save-session", "save-session"),
+    ("load-session", "load-session"),
+    ("activity", "activity"),
+    ("ping", "ping"),
+    ("filter", "filter"),
+    ("grep", "grep"),
+    ("move", "move"),
+    ("m", "move"),
+    ("transfer", "transfer"),
+    ("t", "transfer"),
+    ("quoteblock", "quoteblock"),
+    ("channellink", "channellink"),
+    ("lastfile", "lastfile"),
+    ("dms", "dms"),
+    ("accounts", "accounts"),
+    ("switch", "switch"),
+    ("raw", "raw"),
+    ("unread", "unread"),
+    ("reading", "reading"),
+    ("selfpurge", "selfpurge"),
+    ("delete", "delete"),
+    ("d", "delete"),
+    ("undo", "undo"),
+    ("u", "undo"),
+    ("history", "history"),
+    ("avatar", "avatar"),
+    ("reply_to_current", "reply_to_current"),
+    ("reply", "reply"),
+    ("react", "react"),
+    ("s", "s"),
+    ("compose-edit-external", "compose-edit-external"),
+    ("pin", "pin"),
+    ("unpin", "unpin"),
+    ("resend", "resend"),
+    ("send", "send"),
+    ("split-send", "split-send"),
+    ("tz", "tz"),
+    ("back", "back"),
+    ("peek", "peek"),
+    ("width", "width"),
+    ("rolecolors", "rolecolors"),
+    ("numbers", "numbers"),
+    ("now", "now"),
+    ("highlight_self", "highlight_self"),
+    ("spoilers", "spoilers"),
+    ("reveal", "reveal"),
+    ("theme", "theme"),
+    ("topreactions", "topreactions"),
+    ("pinuser", "pinuser"),
+    ("colortest", "colortest"),
+    ("wjson", "wjson"),
+    ("wipe", "wipe"),
+    ("help", "help"),
+    ("?", "help"),
+];
+
+/// Resolves a typed command name, which may be any unambiguous prefix of a command
+pub fn resolve(name: &str) -> Result<&'static str, CommandError> {
+    if let Some(&(_, command)) = NAMES.iter().find(|&&(known, _)| known == name) {
+        return Ok(command);
+    }
+    let mut candidates: Vec<&'static str> = NAMES
+        .iter()
+        .filter(|&&(known, _)| !name.is_empty() && known.starts_with(name))
+        .map(|&(_, command)| command)
+        .collect();
+    candidates.dedup();
+    match candidates.len() {
+        0 => Err(CommandError::Unknown(name.to_owned())),
+        1 => Ok(candidates[0]),
+        _ => Err(CommandError::Ambiguous(
+            name.to_owned(),
+            candidates.join(", "),
+        )),
+    }
+}
+
+/// Every command, each once
+pub fn commands() -> Vec<&'static str> {
+    let mut commands: Vec<_> = NAMES.iter().map(|&(_, command)| command).collect();
+    commands.dedup();
+    commands
+}
+
+/// Other names a command can be typed as
+pub fn aliases(command: &str) -> Vec<&'static str> {
+    NAMES
+        .iter()
+        .filter(|&&(name, known)| known == command && name != command)
+        .map(|&(name, _)| name)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::resolve;
+    use error::CommandError;
+
+    #[test]
+    fn exact_names_and_aliases() {
+        assert_eq!(resolve("quit"), Ok("quit"));
+        assert_eq!(resolve("q"), Ok("quit"));
+        assert_eq!(resolve("t"), Ok("transfer"));
+        assert_eq!(resolve("tz"), Ok("tz"));
+        assert_eq!(resolve("reply"), Ok("reply"));
+        assert_eq!(resolve("u"), Ok("undo"));
+    }
+
+    #[test]
+    fn unique_prefix() {
+        assert_eq!(resolve("togg"), Ok("togglesidebar"));
+        assert_eq!(resolve("fil"), Ok("filter"));
+        // Aliases of the same command are not ambiguous
+        assert_eq!(resolve("tr"), Ok("transfer"));
+    }
+
+    #[test]
+    fn ambiguous_prefix() {
+        assert_eq!(
+            resolve("re"),
+            Err(CommandError::Ambiguous(
+                "re".to_owned(),
+                "reading, reply_to_current, reply, react, resend, reveal".to_owned()
+            ))
+        );
+        assert_eq!(resolve("zzz"), Err(CommandError::Unknown("zzz".to_owned())));
+    }
+}
@@ -0,0 +1,113 @@
+//! Showing a single message from anywhere for the `peek` command
+
+use chrono::FixedOffset;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, MessageId};
+
+use error::CommandError;
+use helpers::timezone;
+
+/// Hosts message links are copied from
+const LINK_PREFIXES: &[&str] = &[
+    "https://discordapp.com/channels/",
+    "https://discord.com/channels/",
+    "https://ptb.discordapp.com/channels/",
+    "https://canary.discordapp.com/channels/",
+];
+
+/// Parses a message link, a `channel-message` id pair or a message id, the latter being in the
+/// current channel which is returned as `None`
+pub fn parse_target(arg: &str) -> Result<(Option<ChannelId>, MessageId), CommandError> {
+    let invalid = || CommandError::InvalidMessage(arg.to_owned());
+    let arg = arg.trim().trim_matches(|ch| ch == '<' || ch == '>');
+    if let Some(prefix) = LINK_PREFIXES.iter().find(|prefix| arg.starts_with(*prefix)) {
+        // The guild part is `@me` for direct messages, the channel id is enough to fetch
+        let parts: Vec<_> = arg[prefix.len()..].split('/').collect();
+        return match parts.as_slice() {
+            [_, channel, message] => match (channel.parse(), message.parse()) {
+                (Ok(channel), Ok(message)) => Ok((Some(ChannelId(channel)), MessageId(message))),
+                _ => Err(invalid()),
+            },
+            _ => Err(invalid()),
+        };
+    }
+    let mut ids = arg.splitn(2, '-');
+    match (ids.next().map(str::parse), ids.next().map(str::parse)) {
+        (Some(Ok(channel)), Some(Ok(message))) => {
+            Ok((Some(ChannelId(channel)), MessageId(message)))
+        }
+        (Some(Ok(message)), None) => Ok((None, MessageId(message))),
+        _ => Err(invalid()),
+    }
+}
+
+/// Lines of the overlay showing a peeked message, timed in `timezone` like the message view
+pub fn lines(msg: &Message, timestamp_fmt: &str, timezone: Option<FixedOffset>) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{} {}",
+        msg.author.name,
+        timezone::format(msg.timestamp, timezone, timestamp_fmt)
+    )];
+    lines.extend(msg.content.lines().map(str::to_owned));
+    lines.extend(
+        msg.attachments
+            .iter()
+            .map(|attachment| attachment.url.clone()),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::{lines, parse_target};
+    use chrono::FixedOffset;
+    use error::CommandError;
+    use helpers::testing::message;
+    use serenity::model::id::{ChannelId, MessageId};
+
+    #[test]
+    fn timed_like_the_message_view() {
+        let msg = message(1, 10, "hi\nthere");
+
+        assert_eq!(
+            lines(&msg, "%H:%M", Some(FixedOffset::east(3600))),
+            vec!["user10 13:00", "hi", "there"]
+        );
+    }
+
+    #[test]
+    fn message_links() {
+        assert_eq!(
+            parse_target("https://discordapp.com/channels/1/22/333"),
+            Ok((Some(ChannelId(22)), MessageId(333)))
+        );
+        assert_eq!(
+            parse_target("<https://discord.com/channels/@me/22/333>"),
+            Ok((Some(ChannelId(22)), MessageId(333)))
+        );
+    }
+
+    #[test]
+    fn message_ids() {
+        assert_eq!(
+            parse_target("22-333"),
+            Ok((Some(ChannelId(22)), MessageId(333)))
+        );
+        assert_eq!(parse_target("333"), Ok((None, MessageId(333))));
+    }
+
+    #[test]
+    fn invalid_targets() {
+        for arg in &[
+            "https://discordapp.com/channels/1/22",
+            "22-abc",
+            "hello",
+            "",
+        ] {
+            assert_eq!(
+                parse_target(arg),
+                Err(CommandError::InvalidMessage(arg.to_string()))
+            );
+        }
+    }
+}
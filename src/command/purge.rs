@@ -0,0 +1,257 @@
+//! Deleting messages for the `d` and `selfpurge` commands
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serenity::http;
+use serenity::model::id::{ChannelId, MessageId, UserId};
+
+use model::MessageItem;
+
+/// Fewest messages Discord deletes in one bulk request
+const BULK_MIN: usize = 2;
+/// Most messages Discord deletes in one bulk request
+const BULK_MAX: usize = 100;
+/// Messages older than this can only be deleted one at a time
+const BULK_MAX_AGE_DAYS: i64 = 14;
+/// Kept clear of the age limit, as a message may cross it before Discord handles the request
+const BULK_AGE_MARGIN_MINUTES: i64 = 5;
+/// Start of 2015 in milliseconds since the Unix epoch, which snowflakes count from
+const DISCORD_EPOCH: u64 = 1_420_070_400_000;
+
+/// When a message was sent, as encoded in its id
+fn sent_at(id: MessageId) -> DateTime<Utc> {
+    let millis = (id.0 >> 22) + DISCORD_EPOCH;
+    Utc.timestamp((millis / 1000) as i64, (millis % 1000) as u32 * 1_000_000)
+}
+
+/// Messages grouped by channel, in the order each channel first appears
+pub fn by_channel(targets: &[(ChannelId, MessageId)]) -> Vec<(ChannelId, Vec<MessageId>)> {
+    let mut groups: Vec<(ChannelId, Vec<MessageId>)> = Vec::new();
+    for &(channel, message) in targets {
+        match groups.iter().position(|&(known, _)| known == channel) {
+            Some(index) => groups[index].1.push(message),
+            None => groups.push((channel, vec![message])),
+        }
+    }
+    groups
+}
+
+/// How a set of messages is deleted
+#[derive(Debug, PartialEq)]
+pub struct Plan {
+    /// Requests deleting several recent messages of a channel at once
+    pub bulk: Vec<(ChannelId, Vec<MessageId>)>,
+    /// Messages too old for bulk deletion, or left alone in their chunk
+    pub single: Vec<(ChannelId, MessageId)>,
+}
+
+/// For deletes that must not need any permission, such as of the user's own messages
+pub fn never_bulk(_: ChannelId) -> bool {
+    false
+}
+
+/// Splits `targets` into bulk deletes of recent messages in channels where `can_bulk` allows
+/// them and single deletes of the rest
+pub fn plan<F: Fn(ChannelId) -> bool>(
+    targets: &[(ChannelId, MessageId)],
+    now: DateTime<Utc>,
+    can_bulk: F,
+) -> Plan {
+    let cutoff =
+        now - (Duration::days(BULK_MAX_AGE_DAYS) - Duration::minutes(BULK_AGE_MARGIN_MINUTES));
+    let (recent, mut single): (Vec<_>, Vec<_>) = targets
+        .iter()
+        .cloned()
+        .partition(|&(channel, message)| can_bulk(channel) && sent_at(message) > cutoff);
+    let mut bulk = Vec::new();
+    for (channel, messages) in by_channel(&recent) {
+        for chunk in messages.chunks(BULK_MAX) {
+            if chunk.len() >= BULK_MIN {
+                bulk.push((channel, chunk.to_vec()));
+            } else {
+                single.extend(chunk.iter().map(|&message| (channel, message)));
+            }
+        }
+    }
+    Plan { bulk, single }
+}
+
+/// Every message in `messages`
+pub fn all_messages(messages: &[MessageItem]) -> Vec<(ChannelId, MessageId)> {
+    messages
+        .iter()
+        .map(|item| match item {
+            MessageItem::DiscordMessage(msg) => (msg.channel_id, msg.id),
+        })
+        .collect()
+}
+
+/// Messages in `messages` written by `user_id`
+pub fn own_messages(messages: &[MessageItem], user_id: UserId) -> Vec<(ChannelId, MessageId)> {
+    messages
+        .iter()
+        .filter_map(|item| match item {
+            MessageItem::DiscordMessage(msg) if msg.author.id == user_id => {
+                Some((msg.channel_id, msg.id))
+            }
+            MessageItem::DiscordMessage(_) => None,
+        })
+        .collect()
+}
+
+/// Deletes every message, in bulk where Discord and `can_bulk` allow it, returning the deleted
+/// messages and the errors for the rest. A rejected bulk request is retried one by one.
+pub fn run<F: Fn(ChannelId) -> bool>(
+    targets: &[(ChannelId, MessageId)],
+    now: DateTime<Utc>,
+    can_bulk: F,
+) -> (Vec<(ChannelId, MessageId)>, Vec<String>) {
+    let mut plan = plan(targets, now, can_bulk);
+    let mut deleted = Vec::new();
+    let mut errors = Vec::new();
+    for (channel, messages) in plan.bulk {
+        let ids: Vec<_> = messages
+            .iter()
+            .map(|message| message.0.to_string())
+            .collect();
+        match http::delete_messages(channel.0, &json!({ "messages": ids })) {
+            Ok(()) => deleted.extend(messages.into_iter().map(|message| (channel, message))),
+            Err(err) => {
+                warn!("Bulk delete failed, deleting one by one: {}", err);
+                plan.single
+                    .extend(messages.into_iter().map(|message| (channel, message)));
+            }
+        }
+    }
+    for (channel, message) in plan.single {
+        match channel.delete_message(message) {
+            Ok(_) => deleted.push((channel, message)),
+            Err(err) => errors.push(format!("Unable to delete {}: {}", message, err)),
+        }
+    }
+    (deleted, errors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{all_messages, by_channel, never_bulk, own_messages, plan, Plan, DISCORD_EPOCH};
+    use chrono::{Duration, TimeZone, Utc};
+    use helpers::testing::message_item;
+    use serenity::model::id::{ChannelId, MessageId, UserId};
+
+    /// Id of a message sent `days_ago` days before the start of June 2018
+    fn id_sent(days_ago: i64) -> MessageId {
+        let sent = Utc.ymd(2018, 6, 1).and_hms(0, 0, 0) - Duration::days(days_ago);
+        MessageId((sent.timestamp() as u64 * 1000 - DISCORD_EPOCH) << 22)
+    }
+
+    #[test]
+    fn only_own_messages() {
+        let messages = vec![
+            message_item(1, 10, "mine"),
+            message_item(2, 11, "theirs"),
+            message_item(3, 10, "mine again"),
+            message_item(4, 12, "also theirs"),
+        ];
+
+        assert_eq!(
+            own_messages(&messages, UserId(10)),
+            vec![(ChannelId(1), MessageId(1)), (ChannelId(1), MessageId(3))]
+        );
+        assert_eq!(
+            own_messages(&messages[1..2], UserId(11)),
+            vec![(ChannelId(1), MessageId(2))]
+        );
+        assert!(own_messages(&messages, UserId(99)).is_empty());
+    }
+
+    #[test]
+    fn every_message_in_range() {
+        let messages = vec![
+            message_item(1, 10, "mine"),
+            message_item(2, 11, "theirs"),
+            message_item(3, 10, "mine again"),
+        ];
+
+        assert_eq!(
+            all_messages(&messages[1..]),
+            vec![(ChannelId(1), MessageId(2)), (ChannelId(1), MessageId(3))]
+        );
+        assert!(all_messages(&[]).is_empty());
+    }
+
+    #[test]
+    fn groups_by_channel() {
+        let targets = [
+            (ChannelId(1), MessageId(1)),
+            (ChannelId(2), MessageId(2)),
+            (ChannelId(1), MessageId(3)),
+        ];
+
+        assert_eq!(
+            by_channel(&targets),
+            vec![
+                (ChannelId(1), vec![MessageId(1), MessageId(3)]),
+                (ChannelId(2), vec![MessageId(2)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn bulk_deletes_recent_messages() {
+        let now = Utc.ymd(2018, 6, 1).and_hms(0, 0, 0);
+        let old = (ChannelId(1), id_sent(20));
+        let lone = (ChannelId(2), id_sent(1));
+        let mut targets = vec![old, lone];
+        targets.extend((0..103).map(|_| (ChannelId(1), id_sent(2))));
+
+        let Plan { bulk, single } = plan(&targets, now, |_| true);
+        // Chunks of at most 100, a chunk of one being deleted on its own
+        assert_eq!(bulk.len(), 2);
+        assert_eq!((bulk[0].0, bulk[0].1.len()), (ChannelId(1), 100));
+        assert_eq!((bulk[1].0, bulk[1].1.len()), (ChannelId(1), 3));
+        assert_eq!(single, vec![old, lone]);
+    }
+
+    #[test]
+    fn old_messages_are_deleted_singly() {
+        let now = Utc.ymd(2018, 6, 1).and_hms(0, 0, 0);
+        // Just inside the age limit, but too close to it to risk the whole chunk
+        let sent = now - Duration::days(14) + Duration::minutes(1);
+        let borderline = MessageId((sent.timestamp() as u64 * 1000 - DISCORD_EPOCH) << 22);
+        let targets = vec![
+            (ChannelId(1), id_sent(15)),
+            (ChannelId(1), id_sent(14)),
+            (ChannelId(1), borderline),
+        ];
+
+        assert_eq!(
+            plan(&targets, now, |_| true),
+            Plan {
+                bulk: Vec::new(),
+                single: targets.clone(),
+            }
+        );
+    }
+
+    #[test]
+    fn bulk_only_where_allowed() {
+        let now = Utc.ymd(2018, 6, 1).and_hms(0, 0, 0);
+        let dm = vec![(ChannelId(2), id_sent(1)), (ChannelId(2), id_sent(1))];
+        let mut targets = vec![(ChannelId(1), id_sent(1)), (ChannelId(1), id_sent(1))];
+        targets.extend(dm.iter().cloned());
+
+        let Plan { bulk, single } = plan(&targets, now, |channel| channel == ChannelId(1));
+        assert_eq!(bulk, vec![(ChannelId(1), vec![id_sent(1), id_sent(1)])]);
+        assert_eq!(single, dm);
+    }
+
+    #[test]
+    fn self_purge_never_bulk_deletes() {
+        let now = Utc.ymd(2018, 6, 1).and_hms(0, 0, 0);
+        let targets: Vec<_> = (0..5).map(|_| (ChannelId(1), id_sent(1))).collect();
+
+        let Plan { bulk, single } = plan(&targets, now, never_bulk);
+        assert!(bulk.is_empty());
+        assert_eq!(single, targets);
+    }
+}
@@ -0,0 +1,58 @@
+//! Formatting messages for the `quoteblock` command
+
+use chrono::FixedOffset;
+
+use helpers::timezone;
+use model::MessageItem;
+
+/// Formats messages as `**Author** (time): content`, separated by blank lines, with times in
+/// `timezone`
+pub fn quote_block(
+    messages: &[MessageItem],
+    timestamp_fmt: &str,
+    timezone: Option<FixedOffset>,
+) -> String {
+    messages
+        .iter()
+        .map(|item| match item {
+            MessageItem::DiscordMessage(msg) => format!(
+                "**{}** ({}): {}",
+                msg.author.name,
+                timezone::format(msg.timestamp, timezone, timestamp_fmt),
+                msg.content
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::quote_block;
+    use chrono::FixedOffset;
+    use helpers::testing::message_item;
+
+    #[test]
+    fn multi_message_quote() {
+        let messages = vec![
+            message_item(1, 10, "hello"),
+            message_item(2, 11, "two\nlines"),
+        ];
+
+        let utc = Some(FixedOffset::east(0));
+
+        assert_eq!(
+            quote_block(&messages, "%H:%M", utc),
+            "**user10** (12:00): hello\n\n**user11** (12:00): two\nlines"
+        );
+        assert_eq!(
+            quote_block(&messages[..1], "%H:%M", utc),
+            "**user10** (12:00): hello"
+        );
+        // Quoted as the times shown in the message view
+        assert_eq!(
+            quote_block(&messages[..1], "%H:%M", Some(FixedOffset::east(2 * 3600))),
+            "**user10** (14:00): hello"
+        );
+    }
+}
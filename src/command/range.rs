@@ -0,0 +1,512 @@
+//! Resolution of ed-style addresses against the loaded message buffer
+//!
+//! Indices are positions in the buffer, oldest message first. `Fixed(n)` is 1-based from the
+//! oldest loaded message, like line numbers in ed, and `#n` counts back from the newest message,
+//! `#0` being the newest itself. `$` is the newest message, like ed's last line,
+//! and `.` the current message, which is the newest one until another is selected. `+n` and `-n`
+//! count from the current message towards newer and older messages, stopping at either end.
+//! `@name` is the newest message by the user with that username or guild nickname.
+//! `{date}` is the first message sent on or after a time and `<{date}` the last one before it.
+//! `/query/` searches towards newer messages and `?query?` towards older ones, both wrapping,
+//! and `/query/i` ignores case.
+//! In `a;b` the right endpoint is resolved with `a` as the current message, so `/x/;/y/` finds
+//! the first `y` after the `x`.
+
+use chrono::{DateTime, Local, Utc};
+use parsing::cmd::{Endpoint, Range};
+use regex::Regex;
+use serenity::model::id::UserId;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use error::RangeError;
+use model::MessageItem;
+
+thread_local! {
+    /// Patterns of regex endpoints already compiled, so repeating a command reuses them
+    static REGEXES: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+}
+
+/// Compiles a regex endpoint's pattern, or returns the copy compiled earlier
+fn compile(pattern: &str) -> Result<Regex, RangeError> {
+    REGEXES.with(|regexes| {
+        if let Some(regex) = regexes.borrow().get(pattern) {
+            return Ok(regex.clone());
+        }
+        let regex = Regex::new(pattern)
+            .map_err(|err| RangeError::InvalidRegex(format!("~/{}/", pattern), err.to_string()))?;
+        regexes
+            .borrow_mut()
+            .insert(pattern.to_owned(), regex.clone());
+        Ok(regex)
+    })
+}
+
+fn content(item: &MessageItem) -> &str {
+    match item {
+        MessageItem::DiscordMessage(msg) => &msg.content,
+    }
+}
+
+fn sent_before(item: &MessageItem, time: DateTime<Utc>) -> bool {
+    match *item {
+        MessageItem::DiscordMessage(ref msg) => msg.timestamp.with_timezone(&Utc) < time,
+    }
+}
+
+/// A time as written in a date endpoint
+fn date_label(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Whether a message was sent by `name`, compared to usernames and to guild nicknames
+fn sent_by(item: &MessageItem, name: &str, nicknames: &HashMap<UserId, String>) -> bool {
+    match *item {
+        MessageItem::DiscordMessage(ref msg) => {
+            msg.author.name.eq_ignore_ascii_case(name)
+                || nicknames
+                    .get(&msg.author.id)
+                    .map_or(false, |nick| nick.eq_ignore_ascii_case(name))
+        }
+    }
+}
+
+/// Resolves a single endpoint to a buffer index, `current` is the index of the current message
+/// and `nicknames` the guild nicknames of authors that have one
+pub fn resolve_endpoint(
+    endpoint: &Endpoint,
+    messages: &[MessageItem],
+    current: usize,
+    nicknames: &HashMap<UserId, String>,
+) -> Result<usize, RangeError> {
+    if messages.is_empty() {
+        return Err(RangeError::Empty);
+    }
+    match *endpoint {
+        Endpoint::Fixed(n) => {
+            if n >= 1 && n <= messages.len() {
+                Ok(n - 1)
+            } else {
+                Err(RangeError::OutOfBounds(n.to_string()))
+            }
+        }
+        Endpoint::Moment(n) => {
+            if n < messages.len() {
+                Ok(messages.len() - 1 - n)
+            } else {
+                Err(RangeError::OutOfBounds(format!("#{}", n)))
+            }
+        }
+        Endpoint::Last => Ok(messages.len() - 1),
+        Endpoint::Current => Ok(current.min(messages.len() - 1)),
+        Endpoint::Relative(offset) => {
+            let index = current as isize + offset;
+            Ok((index.max(0) as usize).min(messages.len() - 1))
+        }
+        Endpoint::Search(ref query, ignore_case) => {
+            // Search forwards from the message after the current one, wrapping around
+            let len = messages.len();
+            let lowered = query.to_lowercase();
+            (1..len + 1)
+                .map(|offset| (current + offset) % len)
+                .find(|&i| {
+                    let content = content(&messages[i]);
+                    if ignore_case {
+                        content.to_lowercase().contains(&lowered)
+                    } else {
+                        content.contains(&**query)
+                    }
+                })
+                .ok_or_else(|| {
+                    let flag = if ignore_case { "i" } else { "" };
+                    RangeError::NoMatch(format!("/{}/{}", query, flag))
+                })
+        }
+        Endpoint::SearchBackward(ref query) => {
+            // Search backwards from the message before the current one, wrapping around
+            let len = messages.len();
+            (1..len + 1)
+                .map(|offset| (current + len - offset % len) % len)
+                .find(|&i| content(&messages[i]).contains(&**query))
+                .ok_or_else(|| RangeError::NoMatch(format!("?{}?", query)))
+        }
+        Endpoint::After(time) => messages
+            .iter()
+            .position(|item| !sent_before(item, time))
+            .ok_or_else(|| RangeError::NoMatch(format!("{{{}}}", date_label(time)))),
+        Endpoint::Before(time) => messages
+            .iter()
+            .rposition(|item| sent_before(item, time))
+            .ok_or_else(|| RangeError::NoMatch(format!("<{{{}}}", date_label(time)))),
+        Endpoint::Author(name) => messages
+            .iter()
+            .rposition(|item| sent_by(item, name, nicknames))
+            .ok_or_else(|| RangeError::NoMatch(format!("@{}", name))),
+        Endpoint::Regex(ref pattern) => {
+            let regex = compile(pattern)?;
+            let len = messages.len();
+            (1..len + 1)
+                .map(|offset| (current + offset) % len)
+                .find(|&i| regex.is_match(content(&messages[i])))
+                .ok_or_else(|| RangeError::NoMatch(format!("~/{}/", pattern)))
+        }
+    }
+}
+
+/// Resolves a range to an inclusive `(start, end)` pair of buffer indices
+pub fn resolve_range(
+    range: &Range,
+    messages: &[MessageItem],
+    current: usize,
+    nicknames: &HashMap<UserId, String>,
+) -> Result<(usize, usize), RangeError> {
+    if messages.is_empty() {
+        return Err(RangeError::Empty);
+    }
+    range.resolve(current, messages.len() - 1, |endpoint, current| {
+        resolve_endpoint(endpoint, messages, current, nicknames)
+    })
+}
+
+/// Index of the message current after a command on `start..=end` of a buffer of `len` messages,
+/// indices being taken before the command ran. Like ed, that is the last addressed message, or
+/// after a deletion the message following the deleted ones, or else the one before them.
+pub fn current_after(deleted: bool, (start, end): (usize, usize), len: usize) -> Option<usize> {
+    if !deleted {
+        Some(end.min(len.checked_sub(1)?))
+    } else if end + 1 < len {
+        Some(end + 1)
+    } else {
+        start.checked_sub(1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{current_after, resolve_endpoint, resolve_range};
+    use chrono::{TimeZone, Utc};
+    use error::RangeError;
+    use helpers::testing::{message_from_value, message_item, message_value};
+    use model::MessageItem;
+    use parsing::cmd::Endpoint::*;
+    use parsing::cmd::Range::*;
+    use serenity::model::id::UserId;
+    use std::collections::HashMap;
+    use view::message_number;
+
+    fn buffer() -> Vec<MessageItem> {
+        vec![
+            message_item(1, 10, "hello"),
+            message_item(2, 11, "foo"),
+            message_item(3, 10, "bar"),
+            message_item(4, 11, "foo bar"),
+            message_item(5, 10, "baz"),
+            message_item(6, 11, "qux"),
+        ]
+    }
+
+    #[test]
+    fn fixed_is_one_based() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(resolve_endpoint(&Fixed(1), &messages, 5, &nicks), Ok(0));
+        assert_eq!(resolve_endpoint(&Fixed(5), &messages, 5, &nicks), Ok(4));
+        assert_eq!(
+            resolve_endpoint(&Fixed(0), &messages, 5, &nicks),
+            Err(RangeError::OutOfBounds("0".to_owned()))
+        );
+        assert_eq!(
+            resolve_endpoint(&Fixed(7), &messages, 5, &nicks),
+            Err(RangeError::OutOfBounds("7".to_owned()))
+        );
+    }
+
+    #[test]
+    fn gutter_numbers_match_fixed() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        for index in 0..messages.len() {
+            assert_eq!(
+                resolve_endpoint(&Fixed(message_number(index)), &messages, 0, &nicks),
+                Ok(index)
+            );
+        }
+    }
+
+    #[test]
+    fn moment_counts_from_newest() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(resolve_endpoint(&Moment(0), &messages, 5, &nicks), Ok(5));
+        assert_eq!(resolve_endpoint(&Moment(2), &messages, 5, &nicks), Ok(3));
+        assert!(resolve_endpoint(&Moment(6), &messages, 5, &nicks).is_err());
+    }
+
+    #[test]
+    fn last_is_newest() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(resolve_endpoint(&Last, &messages, 0, &nicks), Ok(5));
+        assert_eq!(
+            resolve_endpoint(&Last, &[], 0, &nicks),
+            Err(RangeError::Empty)
+        );
+    }
+
+    #[test]
+    fn relative_to_cursor_clamped() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(resolve_endpoint(&Relative(-1), &messages, 3, &nicks), Ok(2));
+        assert_eq!(resolve_endpoint(&Relative(2), &messages, 3, &nicks), Ok(5));
+        assert_eq!(resolve_endpoint(&Relative(10), &messages, 3, &nicks), Ok(5));
+        assert_eq!(
+            resolve_endpoint(&Relative(-10), &messages, 3, &nicks),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn current_is_cursor() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(resolve_endpoint(&Current, &messages, 2, &nicks), Ok(2));
+        assert_eq!(
+            resolve_range(&DoubledEnded(Current, Last), &messages, 2, &nicks),
+            Ok((2, 5))
+        );
+    }
+
+    #[test]
+    fn anchored_search_starts_after_first_match() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        // With `,` both searches start from the current message and find the same "foo"
+        assert_eq!(
+            resolve_range(
+                &DoubledEnded(Search("foo".into(), false), Search("foo".into(), false)),
+                &messages,
+                5,
+                &nicks
+            ),
+            Ok((1, 1))
+        );
+        // With `;` the second search starts after the first match
+        assert_eq!(
+            resolve_range(
+                &Anchored(Search("foo".into(), false), Search("foo".into(), false)),
+                &messages,
+                5,
+                &nicks
+            ),
+            Ok((1, 3))
+        );
+        assert_eq!(
+            resolve_range(&Anchored(Fixed(2), Relative(2)), &messages, 5, &nicks),
+            Ok((1, 3))
+        );
+    }
+
+    #[test]
+    fn search_wraps_from_current() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(
+            resolve_endpoint(&Search("foo".into(), false), &messages, 5, &nicks),
+            Ok(1)
+        );
+        assert_eq!(
+            resolve_endpoint(&Search("foo".into(), false), &messages, 1, &nicks),
+            Ok(3)
+        );
+        assert_eq!(
+            resolve_endpoint(&Search("nope".into(), false), &messages, 5, &nicks),
+            Err(RangeError::NoMatch("/nope/".to_owned()))
+        );
+    }
+
+    #[test]
+    fn search_ignoring_case() {
+        let messages = vec![
+            message_item(1, 10, "hello"),
+            message_item(2, 11, "Foo"),
+            message_item(3, 10, "FOO bar"),
+        ];
+        let nicks = HashMap::new();
+
+        assert_eq!(
+            resolve_endpoint(&Search("Foo".into(), false), &messages, 2, &nicks),
+            Ok(1)
+        );
+        assert_eq!(
+            resolve_endpoint(&Search("foo".into(), true), &messages, 1, &nicks),
+            Ok(2)
+        );
+        assert_eq!(
+            resolve_endpoint(&Search("foo".into(), false), &messages, 2, &nicks),
+            Err(RangeError::NoMatch("/foo/".to_owned()))
+        );
+    }
+
+    #[test]
+    fn search_backward_wraps_from_current() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(
+            resolve_endpoint(&SearchBackward("foo".into()), &messages, 5, &nicks),
+            Ok(3)
+        );
+        assert_eq!(
+            resolve_endpoint(&SearchBackward("foo".into()), &messages, 3, &nicks),
+            Ok(1)
+        );
+        // Wraps around to the newest message
+        assert_eq!(
+            resolve_endpoint(&SearchBackward("qux".into()), &messages, 1, &nicks),
+            Ok(5)
+        );
+        assert_eq!(
+            resolve_endpoint(&SearchBackward("nope".into()), &messages, 5, &nicks),
+            Err(RangeError::NoMatch("?nope?".to_owned()))
+        );
+    }
+
+    #[test]
+    fn dates_split_the_buffer() {
+        let sent_at = |id, time: &str| {
+            let mut value = message_value(id, 10, "hi");
+            value["timestamp"] = json!(time);
+            MessageItem::DiscordMessage(Box::new(message_from_value(value)))
+        };
+        let messages = vec![
+            sent_at(1, "2021-02-27T12:00:00+00:00"),
+            sent_at(2, "2021-02-28T23:00:00+00:00"),
+            sent_at(3, "2021-03-01T00:00:00+00:00"),
+            sent_at(4, "2021-03-02T08:00:00+00:00"),
+        ];
+        let nicks = HashMap::new();
+        let march = Utc.ymd(2021, 3, 1).and_hms(0, 0, 0);
+
+        assert_eq!(resolve_endpoint(&After(march), &messages, 0, &nicks), Ok(2));
+        assert_eq!(
+            resolve_endpoint(&Before(march), &messages, 0, &nicks),
+            Ok(1)
+        );
+        assert_eq!(
+            resolve_range(&PastToPresent(After(march)), &messages, 0, &nicks),
+            Ok((2, 3))
+        );
+        assert_eq!(
+            resolve_range(&PresentToPast(Before(march)), &messages, 0, &nicks),
+            Ok((0, 1))
+        );
+        let later = Utc.ymd(2022, 1, 1).and_hms(0, 0, 0);
+        assert!(resolve_endpoint(&After(later), &messages, 0, &nicks).is_err());
+    }
+
+    #[test]
+    fn author_is_their_newest_message() {
+        let messages = buffer();
+        let mut nicks = HashMap::new();
+        nicks.insert(UserId(11), "Bobby".to_owned());
+
+        assert_eq!(
+            resolve_endpoint(&Author("user10"), &messages, 0, &nicks),
+            Ok(4)
+        );
+        // Guild nicknames match too, ignoring case
+        assert_eq!(
+            resolve_endpoint(&Author("bobby"), &messages, 0, &nicks),
+            Ok(5)
+        );
+        assert_eq!(
+            resolve_range(&DoubledEnded(Author("user10"), Last), &messages, 0, &nicks),
+            Ok((4, 5))
+        );
+        assert_eq!(
+            resolve_endpoint(&Author("alice"), &messages, 0, &nicks),
+            Err(RangeError::NoMatch("@alice".to_owned()))
+        );
+    }
+
+    #[test]
+    fn regex_wraps_from_current() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(
+            resolve_endpoint(&Regex("^ba".into()), &messages, 5, &nicks),
+            Ok(2)
+        );
+        assert_eq!(
+            resolve_endpoint(&Regex("o{2} b".into()), &messages, 0, &nicks),
+            Ok(3)
+        );
+        assert_eq!(
+            resolve_endpoint(&Regex("^nope$".into()), &messages, 5, &nicks),
+            Err(RangeError::NoMatch("~/^nope$/".to_owned()))
+        );
+        match resolve_endpoint(&Regex("(".into()), &messages, 5, &nicks) {
+            Err(RangeError::InvalidRegex(pattern, _)) => assert_eq!(pattern, "~/(/"),
+            other => panic!("Expected an invalid pattern, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        let messages = buffer();
+        let nicks = HashMap::new();
+
+        assert_eq!(
+            resolve_range(&Single(Fixed(5)), &messages, 5, &nicks),
+            Ok((4, 4))
+        );
+        assert_eq!(
+            resolve_range(&DoubledEnded(Fixed(2), Fixed(4)), &messages, 5, &nicks),
+            Ok((1, 3))
+        );
+        assert_eq!(
+            resolve_range(&PastToPresent(Fixed(3)), &messages, 5, &nicks),
+            Ok((2, 5))
+        );
+        assert_eq!(
+            resolve_range(&PresentToPast(Fixed(3)), &messages, 5, &nicks),
+            Ok((0, 2))
+        );
+        assert_eq!(resolve_range(&All, &messages, 2, &nicks), Ok((0, 5)));
+        // Endpoints given newest first still select the same span
+        assert_eq!(
+            resolve_range(&DoubledEnded(Fixed(4), Fixed(2)), &messages, 5, &nicks),
+            Ok((1, 3))
+        );
+        assert_eq!(resolve_range(&All, &[], 0, &nicks), Err(RangeError::Empty));
+        assert_eq!(
+            resolve_range(&Single(Fixed(1)), &[], 0, &nicks),
+            Err(RangeError::Empty)
+        );
+    }
+
+    #[test]
+    fn current_after_commands() {
+        // Deleting 5 of 6 messages leaves the 6th current
+        assert_eq!(current_after(true, (4, 4), 6), Some(5));
+        // Deleting the newest messages falls back to the one before them
+        assert_eq!(current_after(true, (3, 5), 6), Some(2));
+        assert_eq!(current_after(true, (0, 5), 6), None);
+        // Other commands leave the last addressed message current
+        assert_eq!(current_after(false, (1, 3), 6), Some(3));
+        assert_eq!(current_after(false, (0, 0), 0), None);
+    }
+}
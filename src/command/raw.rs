@@ -0,0 +1,49 @@
+//! Exact message content for the `raw` command, with hidden characters made visible
+
+use super::is_unprintable;
+
+/// Lines of `content` as typed, each ending in `⏎` when followed by a newline, with tabs,
+/// control and zero width characters written as escapes
+pub fn raw_lines(content: &str) -> Vec<String> {
+    let lines: Vec<_> = content.split('\n').collect();
+    let last = lines.len() - 1;
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let mut raw: String = line.chars().map(escape).collect();
+            if i != last {
+                raw.push('⏎');
+            }
+            raw
+        })
+        .collect()
+}
+
+fn escape(ch: char) -> String {
+    match ch {
+        '\t' => "\\t".to_owned(),
+        '\r' => "\\r".to_owned(),
+        '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}' => format!("\\u{{{:x}}}", ch as u32),
+        ch if is_unprintable(ch) => format!("\\u{{{:x}}}", ch as u32),
+        ch => ch.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::raw_lines;
+
+    #[test]
+    fn markers_and_control_bytes_visible() {
+        assert_eq!(
+            raw_lines("**bold** \\*not\\*\n\t`code`\u{1b}[31m\u{200b}"),
+            vec![
+                "**bold** \\*not\\*⏎".to_owned(),
+                "\\t`code`\\u{1b}[31m\\u{200b}".to_owned(),
+            ]
+        );
+        assert_eq!(raw_lines(""), vec!["".to_owned()]);
+        assert_eq!(raw_lines("a\n"), vec!["a⏎".to_owned(), "".to_owned()]);
+    }
+}
@@ -0,0 +1,115 @@
+//! Parsing the emoji given to the `react` command
+
+use serenity::model::channel::ReactionType;
+use serenity::model::id::EmojiId;
+use unicode_segmentation::UnicodeSegmentation;
+
+use error::CommandError;
+use helpers::emoji;
+
+/// Parses a unicode emoji, a `:shortcode:`, the `:name:` of a custom emoji that `custom` finds,
+/// or a custom emoji as Discord writes it, `<:name:id>`
+pub fn parse_emoji<F>(arg: &str, custom: F) -> Result<ReactionType, CommandError>
+where
+    F: Fn(&str) -> Option<ReactionType>,
+{
+    let invalid = || CommandError::InvalidEmoji(arg.to_owned());
+    if arg.len() > 2 && arg.starts_with('<') && arg.ends_with('>') {
+        let parts: Vec<_> = arg[1..arg.len() - 1].split(':').collect();
+        return match parts.as_slice() {
+            [animated, name, id] if ["", "a"].contains(animated) && !name.is_empty() => {
+                match id.parse() {
+                    Ok(id) => Ok(ReactionType::Custom {
+                        animated: *animated == "a",
+                        id: EmojiId(id),
+                        name: Some(name.to_string()),
+                    }),
+                    Err(_) => Err(invalid()),
+                }
+            }
+            _ => Err(invalid()),
+        };
+    }
+    if arg.len() > 2 && arg.starts_with(':') && arg.ends_with(':') {
+        let name = &arg[1..arg.len() - 1];
+        return emoji::from_shortcode(name)
+            .map(|emoji| ReactionType::Unicode(emoji.to_owned()))
+            .or_else(|| custom(name))
+            .ok_or_else(invalid);
+    }
+    // Anything else has to be a single glyph, which Discord checks is really an emoji
+    if UnicodeSegmentation::graphemes(arg, true).count() == 1 && !arg.is_ascii() {
+        Ok(ReactionType::Unicode(arg.to_owned()))
+    } else {
+        Err(invalid())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_emoji;
+    use error::CommandError;
+    use serenity::model::channel::ReactionType;
+    use serenity::model::id::EmojiId;
+
+    fn blob(name: &str) -> Option<ReactionType> {
+        if name == "blob" {
+            Some(ReactionType::Custom {
+                animated: false,
+                id: EmojiId(7),
+                name: Some("blob".to_owned()),
+            })
+        } else {
+            None
+        }
+    }
+
+    #[test]
+    fn unicode_emoji() {
+        assert_eq!(
+            parse_emoji("👍", blob),
+            Ok(ReactionType::Unicode("👍".to_owned()))
+        );
+        assert_eq!(
+            parse_emoji(":fire:", blob),
+            Ok(ReactionType::Unicode("🔥".to_owned()))
+        );
+        // Keycaps start with a plain digit
+        assert_eq!(
+            parse_emoji("1\u{fe0f}\u{20e3}", blob),
+            Ok(ReactionType::Unicode("1\u{fe0f}\u{20e3}".to_owned()))
+        );
+    }
+
+    #[test]
+    fn custom_emoji() {
+        assert_eq!(parse_emoji(":blob:", blob), Ok(blob("blob").unwrap()));
+        assert_eq!(
+            parse_emoji("<a:party:42>", blob),
+            Ok(ReactionType::Custom {
+                animated: true,
+                id: EmojiId(42),
+                name: Some("party".to_owned()),
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_emoji() {
+        for arg in &[
+            "",
+            "x",
+            "ok",
+            "👍👍",
+            ":nope:",
+            "::",
+            "<:party:abc>",
+            "<b:party:42>",
+        ] {
+            assert_eq!(
+                parse_emoji(arg, blob),
+                Err(CommandError::InvalidEmoji(arg.to_string()))
+            );
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Reaction leaderboards for the `topreactions` command
+
+use serenity::model::id::MessageId;
+
+use std::collections::HashMap;
+
+use model::MessageItem;
+use view::reaction_label;
+
+/// Entries shown in a leaderboard
+pub const LEADERBOARD_LEN: usize = 10;
+/// Longest excerpt of a message shown in the leaderboard
+const EXCERPT_LEN: usize = 40;
+
+/// Total uses of each emoji across `messages`, most used first
+pub fn tally_emoji(messages: &[MessageItem]) -> Vec<(String, u64)> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    for item in messages {
+        let reactions = match *item {
+            MessageItem::DiscordMessage(ref msg) => &msg.reactions,
+        };
+        for reaction in reactions {
+            *counts
+                .entry(reaction_label(&reaction.reaction_type))
+                .or_insert(0) += reaction.count;
+        }
+    }
+    let mut tally: Vec<_> = counts.into_iter().collect();
+    tally.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tally
+}
+
+/// Messages with any reactions and their total reaction count, most reacted first
+pub fn tally_messages(messages: &[MessageItem]) -> Vec<(MessageId, u64)> {
+    let mut tally: Vec<_> = messages
+        .iter()
+        .map(|item| match *item {
+            MessageItem::DiscordMessage(ref msg) => (
+                msg.id,
+                msg.reactions
+                    .iter()
+                    .map(|reaction| reaction.count)
+                    .sum::<u64>(),
+            ),
+        })
+        .filter(|&(_, total)| total > 0)
+        .collect();
+    // Ties go to the older message, which got there first
+    tally.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    tally
+}
+
+fn excerpt(messages: &[MessageItem], id: MessageId) -> String {
+    let content = messages
+        .iter()
+        .filter_map(|item| match *item {
+            MessageItem::DiscordMessage(ref msg) if msg.id == id => Some(msg),
+            _ => None,
+        })
+        .map(|msg| {
+            format!(
+                "{}: {}",
+                msg.author.name,
+                msg.content.lines().next().unwrap_or("")
+            )
+        })
+        .next()
+        .unwrap_or_default();
+    if content.chars().count() > EXCERPT_LEN {
+        content.chars().take(EXCERPT_LEN - 1).collect::<String>() + "…"
+    } else {
+        content
+    }
+}
+
+/// Ranked lines of the most used emoji
+pub fn emoji_leaderboard(messages: &[MessageItem]) -> Vec<String> {
+    tally_emoji(messages)
+        .into_iter()
+        .take(LEADERBOARD_LEN)
+        .enumerate()
+        .map(|(i, (label, count))| format!("{:>2}. {} {}", i + 1, label, count))
+        .collect()
+}
+
+/// Ranked lines of the most reacted messages
+pub fn message_leaderboard(messages: &[MessageItem]) -> Vec<String> {
+    tally_messages(messages)
+        .into_iter()
+        .take(LEADERBOARD_LEN)
+        .enumerate()
+        .map(|(i, (id, total))| format!("{:>2}. {} {}", i + 1, total, excerpt(messages, id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{emoji_leaderboard, message_leaderboard, tally_emoji, tally_messages};
+    use helpers::testing::{message_from_value, message_value};
+    use model::MessageItem;
+    use serenity::model::id::MessageId;
+
+    fn reacted(id: u64, content: &str, reactions: &[(&str, u64)]) -> MessageItem {
+        let mut value = message_value(id, 10, content);
+        value["reactions"] = reactions
+            .iter()
+            .map(|&(emoji, count)| {
+                json!({
+                    "count": count,
+                    "me": false,
+                    "emoji": { "id": null, "name": emoji },
+                })
+            })
+            .collect();
+        MessageItem::DiscordMessage(Box::new(message_from_value(value)))
+    }
+
+    fn buffer() -> Vec<MessageItem> {
+        vec![
+            reacted(1, "first", &[("👍", 2), ("🎉", 1)]),
+            reacted(2, "quiet", &[]),
+            reacted(3, "big news", &[("🎉", 4), ("👀", 1)]),
+            reacted(4, "agreed", &[("👍", 3)]),
+        ]
+    }
+
+    #[test]
+    fn tallies_emoji_most_used_first() {
+        assert_eq!(
+            tally_emoji(&buffer()),
+            vec![
+                ("🎉".to_owned(), 5),
+                ("👍".to_owned(), 5),
+                ("👀".to_owned(), 1)
+            ]
+        );
+        assert!(tally_emoji(&[]).is_empty());
+    }
+
+    #[test]
+    fn tallies_messages_most_reacted_first() {
+        assert_eq!(
+            tally_messages(&buffer()),
+            vec![(MessageId(3), 5), (MessageId(1), 3), (MessageId(4), 3)]
+        );
+    }
+
+    #[test]
+    fn leaderboards() {
+        assert_eq!(emoji_leaderboard(&buffer())[2], " 3. 👀 1");
+        assert_eq!(message_leaderboard(&buffer())[0], " 1. 5 user10: big news");
+    }
+}
@@ -0,0 +1,64 @@
+//! ed's `s/pattern/replacement/` as an edit of a message
+
+use parsing::cmd::Substitution;
+use regex::{self, Regex};
+
+/// The pattern of a substitution, honouring its `i` flag
+pub fn compile(substitution: &Substitution) -> Result<Regex, regex::Error> {
+    if substitution.ignore_case {
+        Regex::new(&format!("(?i){}", substitution.pattern))
+    } else {
+        Regex::new(&substitution.pattern)
+    }
+}
+
+/// `content` with the first match replaced, or every one with the `g` flag, `$1` in the
+/// replacement standing for a group. `None` when nothing matches.
+pub fn apply(regex: &Regex, substitution: &Substitution, content: &str) -> Option<String> {
+    if !regex.is_match(content) {
+        return None;
+    }
+    let replacement = &*substitution.replacement;
+    let replaced = if substitution.global {
+        regex.replace_all(content, replacement)
+    } else {
+        regex.replace(content, replacement)
+    };
+    Some(replaced.into_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{apply, compile};
+    use parsing::cmd::parse_substitution;
+
+    fn substitute(text: &str, content: &str) -> Option<String> {
+        let substitution = parse_substitution(text).unwrap();
+        apply(&compile(&substitution).unwrap(), &substitution, content)
+    }
+
+    #[test]
+    fn first_or_every_match() {
+        assert_eq!(substitute("/o/0/", "foo boo"), Some("f0o boo".to_owned()));
+        assert_eq!(substitute("/o/0/g", "foo boo"), Some("f00 b00".to_owned()));
+        assert_eq!(substitute("/x/y/", "foo"), None);
+    }
+
+    #[test]
+    fn flags_and_groups() {
+        assert_eq!(substitute("/HELLO/hi/", "hello there"), None);
+        assert_eq!(
+            substitute("/HELLO/hi/i", "hello there"),
+            Some("hi there".to_owned())
+        );
+        assert_eq!(
+            substitute("/(\\w+) (\\w+)/$2 $1/", "world hello"),
+            Some("hello world".to_owned())
+        );
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(compile(&parse_substitution("/(/x/").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Copying messages to other channels for the `m` and `t` commands
+
+use serenity::model::id::{ChannelId, MessageId};
+
+use model::MessageItem;
+
+/// Destination channel given to a transfer command
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelTarget<'a> {
+    Id(ChannelId),
+    Name(&'a str),
+}
+
+/// Parses `#name`, a `<#id>` mention or a raw channel id
+pub fn parse_target(arg: &str) -> Option<ChannelTarget> {
+    let arg = arg.trim();
+    if arg.starts_with("<#") && arg.ends_with('>') {
+        arg[2..arg.len() - 1]
+            .parse()
+            .ok()
+            .map(|id| ChannelTarget::Id(ChannelId(id)))
+    } else if arg.starts_with('#') && arg.len() > 1 {
+        Some(ChannelTarget::Name(&arg[1..]))
+    } else {
+        arg.parse().ok().map(|id| ChannelTarget::Id(ChannelId(id)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    Copy {
+        target: ChannelId,
+        content: String,
+    },
+    Delete {
+        channel: ChannelId,
+        message: MessageId,
+    },
+}
+
+/// Content to repost for a message, attachments are carried over as links
+fn copy_content(item: &MessageItem) -> String {
+    match item {
+        MessageItem::DiscordMessage(msg) => {
+            let mut content = msg.content.clone();
+            for attachment in &msg.attachments {
+                if !content.is_empty() {
+                    content.push('\n');
+                }
+                content.push_str(&attachment.url);
+            }
+            content
+        }
+    }
+}
+
+/// Builds the steps to copy `messages` in order, each copy followed by deleting the original
+/// when `delete_source` is set
+pub fn plan(messages: &[MessageItem], target: ChannelId, delete_source: bool) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for item in messages {
+        let content = copy_content(item);
+        if content.is_empty() {
+            continue;
+        }
+        steps.push(Step::Copy { target, content });
+        if delete_source {
+            match item {
+                MessageItem::DiscordMessage(msg) => steps.push(Step::Delete {
+                    channel: msg.channel_id,
+                    message: msg.id,
+                }),
+            }
+        }
+    }
+    steps
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub copied: usize,
+    pub deleted: usize,
+    pub errors: Vec<String>,
+}
+
+impl Report {
+    pub fn summary(&self, verb: &str) -> String {
+        let mut summary = format!("{} {} message(s)", verb, self.copied);
+        if !self.errors.is_empty() {
+            summary += &format!(
+                ", {} error(s): {}",
+                self.errors.len(),
+                self.errors.join("; ")
+            );
+        }
+        summary
+    }
+}
+
+/// Runs a plan, an original is only deleted if its copy was sent
+pub fn run(steps: Vec<Step>) -> Report {
+    let mut report = Report::default();
+    let mut copied = false;
+    for step in steps {
+        match step {
+            Step::Copy { target, content } => match target.say(&content) {
+                Ok(_) => {
+                    copied = true;
+                    report.copied += 1;
+                }
+                Err(err) => {
+                    copied = false;
+                    report
+                        .errors
+                        .push(format!("Unable to copy message: {}", err));
+                }
+            },
+            Step::Delete { channel, message } => {
+                if !copied {
+                    continue;
+                }
+                match channel.delete_message(message) {
+                    Ok(_) => report.deleted += 1,
+                    Err(err) => report
+                        .errors
+                        .push(format!("Copied but unable to delete {}: {}", message, err)),
+                }
+            }
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_target, plan, ChannelTarget, Step};
+    use helpers::testing::message_item;
+    use serenity::model::id::{ChannelId, MessageId};
+
+    #[test]
+    fn targets() {
+        assert_eq!(parse_target("#other"), Some(ChannelTarget::Name("other")));
+        assert_eq!(
+            parse_target("<#1234>"),
+            Some(ChannelTarget::Id(ChannelId(1234)))
+        );
+        assert_eq!(
+            parse_target(" 1234"),
+            Some(ChannelTarget::Id(ChannelId(1234)))
+        );
+        assert_eq!(parse_target("#"), None);
+        assert_eq!(parse_target("other"), None);
+    }
+
+    #[test]
+    fn move_plan() {
+        let messages = vec![message_item(5, 10, "hello"), message_item(6, 10, "")];
+
+        assert_eq!(
+            plan(&messages, ChannelId(99), true),
+            vec![
+                Step::Copy {
+                    target: ChannelId(99),
+                    content: "hello".to_owned(),
+                },
+                Step::Delete {
+                    channel: ChannelId(1),
+                    message: MessageId(5),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn copy_plan() {
+        let messages = vec![
+            message_item(5, 10, "first"),
+            message_item(6, 11, "second"),
+            message_item(7, 10, "third"),
+        ];
+
+        assert_eq!(
+            plan(&messages, ChannelId(99), false),
+            vec![
+                Step::Copy {
+                    target: ChannelId(99),
+                    content: "first".to_owned(),
+                },
+                Step::Copy {
+                    target: ChannelId(99),
+                    content: "second".to_owned(),
+                },
+                Step::Copy {
+                    target: ChannelId(99),
+                    content: "third".to_owned(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Clearing local state for the `wipe` command, nothing on Discord is touched
+
+use serenity::model::id::{ChannelId, MessageId};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use model::{CommandHistory, Outbox};
+
+/// A piece of local state removed by `wipe`
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalState {
+    /// Loaded and background message buffers
+    MessageBuffers,
+    CommandHistory,
+    /// Messages held for `split-send` or kept after a failed send
+    Drafts,
+    /// Where each channel was scrolled back to
+    ScrollPositions,
+    /// A persisted state file, along with anything that would rewrite it on exit
+    StateFile(PathBuf),
+}
+
+impl fmt::Display for LocalState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LocalState::MessageBuffers => write!(f, "Loaded messages"),
+            LocalState::CommandHistory => write!(f, "Command history"),
+            LocalState::Drafts => write!(f, "Unsent messages"),
+            LocalState::ScrollPositions => write!(f, "Scroll positions"),
+            LocalState::StateFile(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Everything `wipe` removes, given the locations of the persisted state and command history
+pub fn plan(state_file: PathBuf, history_file: PathBuf) -> Vec<LocalState> {
+    vec![
+        LocalState::MessageBuffers,
+        LocalState::CommandHistory,
+        LocalState::Drafts,
+        LocalState::ScrollPositions,
+        LocalState::StateFile(state_file),
+        LocalState::StateFile(history_file),
+    ]
+}
+
+/// The in-memory state `wipe` clears, apart from the message buffers of the view
+pub struct Memory<'a> {
+    pub history: &'a mut CommandHistory,
+    pub outbox: &'a mut Outbox,
+    pub anchors: &'a mut HashMap<ChannelId, MessageId>,
+}
+
+impl<'a> Memory<'a> {
+    /// Clears what `step` stands for, if it is held here
+    pub fn clear(&mut self, step: &LocalState) {
+        match step {
+            LocalState::CommandHistory => self.history.clear(),
+            LocalState::Drafts => self.outbox.clear(),
+            LocalState::ScrollPositions => self.anchors.clear(),
+            LocalState::MessageBuffers | LocalState::StateFile(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{plan, LocalState, Memory};
+    use model::{CommandHistory, Outbox};
+    use serenity::model::id::{ChannelId, MessageId};
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn files() -> (PathBuf, PathBuf) {
+        (
+            PathBuf::from("/home/user/.config/dex/persistent_state.toml"),
+            PathBuf::from("/home/user/.config/dex/command_history"),
+        )
+    }
+
+    #[test]
+    fn wipes_local_state_only() {
+        let (state_file, history_file) = files();
+        let plan = plan(state_file.clone(), history_file.clone());
+
+        let removed: Vec<_> = plan
+            .iter()
+            .filter_map(|step| match step {
+                LocalState::StateFile(path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(removed, vec![state_file, history_file]);
+        assert!(plan.contains(&LocalState::MessageBuffers));
+    }
+
+    #[test]
+    fn wipe_clears_memory() {
+        let mut history = CommandHistory::new();
+        history.push("grep /secret/".to_owned());
+        let mut outbox = Outbox::new();
+        outbox.hold(ChannelId(1), "too long".to_owned());
+        outbox.failed(ChannelId(1), "unsent".to_owned());
+        let mut anchors = HashMap::new();
+        anchors.insert(ChannelId(1), MessageId(2));
+
+        let (state_file, history_file) = files();
+        {
+            let mut memory = Memory {
+                history: &mut history,
+                outbox: &mut outbox,
+                anchors: &mut anchors,
+            };
+            for step in plan(state_file, history_file) {
+                memory.clear(&step);
+            }
+        }
+
+        assert!(history.entries().is_empty());
+        assert_eq!(outbox.take_held(), None);
+        assert_eq!(outbox.retry(), None);
+        assert!(anchors.is_empty());
+    }
+}
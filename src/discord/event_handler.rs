@@ -40,7 +40,12 @@ impl EventHandler for Handler {
     }
 
     // Called when discord responds READY
-    fn ready(&self, _: Context, _: Ready) {
-        self.0.lock().send(DiscordReady).unwrap();
+    fn ready(&self, _: Context, ready: Ready) {
+        self.0.lock().send(DiscordReady(Box::new(ready))).unwrap();
+    }
+
+    // Called when a dropped gateway connection is resumed
+    fn resume(&self, _: Context, _: ResumedEvent) {
+        self.0.lock().send(Resumed).unwrap();
     }
 }
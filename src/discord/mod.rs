@@ -3,8 +3,10 @@ pub mod utils;
 
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use serenity::client::bridge::gateway::ShardManager;
+use serenity::model::gateway::Game;
 use serenity::prelude::*;
 use serenity::Client;
 
@@ -35,6 +37,21 @@ impl DiscordClient {
         Ok(DiscordClient { shard_manager })
     }
 
+    /// Sets the rich presence game on every running shard
+    pub fn set_game(&self, game: Option<Game>) {
+        let manager = self.shard_manager.lock();
+        for runner in manager.runners.lock().values() {
+            runner.runner_tx.set_game(game.clone());
+        }
+    }
+
+    /// Heartbeat latency of the first shard that has reported one
+    pub fn latency(&self) -> Option<Duration> {
+        let manager = self.shard_manager.lock();
+        let runners = manager.runners.lock();
+        runners.values().filter_map(|runner| runner.latency).next()
+    }
+
     pub fn shutdown(&self) {
         debug!("Shutting down");
         self.shard_manager.lock().shutdown_all();
@@ -1,11 +1,16 @@
+use serde_json::Value;
+use serenity::http;
 use serenity::model::channel::{Channel, Message};
 use serenity::model::event::MessageUpdateEvent;
 use serenity::model::guild::{Guild, Member};
 use serenity::model::id::*;
+use serenity::model::user::User;
 use serenity::prelude::RwLock;
+use serenity::utils::Colour;
 use serenity::CACHE;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 pub fn guild(message: &Message) -> Option<Arc<RwLock<Guild>>> {
     guild_id(message).and_then(|guild_id| CACHE.read().guild(guild_id))
@@ -18,6 +23,143 @@ pub fn guild_id(message: &Message) -> Option<GuildId> {
     }
 }
 
+/// Guild a channel belongs to, if it is a cached guild channel
+pub fn channel_guild_id(channel_id: ChannelId) -> Option<GuildId> {
+    CACHE
+        .read()
+        .guild_channel(channel_id)
+        .map(|channel| channel.read().guild_id)
+}
+
+/// Link to a channel as Discord's clients copy it, direct messages being under `@me`
+pub fn channel_link(guild_id: Option<GuildId>, channel_id: ChannelId) -> String {
+    match guild_id {
+        Some(guild_id) => format!("https://discord.com/channels/{}/{}", guild_id, channel_id),
+        None => format!("https://discord.com/channels/@me/{}", channel_id),
+    }
+}
+
+/// Colour of a member's highest coloured role
+pub fn member_colour(guild_id: GuildId, user_id: UserId) -> Option<Colour> {
+    let guild = CACHE.read().guild(guild_id)?;
+    let member = guild.read().members.get(&user_id).cloned()?;
+    member.colour()
+}
+
+/// Per-user send interval of a channel with slow mode enabled
+pub fn slow_mode(channel_id: ChannelId) -> Option<Duration> {
+    let channel = CACHE.read().guild_channel(channel_id)?;
+    let rate = channel.read().slow_mode_rate;
+    match rate {
+        Some(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+        _ => None,
+    }
+}
+
+/// Whether `user_id` may bulk delete in a channel, which needs Manage Messages in a guild channel
+pub fn can_bulk_delete(channel_id: ChannelId, user_id: UserId) -> bool {
+    match CACHE.read().guild_channel(channel_id) {
+        Some(channel) => channel
+            .read()
+            .permissions_for(user_id)
+            .map(|perms| perms.manage_messages())
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Finds a channel in a guild by name
+pub fn find_channel(guild_id: GuildId, name: &str) -> Option<ChannelId> {
+    let guild = CACHE.read().guild(guild_id)?;
+    let guild = guild.read();
+    let channel_id = guild
+        .channels
+        .values()
+        .find(|channel| channel.read().name == name)
+        .map(|channel| channel.read().id);
+    channel_id
+}
+
+/// Name of a cached guild channel, falling back to its id
+pub fn channel_name(channel_id: ChannelId) -> String {
+    match CACHE.read().guild_channel(channel_id) {
+        Some(channel) => channel.read().name.clone(),
+        None => channel_id.to_string(),
+    }
+}
+
+/// Parses a `<@id>`/`<@!id>` mention or a raw user id
+pub fn parse_user_id(arg: &str) -> Option<UserId> {
+    let id = if arg.starts_with("<@") && arg.ends_with('>') {
+        arg[2..arg.len() - 1].trim_left_matches('!')
+    } else {
+        arg
+    };
+    id.parse().ok().map(UserId)
+}
+
+/// Finds a user from a mention, id, or a `@name` matching a username or nickname in `guild`
+pub fn find_user(guild_id: Option<GuildId>, arg: &str) -> Option<User> {
+    if let Some(user_id) = parse_user_id(arg) {
+        return match user_id.find() {
+            Some(user) => Some(user.read().clone()),
+            None => user_id.get().ok(),
+        };
+    }
+
+    let name = arg.trim_left_matches('@');
+    let guild = CACHE.read().guild(guild_id?)?;
+    let guild = guild.read();
+    let user = guild
+        .members
+        .values()
+        .find(|member| {
+            member.nick.as_ref().map(String::as_str) == Some(name)
+                || member.user.read().name == name
+        })
+        .map(|member| member.user.read().clone());
+    user
+}
+
+/// CDN url of a user's avatar, or of the default avatar if they have not set one
+pub fn avatar_url(user_id: UserId, avatar: Option<&str>, discriminator: u16) -> String {
+    match avatar {
+        Some(hash) => {
+            let ext = if hash.starts_with("a_") { "gif" } else { "png" };
+            format!(
+                "https://cdn.discordapp.com/avatars/{}/{}.{}?size=1024",
+                user_id, hash, ext
+            )
+        }
+        None => format!(
+            "https://cdn.discordapp.com/embed/avatars/{}.png",
+            discriminator % 5
+        ),
+    }
+}
+
+/// Body of a send message request, replying to `current` when `reply_to_current` is enabled
+pub fn message_payload(
+    channel_id: ChannelId,
+    content: &str,
+    current: Option<MessageId>,
+    reply_to_current: bool,
+) -> Value {
+    let mut payload = json!({ "content": content });
+    if let (true, Some(current)) = (reply_to_current, current) {
+        payload["message_reference"] = json!({
+            "channel_id": channel_id.0.to_string(),
+            "message_id": current.0.to_string(),
+        });
+    }
+    payload
+}
+
+/// Sends a message with a raw request, as serenity's builder cannot attach a reply reference
+pub fn send_message(channel_id: ChannelId, payload: &Value) -> ::serenity::Result<Message> {
+    http::send_message(channel_id.0, payload)
+}
+
 pub fn member(message: &Message) -> Option<Member> {
     guild(message).and_then(|g| g.read().members.get(&message.author.id).cloned())
 }
@@ -63,3 +205,76 @@ pub fn update_msg(msg: &mut Message, update: MessageUpdateEvent) {
     //     msg.embeds = embeds;
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{avatar_url, channel_guild_id, channel_link, message_payload, parse_user_id};
+    use helpers::testing::cache_guild_channel;
+    use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
+
+    #[test]
+    fn user_ids() {
+        assert_eq!(parse_user_id("<@1234>"), Some(UserId(1234)));
+        assert_eq!(parse_user_id("<@!1234>"), Some(UserId(1234)));
+        assert_eq!(parse_user_id("1234"), Some(UserId(1234)));
+        assert_eq!(parse_user_id("@bob"), None);
+    }
+
+    #[test]
+    fn channel_guild() {
+        cache_guild_channel(228_001, 228_002, "general");
+
+        assert_eq!(channel_guild_id(ChannelId(228_001)), Some(GuildId(228_002)));
+        assert_eq!(channel_guild_id(ChannelId(228_003)), None);
+    }
+
+    #[test]
+    fn channel_links() {
+        assert_eq!(
+            channel_link(Some(GuildId(1)), ChannelId(22)),
+            "https://discord.com/channels/1/22"
+        );
+        assert_eq!(
+            channel_link(None, ChannelId(22)),
+            "https://discord.com/channels/@me/22"
+        );
+    }
+
+    #[test]
+    fn reply_payloads() {
+        let channel = ChannelId(1);
+        let current = Some(MessageId(2));
+        let plain = json!({ "content": "hi" });
+
+        assert_eq!(message_payload(channel, "hi", current, false), plain);
+        assert_eq!(message_payload(channel, "hi", None, true), plain);
+        assert_eq!(
+            message_payload(channel, "hi", current, true),
+            json!({
+                "content": "hi",
+                "message_reference": { "channel_id": "1", "message_id": "2" },
+            })
+        );
+    }
+
+    #[test]
+    fn avatar_urls() {
+        assert_eq!(
+            avatar_url(
+                UserId(80351110224678912),
+                Some("8342729096ea3675442027381ff50dfe"),
+                1337
+            ),
+            "https://cdn.discordapp.com/avatars/80351110224678912/\
+             8342729096ea3675442027381ff50dfe.png?size=1024"
+        );
+        assert_eq!(
+            avatar_url(UserId(1), Some("a_1269e74af4df7417b13759eae50c83dc"), 1),
+            "https://cdn.discordapp.com/avatars/1/a_1269e74af4df7417b13759eae50c83dc.gif?size=1024"
+        );
+        assert_eq!(
+            avatar_url(UserId(1), None, 1337),
+            "https://cdn.discordapp.com/embed/avatars/2.png"
+        );
+    }
+}
@@ -6,6 +6,10 @@ use serenity;
 #[fail(display = "Unable to locate user home directory")]
 pub struct HomeDirError;
 
+#[derive(Fail, Debug)]
+#[fail(display = "Invalid session name: {:?}", _0)]
+pub struct InvalidSessionName(pub String);
+
 #[derive(Fail, Debug)]
 #[fail(display = "Error originating from Serentity:\n{}", _0)]
 pub struct InternalSerenityError(pub String);
@@ -15,3 +19,35 @@ impl From<serenity::Error> for InternalSerenityError {
         InternalSerenityError(err.description().into())
     }
 }
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum CommandError {
+    #[fail(display = "Unknown command: {}", _0)]
+    Unknown(String),
+    #[fail(display = "Ambiguous command {}, could be: {}", _0, _1)]
+    Ambiguous(String, String),
+    #[fail(display = "Not a message link or id: {}", _0)]
+    InvalidMessage(String),
+    #[fail(display = "Not an emoji: {}", _0)]
+    InvalidEmoji(String),
+}
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum RangeError {
+    #[fail(display = "No messages loaded")]
+    Empty,
+    #[fail(display = "Address {} is out of range", _0)]
+    OutOfBounds(String),
+    #[fail(display = "No message matches {}", _0)]
+    NoMatch(String),
+    #[fail(display = "Invalid pattern {}: {}", _0, _1)]
+    InvalidRegex(String, String),
+}
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum EditorError {
+    #[fail(display = "Unable to run {}: {}", _0, _1)]
+    Spawn(String, String),
+    #[fail(display = "{} exited with an error, nothing was changed", _0)]
+    Failed(String),
+}
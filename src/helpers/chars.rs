@@ -38,6 +38,13 @@ impl CharSet {
         }
     }
 
+    pub fn pin(&self) -> char {
+        match self.char_type {
+            Unicode => '\u{1f4cc}', // 📌
+            Nerd => '\u{f08d}',
+        }
+    }
+
     pub fn paper_clip(&self) -> char {
         // Alternates:
         // Paperclip
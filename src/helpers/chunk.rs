@@ -0,0 +1,58 @@
+/// Longest message Discord accepts, in characters
+pub const MAX_MESSAGE_LEN: usize = 2000;
+
+/// Splits `content` into parts of at most `limit` characters, breaking between paragraphs, then
+/// lines, then words where possible
+pub fn chunk(content: &str, limit: usize) -> Vec<String> {
+    let limit = limit.max(1);
+    let mut parts = Vec::new();
+    let mut rest = content;
+    while rest.chars().count() > limit {
+        let end = rest
+            .char_indices()
+            .nth(limit)
+            .map(|(i, _)| i)
+            .unwrap_or_else(|| rest.len());
+        let window = &rest[..end];
+        let (split, skip) = ["\n\n", "\n", " "]
+            .iter()
+            .filter_map(|sep| window.rfind(sep).filter(|&i| i > 0).map(|i| (i, sep.len())))
+            .next()
+            .unwrap_or((end, 0));
+        parts.push(rest[..split].to_owned());
+        rest = &rest[split + skip..];
+    }
+    if !rest.is_empty() {
+        parts.push(rest.to_owned());
+    }
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use super::chunk;
+
+    #[test]
+    fn never_exceeds_limit() {
+        let content = "word ".repeat(100) + &"x".repeat(75);
+        let parts = chunk(&content, 30);
+        assert!(parts.iter().all(|part| part.chars().count() <= 30));
+        assert_eq!(parts.last().map(String::as_str), Some(&"x".repeat(15)[..]));
+        assert!(chunk("", 30).is_empty());
+        assert_eq!(chunk("short", 30), vec!["short"]);
+    }
+
+    #[test]
+    fn prefers_line_boundaries() {
+        assert_eq!(
+            chunk("first line\nsecond line here", 20),
+            vec!["first line", "second line here"]
+        );
+        assert_eq!(
+            chunk("one two\n\nthree four\nfive", 22),
+            vec!["one two", "three four\nfive"]
+        );
+        // Multi-byte characters are counted, not bytes
+        assert_eq!(chunk("ééééé", 2), vec!["éé", "éé", "é"]);
+    }
+}
@@ -0,0 +1,37 @@
+use failure::Error;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Copies text to somewhere it can be pasted from
+pub trait Clipboard {
+    fn copy(&self, text: &str) -> Result<(), Error>;
+}
+
+/// The desktop clipboard, through `pbcopy` or `xclip`
+pub struct SystemClipboard;
+
+impl Clipboard for SystemClipboard {
+    fn copy(&self, text: &str) -> Result<(), Error> {
+        let mut command = if cfg!(target_os = "macos") {
+            Command::new("pbcopy")
+        } else {
+            let mut command = Command::new("xclip");
+            command.args(&["-selection", "clipboard"]);
+            command
+        };
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        if let Some(ref mut stdin) = child.stdin {
+            stdin.write_all(text.as_bytes())?;
+        }
+        let status = child.wait()?;
+        if !status.success() {
+            bail!("Clipboard command failed: {}", status);
+        }
+        Ok(())
+    }
+}
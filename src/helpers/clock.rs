@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+
+#[cfg(test)]
+use chrono::Duration;
+use std::cell::Cell;
+
+/// Source of the current time, so code reading it can be driven by tests
+pub trait Clock {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock standing still at a set time, moved only with `advance`
+pub struct FakeClock {
+    now: Cell<DateTime<Utc>>,
+}
+
+impl FakeClock {
+    pub fn new(now: DateTime<Utc>) -> FakeClock {
+        FakeClock {
+            now: Cell::new(now),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        self.now.get()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Clock, FakeClock};
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn fake_clock_only_moves_when_advanced() {
+        let start = Utc.ymd(2018, 6, 1).and_hms(12, 0, 0);
+        let clock = FakeClock::new(start);
+
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start);
+        clock.advance(Duration::seconds(90));
+        assert_eq!(clock.now(), start + Duration::seconds(90));
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+/// Time left before an action last done at `last` may be repeated, `None` once it is allowed
+pub fn remaining(last: Instant, now: Instant, interval: Duration) -> Option<Duration> {
+    if now < last {
+        return Some(interval);
+    }
+    let elapsed = now.duration_since(last);
+    if elapsed >= interval {
+        None
+    } else {
+        Some(interval - elapsed)
+    }
+}
+
+/// Whole seconds left, rounded up so a countdown never shows zero while still waiting
+pub fn seconds_left(remaining: Duration) -> u64 {
+    if remaining.subsec_nanos() > 0 {
+        remaining.as_secs() + 1
+    } else {
+        remaining.as_secs()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{remaining, seconds_left};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn cooldown_remaining() {
+        let sent = Instant::now();
+        let interval = Duration::from_secs(10);
+
+        assert_eq!(remaining(sent, sent, interval), Some(interval));
+        assert_eq!(
+            remaining(sent, sent + Duration::from_secs(4), interval),
+            Some(Duration::from_secs(6))
+        );
+        assert_eq!(remaining(sent, sent + interval, interval), None);
+        assert_eq!(
+            remaining(sent, sent + Duration::from_secs(60), interval),
+            None
+        );
+    }
+
+    #[test]
+    fn countdown_rounds_up() {
+        assert_eq!(seconds_left(Duration::from_millis(5100)), 6);
+        assert_eq!(seconds_left(Duration::from_secs(5)), 5);
+    }
+}
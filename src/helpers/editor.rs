@@ -0,0 +1,168 @@
+//! Editing text in the user's `$EDITOR`, with the terminal handed over to it
+
+use failure::Error;
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use error::EditorError;
+
+/// Editor from `$VISUAL` or `$EDITOR`, vi when neither is set
+pub fn editor_command() -> String {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_owned())
+}
+
+/// How text gets edited: the file it is written to and the command that edits it
+#[derive(Debug, PartialEq)]
+pub struct Plan {
+    pub path: PathBuf,
+    pub program: String,
+    /// The editor's own arguments followed by the file
+    pub args: Vec<String>,
+}
+
+impl Plan {
+    /// Edits a file in `dir` with `editor`, which may carry arguments like `code --wait`
+    pub fn new(editor: &str, dir: &Path) -> Plan {
+        let mut words = editor.split_whitespace().map(str::to_owned);
+        let program = words.next().unwrap_or_else(|| "vi".to_owned());
+        let path = dir.join(format!("dex-draft-{}.md", process::id()));
+        let mut args: Vec<_> = words.collect();
+        args.push(path.to_string_lossy().into_owned());
+        Plan {
+            path,
+            program,
+            args,
+        }
+    }
+
+    /// Writes the text to edit to a new file only the user can read, never one already there
+    pub fn prepare(&self, text: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&self.path)?;
+        file.write_all(text.as_bytes())
+    }
+
+    /// Runs the editor, waiting for it to exit
+    pub fn run(&self) -> Result<(), EditorError> {
+        match Command::new(&self.program).args(&self.args).status() {
+            Ok(ref status) if status.success() => Ok(()),
+            Ok(_) => Err(EditorError::Failed(self.program.clone())),
+            Err(err) => Err(EditorError::Spawn(self.program.clone(), err.to_string())),
+        }
+    }
+
+    /// Reads back the edited text and removes the file, `None` when it was emptied
+    pub fn result(&self) -> io::Result<Option<String>> {
+        let text = fs::read_to_string(&self.path)?;
+        self.discard();
+        Ok(edited(&text))
+    }
+
+    pub fn discard(&self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Text as saved, without the newline editors add at the end, `None` if nothing is left
+fn edited(text: &str) -> Option<String> {
+    let text = text.trim_right_matches(|ch: char| ch == '\n' || ch == '\r');
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(text.to_owned())
+    }
+}
+
+/// Edits `text` in the user's editor, `None` when they emptied it to abort
+pub fn edit(text: &str) -> Result<Option<String>, Error> {
+    let plan = Plan::new(&editor_command(), &env::temp_dir());
+    plan.prepare(text)?;
+    if let Err(err) = plan.run() {
+        plan.discard();
+        return Err(err)?;
+    }
+    Ok(plan.result()?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{edited, Plan};
+    use error::EditorError;
+    use std::env;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process;
+
+    #[test]
+    fn editor_invocation() {
+        let dir = env::temp_dir();
+        let plan = Plan::new("code --wait", &dir);
+
+        assert_eq!(plan.program, "code");
+        assert!(plan.path.starts_with(&dir));
+        assert_eq!(
+            plan.args,
+            vec![
+                "--wait".to_owned(),
+                plan.path.to_string_lossy().into_owned()
+            ]
+        );
+        assert_eq!(Plan::new("", &dir).program, "vi");
+    }
+
+    #[test]
+    fn draft_round_trip() {
+        let plan = Plan::new("vi", &env::temp_dir());
+        plan.prepare("draft").unwrap();
+        assert_eq!(fs::read_to_string(&plan.path).unwrap(), "draft");
+
+        // Standing in for the editor
+        fs::write(&plan.path, "draft, edited\n").unwrap();
+        assert_eq!(plan.result().unwrap(), Some("draft, edited".to_owned()));
+        assert!(!plan.path.exists());
+    }
+
+    #[test]
+    fn draft_is_private() {
+        let dir = env::temp_dir().join(format!("dex-draft-test-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let plan = Plan::new("vi", &dir);
+        plan.prepare("draft").unwrap();
+        let mode = fs::metadata(&plan.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Whatever is already at the path, a planted link included, is left alone
+        assert!(plan.prepare("other").is_err());
+        assert_eq!(fs::read_to_string(&plan.path).unwrap(), "draft");
+        plan.discard();
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn emptied_draft_aborts() {
+        assert_eq!(edited(""), None);
+        assert_eq!(edited(" \n\n"), None);
+        assert_eq!(edited("line\nline\n\n"), Some("line\nline".to_owned()));
+    }
+
+    #[test]
+    fn missing_editor() {
+        let plan = Plan::new("dex-no-such-editor", &env::temp_dir());
+        match plan.run() {
+            Err(EditorError::Spawn(program, _)) => assert_eq!(program, "dex-no-such-editor"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Emoji shortcodes, for terminals that cannot draw emoji glyphs
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Variation selector that asks for the emoji presentation of a character
+const EMOJI_PRESENTATION: char = '\u{fe0f}';
+
+/// Shortcode names of common emoji, as Discord spells them
+const SHORTCODES: &[(&str, &str)] = &[
+    ("grinning", "\u{1f600}"),
+    ("smiley", "\u{1f603}"),
+    ("smile", "\u{1f604}"),
+    ("grin", "\u{1f601}"),
+    ("laughing", "\u{1f606}"),
+    ("sweat_smile", "\u{1f605}"),
+    ("joy", "\u{1f602}"),
+    ("rofl", "\u{1f923}"),
+    ("slight_smile", "\u{1f642}"),
+    ("upside_down", "\u{1f643}"),
+    ("wink", "\u{1f609}"),
+    ("blush", "\u{1f60a}"),
+    ("innocent", "\u{1f607}"),
+    ("heart_eyes", "\u{1f60d}"),
+    ("kissing_heart", "\u{1f618}"),
+    ("yum", "\u{1f60b}"),
+    ("stuck_out_tongue", "\u{1f61b}"),
+    ("thinking", "\u{1f914}"),
+    ("neutral_face", "\u{1f610}"),
+    ("expressionless", "\u{1f611}"),
+    ("no_mouth", "\u{1f636}"),
+    ("smirk", "\u{1f60f}"),
+    ("unamused", "\u{1f612}"),
+    ("rolling_eyes", "\u{1f644}"),
+    ("grimacing", "\u{1f62c}"),
+    ("relieved", "\u{1f60c}"),
+    ("pensive", "\u{1f614}"),
+    ("sleepy", "\u{1f62a}"),
+    ("sleeping", "\u{1f634}"),
+    ("sunglasses", "\u{1f60e}"),
+    ("confused", "\u{1f615}"),
+    ("worried", "\u{1f61f}"),
+    ("frowning2", "\u{2639}"),
+    ("open_mouth", "\u{1f62e}"),
+    ("flushed", "\u{1f633}"),
+    ("cry", "\u{1f622}"),
+    ("sob", "\u{1f62d}"),
+    ("scream", "\u{1f631}"),
+    ("angry", "\u{1f620}"),
+    ("rage", "\u{1f621}"),
+    ("skull", "\u{1f480}"),
+    ("poop", "\u{1f4a9}"),
+    ("clown", "\u{1f921}"),
+    ("ghost", "\u{1f47b}"),
+    ("robot", "\u{1f916}"),
+    ("eyes", "\u{1f440}"),
+    ("wave", "\u{1f44b}"),
+    ("ok_hand", "\u{1f44c}"),
+    ("v", "\u{270c}"),
+    ("point_up", "\u{261d}"),
+    ("point_right", "\u{1f449}"),
+    ("thumbsup", "\u{1f44d}"),
+    ("thumbsdown", "\u{1f44e}"),
+    ("clap", "\u{1f44f}"),
+    ("raised_hands", "\u{1f64c}"),
+    ("pray", "\u{1f64f}"),
+    ("muscle", "\u{1f4aa}"),
+    ("heart", "\u{2764}"),
+    ("orange_heart", "\u{1f9e1}"),
+    ("yellow_heart", "\u{1f49b}"),
+    ("green_heart", "\u{1f49a}"),
+    ("blue_heart", "\u{1f499}"),
+    ("purple_heart", "\u{1f49c}"),
+    ("broken_heart", "\u{1f494}"),
+    ("sparkles", "\u{2728}"),
+    ("star", "\u{2b50}"),
+    ("fire", "\u{1f525}"),
+    ("100", "\u{1f4af}"),
+    ("tada", "\u{1f389}"),
+    ("rocket", "\u{1f680}"),
+    ("zap", "\u{26a1}"),
+    ("warning", "\u{26a0}"),
+    ("white_check_mark", "\u{2705}"),
+    ("x", "\u{274c}"),
+    ("question", "\u{2753}"),
+    ("exclamation", "\u{2757}"),
+    ("bug", "\u{1f41b}"),
+    ("coffee", "\u{2615}"),
+    ("beer", "\u{1f37a}"),
+    ("pizza", "\u{1f355}"),
+    ("cookie", "\u{1f36a}"),
+    ("cat", "\u{1f431}"),
+    ("dog", "\u{1f436}"),
+    ("crab", "\u{1f980}"),
+    ("eggplant", "\u{1f346}"),
+    ("pushpin", "\u{1f4cc}"),
+    ("paperclip", "\u{1f4ce}"),
+    ("lock", "\u{1f512}"),
+    ("bell", "\u{1f514}"),
+    ("speaker", "\u{1f508}"),
+    ("sound", "\u{1f509}"),
+    ("loud_sound", "\u{1f50a}"),
+];
+
+/// The emoji a grapheme shows, ignoring the presentation selector
+fn shortcode(grapheme: &str) -> Option<&'static str> {
+    let glyph = grapheme.trim_right_matches(EMOJI_PRESENTATION);
+    SHORTCODES
+        .iter()
+        .find(|&&(_, emoji)| emoji == glyph)
+        .map(|&(name, _)| name)
+}
+
+/// The emoji a shortcode names, given without its colons
+pub fn from_shortcode(name: &str) -> Option<&'static str> {
+    SHORTCODES
+        .iter()
+        .find(|&&(known, _)| known == name)
+        .map(|&(_, emoji)| emoji)
+}
+
+/// Replaces every known emoji in `content` with its `:shortcode:`
+pub fn to_text(content: &str) -> String {
+    let mut text = String::with_capacity(content.len());
+    for grapheme in UnicodeSegmentation::graphemes(content, true) {
+        match shortcode(grapheme) {
+            Some(name) => {
+                text.push(':');
+                text.push_str(name);
+                text.push(':');
+            }
+            None => text.push_str(grapheme),
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::{from_shortcode, to_text};
+
+    #[test]
+    fn replaces_emoji_with_shortcodes() {
+        assert_eq!(to_text("nice \u{1f44d}\u{1f525}"), "nice :thumbsup::fire:");
+        assert_eq!(to_text("I \u{2764}\u{fe0f} rust"), "I :heart: rust");
+    }
+
+    #[test]
+    fn shortcode_lookup() {
+        assert_eq!(from_shortcode("thumbsup"), Some("\u{1f44d}"));
+        assert_eq!(from_shortcode(":thumbsup:"), None);
+        assert_eq!(from_shortcode("nope"), None);
+    }
+
+    #[test]
+    fn keeps_other_text() {
+        assert_eq!(to_text("plain text, caf\u{e9}"), "plain text, caf\u{e9}");
+        // Unknown emoji are left as they are
+        assert_eq!(to_text("\u{1f9ff}"), "\u{1f9ff}");
+    }
+}
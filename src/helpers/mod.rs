@@ -1,2 +1,13 @@
 pub mod chars;
+pub mod chunk;
+pub mod clipboard;
+pub mod clock;
+pub mod cooldown;
+pub mod editor;
+pub mod emoji;
+pub mod opener;
+pub mod relative;
 pub mod signal;
+#[cfg(test)]
+pub mod testing;
+pub mod timezone;
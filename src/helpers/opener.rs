@@ -0,0 +1,27 @@
+use failure::Error;
+
+use std::process::{Command, Stdio};
+
+/// Opens URLs outside of the terminal
+pub trait Opener {
+    fn open(&self, url: &str) -> Result<(), Error>;
+}
+
+/// Opens URLs with the desktop's default handler
+pub struct SystemOpener;
+
+impl Opener for SystemOpener {
+    fn open(&self, url: &str) -> Result<(), Error> {
+        let program = if cfg!(target_os = "macos") {
+            "open"
+        } else {
+            "xdg-open"
+        };
+        Command::new(program)
+            .arg(url)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{DateTime, Duration, FixedOffset};
+
+use helpers::clock::Clock;
+use model::RelativeUnit;
+
+/// Short age of a message like `5m` or `2d`, with nothing finer than `finest`
+pub fn format_relative(elapsed: Duration, finest: RelativeUnit) -> String {
+    let seconds = elapsed.num_seconds().max(0);
+    if seconds < 60 {
+        return match finest {
+            RelativeUnit::Seconds => format!("{}s", seconds),
+            RelativeUnit::Minutes => "now".to_owned(),
+        };
+    }
+    match seconds {
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s if s < 24 * 60 * 60 => format!("{}h", s / (60 * 60)),
+        s => format!("{}d", s / (24 * 60 * 60)),
+    }
+}
+
+/// Age of something sent at `sent`, as it is by `clock`
+pub fn format_age<C>(sent: DateTime<FixedOffset>, clock: &C, finest: RelativeUnit) -> String
+where
+    C: Clock,
+{
+    format_relative(clock.now().signed_duration_since(sent), finest)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format_age, format_relative};
+    use chrono::{DateTime, Duration, Utc};
+    use helpers::clock::FakeClock;
+    use model::RelativeUnit;
+
+    #[test]
+    fn sub_minute() {
+        let seconds = |s| format_relative(Duration::seconds(s), RelativeUnit::Seconds);
+        assert_eq!(seconds(0), "0s");
+        assert_eq!(seconds(3), "3s");
+        assert_eq!(seconds(45), "45s");
+        assert_eq!(seconds(59), "59s");
+        assert_eq!(seconds(60), "1m");
+
+        assert_eq!(
+            format_relative(Duration::seconds(45), RelativeUnit::Minutes),
+            "now"
+        );
+    }
+
+    #[test]
+    fn coarse_units() {
+        let minutes = |s| format_relative(Duration::seconds(s), RelativeUnit::Minutes);
+        assert_eq!(minutes(125), "2m");
+        assert_eq!(minutes(2 * 60 * 60 + 5), "2h");
+        assert_eq!(minutes(3 * 24 * 60 * 60), "3d");
+        // Clock skew can put messages slightly in the future
+        assert_eq!(minutes(-5), "now");
+    }
+
+    #[test]
+    fn fixed_clock() {
+        let sent = DateTime::parse_from_rfc3339("2018-06-01T12:00:00+02:00").unwrap();
+        let now = DateTime::parse_from_rfc3339("2018-06-01T10:30:00Z").unwrap();
+        let clock = FakeClock::new(now.with_timezone(&Utc));
+
+        assert_eq!(format_age(sent, &clock, RelativeUnit::Minutes), "30m");
+        assert_eq!(format_age(sent, &clock, RelativeUnit::Seconds), "30m");
+        clock.advance(Duration::minutes(90));
+        assert_eq!(format_age(sent, &clock, RelativeUnit::Minutes), "2h");
+        let recent = sent + Duration::seconds(2 * 60 * 60 - 45);
+        assert_eq!(format_age(recent, &clock, RelativeUnit::Seconds), "45s");
+    }
+}
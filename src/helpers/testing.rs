@@ -0,0 +1,92 @@
+//! Fixtures shared by unit tests
+
+use serde_json::{self, Value};
+use serenity::model::channel::{GuildChannel, Message};
+use serenity::prelude::RwLock;
+use serenity::CACHE;
+
+use std::sync::mpsc;
+use std::sync::Arc;
+
+use model::{Context, MessageItem, Preferences, State, Theme};
+
+/// Raw gateway payload for a message, tweak fields before calling `from_value`
+pub fn message_value(id: u64, author_id: u64, content: &str) -> Value {
+    json!({
+        "id": id.to_string(),
+        "channel_id": "1",
+        "author": {
+            "id": author_id.to_string(),
+            "username": format!("user{}", author_id),
+            "discriminator": "0001",
+            "avatar": null,
+            "bot": false,
+        },
+        "content": content,
+        "timestamp": "2018-06-01T12:00:00+00:00",
+        "edited_timestamp": null,
+        "tts": false,
+        "mention_everyone": false,
+        "mentions": [],
+        "mention_roles": [],
+        "attachments": [],
+        "embeds": [],
+        "reactions": [],
+        "pinned": false,
+        "nonce": null,
+        "type": 0,
+    })
+}
+
+pub fn message_from_value(value: Value) -> Message {
+    serde_json::from_value(value).expect("Invalid message fixture")
+}
+
+pub fn message(id: u64, author_id: u64, content: &str) -> Message {
+    message_from_value(message_value(id, author_id, content))
+}
+
+pub fn message_item(id: u64, author_id: u64, content: &str) -> MessageItem {
+    MessageItem::DiscordMessage(Box::new(message(id, author_id, content)))
+}
+
+/// Raw payload for an image attachment
+pub fn attachment_value(id: u64, filename: &str) -> Value {
+    json!({
+        "id": id.to_string(),
+        "filename": filename,
+        "height": 100,
+        "width": 100,
+        "proxy_url": format!("https://media.discordapp.net/attachments/1/{}/{}", id, filename),
+        "url": format!("https://cdn.discordapp.com/attachments/1/{}/{}", id, filename),
+        "size": 1024,
+    })
+}
+
+/// Context with default preferences and state, whose events go nowhere
+pub fn context() -> Context {
+    let preferences: Preferences = ::toml::from_str("token = \"\"").unwrap();
+    let state: State = ::toml::from_str("").unwrap();
+    let (event_channel, _) = mpsc::channel();
+    Context::new(&preferences, &state, Theme::default(), event_channel)
+}
+
+/// Adds a text channel belonging to `guild_id` to the global cache
+pub fn cache_guild_channel(id: u64, guild_id: u64, name: &str) {
+    let channel: GuildChannel = serde_json::from_value(json!({
+        "id": id.to_string(),
+        "guild_id": guild_id.to_string(),
+        "name": name,
+        "type": 0,
+        "position": 0,
+        "permission_overwrites": [],
+        "topic": null,
+        "last_message_id": null,
+        "nsfw": false,
+    }))
+    .expect("Invalid channel fixture");
+    CACHE
+        .write()
+        .channels
+        .insert(channel.id, Arc::new(RwLock::new(channel)));
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, FixedOffset, Local};
+
+/// Parses a configured timezone: `UTC`, or an offset like `+05:30`, `-8` or `UTC-08:00`
+pub fn parse_offset(zone: &str) -> Option<FixedOffset> {
+    let zone = zone.trim();
+    let offset = if zone.starts_with("UTC") {
+        &zone[3..]
+    } else {
+        zone
+    };
+    if offset.is_empty() || offset == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let mut parts = offset[1..].splitn(2, ':');
+    let hours: i32 = parts.next()?.parse().ok()?;
+    let minutes: i32 = match parts.next() {
+        Some(minutes) => minutes.parse().ok()?,
+        None => 0,
+    };
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Formats a timestamp in the configured `timezone`, the local one without it
+pub fn format(
+    timestamp: DateTime<FixedOffset>,
+    timezone: Option<FixedOffset>,
+    fmt: &str,
+) -> String {
+    match timezone {
+        Some(offset) => timestamp.with_timezone(&offset).format(fmt).to_string(),
+        None => timestamp.with_timezone(&Local).format(fmt).to_string(),
+    }
+}
+
+/// Name shown for a timezone, the configured name or the offset from UTC
+pub fn label(name: Option<&str>, offset: FixedOffset) -> String {
+    if let Some(name) = name {
+        return name.to_owned();
+    }
+    let seconds = offset.local_minus_utc();
+    if seconds == 0 {
+        return "UTC".to_owned();
+    }
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{format, label, parse_offset};
+    use chrono::{DateTime, FixedOffset};
+
+    #[test]
+    fn offsets() {
+        assert_eq!(parse_offset("UTC"), Some(FixedOffset::east(0)));
+        assert_eq!(
+            parse_offset("+05:30"),
+            Some(FixedOffset::east(5 * 3600 + 1800))
+        );
+        assert_eq!(parse_offset("UTC-8"), Some(FixedOffset::west(8 * 3600)));
+        assert_eq!(parse_offset("PST"), None);
+        assert_eq!(parse_offset("+25:00"), None);
+    }
+
+    #[test]
+    fn formats_in_timezone() {
+        let timestamp = DateTime::parse_from_rfc3339("2018-06-01T12:00:00+00:00").unwrap();
+        let ist = FixedOffset::east(5 * 3600 + 1800);
+
+        assert_eq!(format(timestamp, Some(ist), "%H:%M"), "17:30");
+        assert_eq!(
+            format(timestamp, Some(FixedOffset::east(0)), "%H:%M"),
+            "12:00"
+        );
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(label(Some("PST"), FixedOffset::west(8 * 3600)), "PST");
+        assert_eq!(label(None, FixedOffset::west(8 * 3600)), "UTC-08:00");
+        assert_eq!(label(None, FixedOffset::east(5 * 3600 + 1800)), "UTC+05:30");
+        assert_eq!(label(None, FixedOffset::east(0)), "UTC");
+    }
+}
@@ -12,8 +12,13 @@ extern crate toml;
 extern crate log;
 extern crate notify_rust;
 extern crate parsing;
+extern crate regex;
 extern crate signal;
 extern crate textwrap;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+#[macro_use]
+extern crate serde_json;
 
 mod command;
 mod discord;
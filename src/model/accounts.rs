@@ -0,0 +1,107 @@
+/// An account dex can log in as
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Account {
+    /// Shown by `accounts` to tell accounts apart
+    pub name: String,
+    pub token: String,
+}
+
+/// Configured accounts and which one the session is logged in as
+pub struct Accounts {
+    accounts: Vec<Account>,
+    active: usize,
+}
+
+impl Accounts {
+    /// The account of the `token` preference, active, followed by any extra configured accounts
+    pub fn new(token: &str, extra: &[Account]) -> Accounts {
+        let mut accounts = vec![Account {
+            name: "default".to_owned(),
+            token: token.to_owned(),
+        }];
+        accounts.extend(extra.iter().cloned());
+        Accounts {
+            accounts,
+            active: 0,
+        }
+    }
+
+    pub fn active(&self) -> &Account {
+        &self.accounts[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Account> {
+        self.accounts.get(index)
+    }
+
+    /// Makes the account at `index` active, returning it, or `None` if there is no such account
+    pub fn switch(&mut self, index: usize) -> Option<&Account> {
+        if index >= self.accounts.len() {
+            return None;
+        }
+        self.active = index;
+        Some(&self.accounts[index])
+    }
+
+    /// One line per account numbered from 1 as `switch` takes them, marking the active one
+    pub fn listing(&self) -> Vec<String> {
+        self.accounts
+            .iter()
+            .enumerate()
+            .map(|(index, account)| {
+                let marker = if index == self.active { '*' } else { ' ' };
+                format!("{} {:>2}  {}", marker, index + 1, account.name)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Account, Accounts};
+
+    fn accounts() -> Accounts {
+        Accounts::new(
+            "main-token",
+            &[Account {
+                name: "alt".to_owned(),
+                token: "alt-token".to_owned(),
+            }],
+        )
+    }
+
+    #[test]
+    fn switch_changes_active_account() {
+        let mut accounts = accounts();
+        assert_eq!(accounts.active_index(), 0);
+        assert_eq!(accounts.active().token, "main-token");
+
+        assert_eq!(
+            accounts.switch(1).map(|account| account.name.as_str()),
+            Some("alt")
+        );
+        assert_eq!(accounts.active_index(), 1);
+        assert_eq!(accounts.active().token, "alt-token");
+    }
+
+    #[test]
+    fn switch_to_missing_account() {
+        let mut accounts = accounts();
+        accounts.switch(1);
+
+        assert_eq!(accounts.switch(2), None);
+        assert_eq!(accounts.active_index(), 1);
+    }
+
+    #[test]
+    fn listing_marks_active_account() {
+        let mut accounts = accounts();
+        accounts.switch(1);
+
+        assert_eq!(accounts.listing(), vec!["   1  default", "*  2  alt"]);
+    }
+}
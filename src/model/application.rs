@@ -1,17 +1,35 @@
+use chrono::Local;
 use failure::Error;
 use notify_rust::Notification;
 use serenity::prelude::RwLock;
-use serenity::CACHE;
 
-use std::sync::mpsc::{self, Receiver};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serenity::http;
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, MessageId};
+use termbuf::termion::event::Key;
 
 use command::CommandHandler;
+use discord::utils;
 use discord::DiscordClient;
+use helpers::chunk::{self, MAX_MESSAGE_LEN};
+use helpers::cooldown;
+use helpers::editor;
 use helpers::signal::SignalHandler;
+use model::keywords;
+use model::settle;
 use model::State as SavedState;
-use model::{Context, Event, MessageItem, Preferences};
-use view::View;
+use model::{
+    CommandHistory, Context, Event, MessageItem, Outbox, Preferences, ReadMarker, Settle, Theme,
+};
+use view::{self, Frame, View};
 
 enum State {
     NotReady,
@@ -24,14 +42,24 @@ pub struct Application {
     pub discord_client: DiscordClient,
     pub context: Arc<RwLock<Context>>,
     pub command_handler: CommandHandler,
+    pub command_history: RefCell<CommandHistory>,
     state: State,
     events: Receiver<Event>,
+    /// Events held back after a reconnect
+    settle: Option<Settle>,
+    /// When a message was last sent to each channel, for slow mode
+    last_sent: HashMap<ChannelId, Instant>,
+    /// Messages that could not be sent as they were, kept for `resend` and `split-send`
+    pub outbox: RefCell<Outbox>,
+    /// Unread messages in the open channel
+    read_marker: ReadMarker,
 }
 
 impl Application {
     pub fn new() -> Result<Application, Error> {
         let preferences = Preferences::load()?;
         let state = SavedState::load()?;
+        let theme = Theme::load()?;
 
         let state = state;
 
@@ -42,13 +70,22 @@ impl Application {
         let context = Arc::new(RwLock::new(Context::new(
             &preferences,
             &state,
+            theme,
             event_channel,
         )));
 
         let view = View::new(&context.clone());
+        view.footer.set_lasting_status("Connecting…".to_owned());
+
+        let read_marker = ReadMarker::new(context.read().mark_read_delay);
 
         let command_handler = CommandHandler::new();
 
+        let command_history = CommandHistory::load().unwrap_or_else(|err| {
+            warn!("Unable to load command history: {}", err);
+            CommandHistory::new()
+        });
+
         let discord_client = DiscordClient::start(&context.clone())?;
 
         Ok(Application {
@@ -56,8 +93,13 @@ impl Application {
             discord_client,
             context,
             command_handler,
+            command_history: RefCell::new(command_history),
             state: State::NotReady,
             events,
+            settle: None,
+            last_sent: HashMap::new(),
+            outbox: RefCell::new(Outbox::new()),
+            read_marker,
         })
     }
 
@@ -65,115 +107,477 @@ impl Application {
         self.view.message_view.load_messages(self);
 
         loop {
+            let mut timeout = None;
             match self.state {
-                State::NotReady => {
-                    println!("Loading...");
-                }
-                State::Ready => {
-                    self.view.present()?;
-                }
+                State::NotReady | State::Ready => match self.view.next_frame(Instant::now()) {
+                    Frame::Render => {
+                        self.view.present()?;
+                        self.update_read_marker();
+                    }
+                    Frame::Wait(delay) => timeout = Some(delay),
+                    Frame::Idle => {}
+                },
                 State::Exiting => {
                     debug!("Exiting event loop");
                     trace!("Saving state...");
+                    self.record_anchor();
                     self.context.read().save_state()?;
+                    if self.context.read().persist_state {
+                        self.command_history.borrow().save()?;
+                    }
                     debug!("Saved state");
                     break;
                 }
             }
-            self.wait_for_event();
+            self.wait_for_event(timeout);
         }
         Ok(())
     }
 
-    pub fn wait_for_event(&mut self) {
-        use termbuf::termion::event::Key;
-        let event = self.events.recv();
+    /// Handles the next event, giving up after `timeout` so a held back redraw can happen
+    pub fn wait_for_event(&mut self, timeout: Option<Duration>) {
+        let event = match timeout {
+            Some(timeout) => match self.events.recv_timeout(timeout) {
+                Ok(event) => Ok(event),
+                Err(RecvTimeoutError::Timeout) => return,
+                Err(RecvTimeoutError::Disconnected) => Err(mpsc::RecvError),
+            },
+            None => self.events.recv(),
+        };
         trace!("Event: {:?}", event);
         match event {
-            Ok(Event::InternalError(err)) => {
+            Ok(event) => self.handle_event(event),
+            Err(err) => error!("{:?}", err),
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        if let Some(ref mut settle) = self.settle {
+            if settle::is_buffered(&event) && settle.should_buffer(Instant::now()) {
+                settle.push(event);
+                return;
+            }
+        }
+
+        // Message buffer changes track their own dirty state
+        match event {
+            Event::NewMessage(_)
+            | Event::MessageDelete(..)
+            | Event::MessageDeleteBulk(..)
+            | Event::MessageUpdateEvent(_)
+            | Event::UserTyping
+            | Event::Notification(..)
+            | Event::InternalError(_) => {}
+            _ => self.view.mark_dirty(),
+        }
+        match event {
+            Event::InternalError(err) => {
                 error!("Internal error: {}", err);
             }
-            Ok(Event::DiscordReady) => {
+            Event::Status(status) => self.show_status(status),
+            Event::Notification(summary, body) => {
+                let quiet = self.context.read().quiet_hours;
+                if quiet.map_or(false, |quiet| quiet.contains(Local::now().time())) {
+                    return;
+                }
+                if let Err(e) = Notification::new().summary(&summary).body(&body).show() {
+                    self.send_err(format_err!("Error displaying notification: {}", e));
+                }
+            }
+            Event::DiscordReady(ready) => {
                 debug!("Discord ready");
-                self.context.write().current_user = Some(CACHE.read().user.clone());
+                match self.state {
+                    State::Ready => self.start_settle(),
+                    State::NotReady => self.view.footer.clear_status(),
+                    State::Exiting => {}
+                }
+                self.context.write().apply_ready(&ready);
                 self.state = State::Ready;
 
+                let activity = self.context.read().activity.clone();
+                if activity.is_some() {
+                    self.discord_client.set_game(activity);
+                }
+
                 self.view.guild_list.populate_guild_list();
             }
-            Ok(Event::Keypress(key)) => match key {
-                Key::Ctrl('c') | Key::Ctrl('d') => {
-                    self.discord_client.shutdown();
-                    self.state = State::Exiting;
-                }
-                key => {
-                    if let Err(err) = self.view.input_view.key_press(key) {
-                        self.send_err(format_err!("Error handling input: {}", err))
-                    }
+            Event::Keypress(key) => {
+                self.view.footer.dismiss_status();
+                self.handle_key(key)
+            }
+            Event::Click(x, y) => {
+                if !self.view.overlay.is_visible() {
+                    self.view.message_view.select_at(x, y);
                 }
-            },
-            Ok(Event::ShutdownAll) => {
+            }
+            Event::ShutdownAll => {
                 self.discord_client.shutdown();
                 self.state = State::Exiting;
             }
-            Ok(Event::NewMessage(msg)) => {
-                if Some(msg.channel_id) == self.context.read().channel {
+            Event::NewMessage(msg) => {
+                let active = Some(msg.channel_id) == self.context.read().channel;
+                if !msg.is_own() {
+                    let notify = active || {
+                        let context = self.context.read();
+                        keywords::should_notify(
+                            context.notify_keywords.as_ref(),
+                            &msg.content,
+                            msg.channel_id,
+                            &context.muted_channels,
+                        )
+                    };
+                    if notify {
+                        self.context
+                            .read()
+                            .event_channel
+                            .send(Event::Notification(
+                                msg.author.name.clone(),
+                                msg.content.clone(),
+                            ))
+                            .unwrap();
+                    }
+                }
+                if active {
                     if !msg.is_own() {
-                        if let Err(e) = Notification::new()
-                            .summary(&msg.author.name)
-                            .body(&msg.content)
-                            .show()
-                        {
-                            self.send_err(format_err!("Error displaying notification: {}", e));
-                        }
+                        self.read_marker.message_arrived();
                     }
                     self.view
                         .message_view
                         .add_msg(MessageItem::DiscordMessage(msg));
+                } else {
+                    let user = self
+                        .context
+                        .read()
+                        .current_user
+                        .as_ref()
+                        .map(|user| user.id);
+                    self.view
+                        .message_view
+                        .add_background_msg(MessageItem::DiscordMessage(msg), user);
                 }
             }
-            Ok(Event::MessageDelete(channel_id, message_id)) => {
+            Event::MessageDelete(channel_id, message_id) => {
                 self.view.message_view.delete_msg(channel_id, message_id)
             }
-            Ok(Event::MessageDeleteBulk(channel_id, message_ids)) => self
+            Event::MessageDeleteBulk(channel_id, message_ids) => self
                 .view
                 .message_view
                 .delete_msg_bulk(channel_id, &message_ids),
-            Ok(Event::MessageUpdateEvent(update)) => self.view.message_view.update_message(*update),
-            Ok(Event::ChannelUpdateEvent) => self.view.guild_list.populate_guild_list(),
-            Ok(Event::UserMessage(msg)) => {
-                if self
-                    .context
-                    .read()
-                    .channel
-                    .map(|channel| channel.say(msg))
-                    .is_none()
-                {
-                    self.send_err(format_err!("Unable to send message in current channel"))
+            Event::MessageUpdateEvent(update) => self.view.message_view.update_message(*update),
+            Event::ChannelUpdateEvent => self.view.guild_list.populate_guild_list(),
+            Event::UserMessage(msg) => {
+                let channel = self.context.read().channel;
+                match channel {
+                    Some(channel) => self.send_message(channel, msg, None),
+                    None => self.send_err(format_err!("Unable to send message in current channel")),
+                }
+            }
+            Event::Reply(channel, message, content) => {
+                self.send_message(channel, content, Some(message))
+            }
+            Event::Resend => {
+                let failed = self.outbox.borrow_mut().retry();
+                match failed {
+                    Some((channel, content)) => self.send_message(channel, content, None),
+                    None => self.show_status("Nothing to resend".to_owned()),
+                }
+            }
+            Event::SplitSend => {
+                let held = self.outbox.borrow_mut().take_held();
+                match held {
+                    Some((channel, content)) => {
+                        for part in chunk::chunk(&content, MAX_MESSAGE_LEN) {
+                            self.send_message(channel, part, None);
+                        }
+                    }
+                    None => self.show_status("Nothing to split".to_owned()),
                 }
             }
-            Ok(Event::SetChannel(new_chan)) => {
+            Event::ExternalEdit(message) => self.edit_externally(message),
+            Event::SetChannel(new_chan) => {
+                self.record_anchor();
                 self.context.write().channel = Some(new_chan);
+                self.read_marker.reset();
                 self.view.message_view.load_messages(self);
             }
-            Ok(Event::UserCommand(cmd)) => self.command_handler.execute(self, &cmd),
-            Ok(Event::UserTyping) => {
+            Event::SwitchAccount(index) => self.switch_account(index),
+            Event::UserCommand(cmd) => {
+                self.command_history.borrow_mut().push(cmd.clone());
+                self.command_handler.execute(self, &cmd)
+            }
+            Event::UserTyping => {
                 if let Some(channel) = self.context.read().channel {
                     if let Err(err) = channel.broadcast_typing() {
                         self.send_err(format_err!("Error broadcasting typing status: {}", err));
                     }
                 }
             }
-            Ok(Event::TypingStart(event)) => {
+            Event::TypingStart(event) => {
                 self.view.indicator.typing_start(event);
             }
-            Ok(Event::WindowSizeChange) => {
+            Event::WindowSizeChange => {
                 self.view.update_size();
             }
-            Err(err) => error!("{:?}", err),
+            Event::Redraw => {}
+            Event::Resumed => self.start_settle(),
+            Event::SettleTimeout => self.finish_settle(),
         }
     }
 
-    fn send_err(&self, err: Error) {
+    fn handle_key(&mut self, key: Key) {
+        match key {
+            Key::Ctrl('c') | Key::Ctrl('d') => {
+                self.discord_client.shutdown();
+                self.state = State::Exiting;
+            }
+            Key::Ctrl('e') if !self.view.input_view.is_command() => self.edit_externally(None),
+            Key::PageUp if self.view.overlay.is_visible() => self.view.overlay.scroll_by(-10),
+            Key::PageDown if self.view.overlay.is_visible() => self.view.overlay.scroll_by(10),
+            Key::PageUp => {
+                if !self.view.message_view.scroll_up(self) {
+                    self.send_status("Beginning of channel");
+                }
+            }
+            Key::PageDown => self.view.message_view.scroll_down(),
+            Key::Up if self.view.input_view.is_command() => {
+                let mut history = self.command_history.borrow_mut();
+                if let Some(command) = history.older() {
+                    self.view.input_view.set_command(command);
+                }
+            }
+            Key::Down if self.view.input_view.is_command() => {
+                let mut history = self.command_history.borrow_mut();
+                if let Some(command) = history.newer() {
+                    self.view.input_view.set_command(command);
+                }
+            }
+            key => {
+                if key == Key::Esc {
+                    self.view.overlay.hide();
+                    self.command_history.borrow_mut().reset_recall();
+                }
+                if let Err(err) = self.view.input_view.key_press(key) {
+                    self.send_err(format_err!("Error handling input: {}", err))
+                }
+            }
+        }
+    }
+
+    /// Edits the message being composed in `$EDITOR`, or `message` which is the user's own
+    fn edit_externally(&mut self, message: Option<Box<Message>>) {
+        let text = match message {
+            Some(ref msg) => msg.content.clone(),
+            None => self.view.input_view.text().to_owned(),
+        };
+        let edited = match self.view.suspend(|| editor::edit(&text)) {
+            Ok(edited) => edited,
+            Err(err) => {
+                error!("Unable to restore the terminal: {}", err);
+                self.discord_client.shutdown();
+                self.state = State::Exiting;
+                return;
+            }
+        };
+        match (edited, message) {
+            (Err(err), _) => self.show_status(err.to_string()),
+            (Ok(None), _) => self.show_status("Left empty, nothing was changed".to_owned()),
+            (Ok(Some(edited)), None) => self.view.input_view.set_text(edited),
+            (Ok(Some(edited)), Some(msg)) => {
+                match msg.channel_id.edit_message(msg.id, |m| m.content(&edited)) {
+                    Ok(msg) => self.view.message_view.apply_edit(&msg),
+                    Err(err) => self.show_status(format!("Unable to edit message: {}", err)),
+                }
+            }
+        }
+    }
+
+    /// Sends `content` to `channel`, replying to `reply_to` or otherwise to the current message
+    /// when `reply_to_current` is set
+    fn send_message(&mut self, channel: ChannelId, content: String, reply_to: Option<MessageId>) {
+        let len = content.chars().count();
+        if len > MAX_MESSAGE_LEN {
+            let parts = chunk::chunk(&content, MAX_MESSAGE_LEN).len();
+            self.show_status(format!(
+                "Message is {} characters, use split-send to send it in {} parts",
+                len, parts
+            ));
+            self.outbox.borrow_mut().hold(channel, content);
+            return;
+        }
+        let now = Instant::now();
+        if let (Some(interval), Some(&last)) =
+            (utils::slow_mode(channel), self.last_sent.get(&channel))
+        {
+            if let Some(remaining) = cooldown::remaining(last, now, interval) {
+                self.show_status(format!(
+                    "Slow mode is enabled, wait {}s",
+                    cooldown::seconds_left(remaining)
+                ));
+                self.view.input_view.set_text(content);
+                return;
+            }
+        }
+        let payload = match reply_to {
+            Some(message) => utils::message_payload(channel, &content, Some(message), true),
+            None => utils::message_payload(
+                channel,
+                &content,
+                self.view.message_view.current_message(),
+                self.context.read().reply_to_current,
+            ),
+        };
+        match utils::send_message(channel, &payload) {
+            Ok(msg) => {
+                // Shown right away rather than when the gateway echoes it back
+                self.view
+                    .message_view
+                    .add_msg(MessageItem::DiscordMessage(Box::new(msg)));
+                self.outbox.borrow_mut().sent(channel);
+                self.last_sent.insert(channel, now);
+                if let Some(interval) = utils::slow_mode(channel) {
+                    self.start_slow_mode_countdown(now + interval, interval);
+                }
+            }
+            Err(err) => {
+                self.outbox.borrow_mut().failed(channel, content);
+                self.send_err(format_err!("Unable to send message: {}", err));
+                self.send_status("Unable to send message, use resend to retry");
+            }
+        }
+    }
+
+    /// Marks the open channel read once its newest message has been visible long enough
+    fn update_read_marker(&mut self) {
+        let at_bottom = self.view.message_view.at_bottom();
+        if let Some(delay) = self.read_marker.observe(at_bottom, Instant::now()) {
+            let event_channel = self.context.read().event_channel.clone();
+            thread::spawn(move || {
+                thread::sleep(delay);
+                let _ = event_channel.send(Event::Redraw);
+            });
+        }
+        if self.view.footer.set_unseen(self.read_marker.unseen()) {
+            self.view.mark_dirty();
+        }
+    }
+
+    /// Shows the slow mode countdown in the footer, redrawing every second until it ends
+    fn start_slow_mode_countdown(&self, until: Instant, interval: Duration) {
+        self.view.footer.set_slow_mode_until(until);
+        let event_channel = self.context.read().event_channel.clone();
+        thread::spawn(move || {
+            for _ in 0..cooldown::seconds_left(interval) {
+                thread::sleep(Duration::from_secs(1));
+                if event_channel.send(Event::Redraw).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Logs in as another account, starting over from a fresh session as on startup
+    fn switch_account(&mut self, index: usize) {
+        let (previous, account) = {
+            let mut context = self.context.write();
+            let previous = context.accounts.active_index();
+            match context.accounts.switch(index) {
+                Some(account) => (previous, account.clone()),
+                None => return,
+            }
+        };
+        let previous_token = mem::replace(&mut self.context.write().token, account.token.clone());
+        // The current session is only torn down once the new one could be started
+        let client = match DiscordClient::start(&self.context) {
+            Ok(client) => client,
+            Err(err) => {
+                {
+                    let mut context = self.context.write();
+                    context.accounts.switch(previous);
+                    http::set_token(&previous_token);
+                    context.token = previous_token;
+                }
+                return self.show_status(format!("Unable to log in as {}: {}", account.name, err));
+            }
+        };
+        self.record_anchor();
+        self.discord_client.shutdown();
+        self.discord_client = client;
+        {
+            let mut context = self.context.write();
+            context.channel = None;
+            context.guild = None;
+            context.current_user = None;
+            context.guilds.clear();
+        }
+        self.settle = None;
+        self.read_marker.reset();
+        self.outbox.borrow_mut().clear();
+        self.view.message_view.clear();
+        self.view
+            .footer
+            .set_lasting_status(format!("Logging in as {}…", account.name));
+        self.state = State::NotReady;
+    }
+
+    /// Starts holding back events after a reconnect until the settle window passes
+    fn start_settle(&mut self) {
+        let window = self.context.read().reconnect_settle;
+        self.settle = Some(Settle::new(Instant::now(), window));
+        self.view
+            .footer
+            .set_lasting_status("Reconnecting…".to_owned());
+
+        let event_channel = self.context.read().event_channel.clone();
+        thread::spawn(move || {
+            thread::sleep(window);
+            let _ = event_channel.send(Event::SettleTimeout);
+        });
+    }
+
+    /// Reloads the channel and applies everything that arrived while settling
+    fn finish_settle(&mut self) {
+        if let Some(settle) = self.settle.take() {
+            self.view.message_view.load_messages(self);
+            for event in settle.flush() {
+                self.handle_event(event);
+            }
+            self.view.footer.clear_status();
+        }
+    }
+
+    /// Remembers where the current channel is scrolled to, for the next visit or session
+    fn record_anchor(&self) {
+        let channel = match self.context.read().channel {
+            Some(channel) => channel,
+            None => return,
+        };
+        let anchor = self.view.message_view.anchor();
+        let mut context = self.context.write();
+        match anchor {
+            Some(anchor) => context.anchors.insert(channel, anchor),
+            None => context.anchors.remove(&channel),
+        };
+    }
+
+    /// Shows feedback in the footer and redraws once it has expired
+    fn show_status(&self, status: String) {
+        self.view.footer.set_status(status);
+        let event_channel = self.context.read().event_channel.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(view::STATUS_SECS));
+            let _ = event_channel.send(Event::Redraw);
+        });
+    }
+
+    pub fn send_status<S: Into<String>>(&self, status: S) {
+        self.context
+            .read()
+            .event_channel
+            .send(Event::Status(status.into()))
+            .unwrap()
+    }
+
+    pub fn send_err(&self, err: Error) {
         self.context
             .read()
             .event_channel
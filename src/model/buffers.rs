@@ -0,0 +1,123 @@
+use serenity::model::id::{ChannelId, MessageId};
+
+use std::collections::{HashMap, HashSet};
+
+use model::MessageItem;
+
+/// Most messages kept for a channel that is not open
+const MAX_BUFFERED: usize = 100;
+
+/// Messages received for channels other than the active one, kept until the channel is opened
+#[derive(Default)]
+pub struct ChannelBuffers {
+    buffers: HashMap<ChannelId, Vec<MessageItem>>,
+    unread: HashMap<ChannelId, usize>,
+    /// Channels with an unread message mentioning the current user
+    mentions: HashSet<ChannelId>,
+}
+
+fn ids(item: &MessageItem) -> (ChannelId, MessageId) {
+    match item {
+        MessageItem::DiscordMessage(msg) => (msg.channel_id, msg.id),
+    }
+}
+
+impl ChannelBuffers {
+    pub fn new() -> ChannelBuffers {
+        ChannelBuffers::default()
+    }
+
+    /// Buffers a message for its channel, returns false if it was already buffered
+    pub fn push(&mut self, item: MessageItem, mention: bool) -> bool {
+        let (channel_id, message_id) = ids(&item);
+        let buffer = self.buffers.entry(channel_id).or_default();
+        if buffer.iter().any(|msg| ids(msg).1 == message_id) {
+            return false;
+        }
+        buffer.push(item);
+        let overflow = buffer.len().saturating_sub(MAX_BUFFERED);
+        buffer.drain(0..overflow);
+        *self.unread.entry(channel_id).or_default() += 1;
+        if mention {
+            self.mentions.insert(channel_id);
+        }
+        true
+    }
+
+    pub fn unread(&self, channel_id: ChannelId) -> usize {
+        self.unread.get(&channel_id).cloned().unwrap_or(0)
+    }
+
+    /// Newest buffered message of a channel
+    pub fn latest(&self, channel_id: ChannelId) -> Option<&MessageItem> {
+        self.buffers
+            .get(&channel_id)
+            .and_then(|buffer| buffer.last())
+    }
+
+    /// Channels with unread messages and whether any mention the user, mentions first
+    pub fn unread_channels(&self) -> Vec<(ChannelId, usize, bool)> {
+        let mut channels: Vec<_> = self
+            .unread
+            .iter()
+            .map(|(&channel_id, &count)| (channel_id, count, self.mentions.contains(&channel_id)))
+            .collect();
+        channels.sort_by_key(|&(channel_id, _, mention)| (!mention, channel_id));
+        channels
+    }
+
+    /// Takes the buffered messages of a channel that is being opened, marking it read
+    pub fn take(&mut self, channel_id: ChannelId) -> Vec<MessageItem> {
+        self.unread.remove(&channel_id);
+        self.mentions.remove(&channel_id);
+        self.buffers.remove(&channel_id).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChannelBuffers;
+    use helpers::testing::{message_from_value, message_value};
+    use model::MessageItem;
+    use serenity::model::id::{ChannelId, MessageId};
+
+    fn item(id: u64, channel_id: u64) -> MessageItem {
+        let mut value = message_value(id, 10, "hi");
+        value["channel_id"] = json!(channel_id.to_string());
+        MessageItem::DiscordMessage(Box::new(message_from_value(value)))
+    }
+
+    #[test]
+    fn routes_to_channel_buffer() {
+        let mut buffers = ChannelBuffers::new();
+        assert!(buffers.push(item(1, 5), false));
+        assert!(buffers.push(item(2, 5), false));
+        assert!(buffers.push(item(3, 6), false));
+        assert!(!buffers.push(item(2, 5), false));
+
+        assert_eq!(buffers.unread(ChannelId(5)), 2);
+        assert_eq!(buffers.unread(ChannelId(6)), 1);
+        assert_eq!(buffers.unread(ChannelId(7)), 0);
+
+        assert_eq!(buffers.take(ChannelId(5)).len(), 2);
+        assert_eq!(buffers.unread(ChannelId(5)), 0);
+        assert!(buffers.take(ChannelId(5)).is_empty());
+    }
+
+    #[test]
+    fn unread_channels_list_mentions_first() {
+        let mut buffers = ChannelBuffers::new();
+        buffers.push(item(1, 5), false);
+        buffers.push(item(2, 6), false);
+        buffers.push(item(3, 6), true);
+        buffers.push(item(4, 6), false);
+
+        assert_eq!(
+            buffers.unread_channels(),
+            vec![(ChannelId(6), 3, true), (ChannelId(5), 1, false)]
+        );
+
+        buffers.take(ChannelId(6));
+        assert_eq!(buffers.unread_channels(), vec![(ChannelId(5), 1, false)]);
+    }
+}
@@ -1,23 +1,105 @@
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use regex::Regex;
+
+use std::collections::HashMap;
 use std::sync::mpsc::Sender;
+use std::time::Duration;
 
-use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::gateway::{Game, Ready};
+use serenity::model::id::{ChannelId, GuildId, MessageId};
 use serenity::model::user::CurrentUser;
 
+use discord::utils;
 use helpers::chars::CharSet;
-use model::{Event, Preferences, State};
+use helpers::timezone;
+use model::keywords;
+use model::{
+    Accounts, Anchor, EmojiStyle, Event, NameAlign, NameWidth, Preferences, QuietHours,
+    RelativeUnit, Session, State, Theme,
+};
 
 use failure::Error;
 
+/// Widest relative timestamp expected, e.g. `59m` or `364d`
+const RELATIVE_TIMESTAMP_WIDTH: usize = 4;
+
+/// Widest a timestamp format gets, found by formatting times with wide fields
+fn timestamp_width(timestamp_fmt: &str) -> usize {
+    let samples = [
+        Utc.ymd(2018, 12, 31).and_hms(23, 59, 59),
+        Utc.ymd(2018, 9, 5).and_hms(10, 59, 59),
+        Utc.ymd(2018, 5, 3).and_hms(9, 9, 9),
+    ];
+    samples
+        .iter()
+        .map(|time| time.format(timestamp_fmt).to_string().len())
+        .max()
+        .unwrap_or(0)
+}
+
 pub struct Context {
     /// Users OAuth token
     pub token: String,
+    /// Accounts that can be switched to, the active one's token being `token`
+    pub accounts: Accounts,
     /// Printf-line format string for displaying timestamps
     pub timestamp_fmt: String,
     /// Whether or not to use Nerd Fonts
     pub nerd_fonts: bool,
+    /// Force truecolor on or off, ignoring `COLORTERM`
+    pub truecolor: Option<bool>,
+    /// How long to hold back events after reconnecting
+    pub reconnect_settle: Duration,
+    /// How long the newest message must stay visible before the channel counts as read
+    pub mark_read_delay: Duration,
+    /// Alignment of nicknames within their column
+    pub name_align: NameAlign,
+    /// Send composed messages as replies to the selected message
+    pub reply_to_current: bool,
+    /// Reactions shown per message before the rest are summarized
+    pub max_reactions: usize,
+    /// Timezone to show timestamps in instead of the local one
+    pub timezone: Option<FixedOffset>,
+    /// Name of `timezone` shown in the footer
+    pub timezone_label: Option<String>,
+    /// Shortest time between two redraws
+    pub frame_interval: Duration,
+    /// Show the age of messages instead of when they were sent
+    pub relative_timestamps: bool,
+    /// Finest unit shown by relative timestamps
+    pub relative_precision: RelativeUnit,
+    /// Time relative timestamps are measured from instead of the real time, set by `now`
+    pub clock: Option<DateTime<Utc>>,
+    /// Fixed width of the timestamp column, so nicknames line up
+    pub timestamp_column: Option<usize>,
+    /// Words that raise a notification in any channel
+    pub notify_keywords: Option<Regex>,
+    /// Channels that never raise keyword notifications
+    pub muted_channels: Vec<ChannelId>,
+    /// Daily window without notifications
+    pub quiet_hours: Option<QuietHours>,
+    /// Draw a dated rule between messages sent on different days
+    pub day_separators: bool,
+    /// Draw the current user's messages in the theme's `own_message` style
+    pub highlight_self: bool,
+    /// How unicode emoji in messages are drawn
+    pub emoji: EmojiStyle,
+    /// Narrowest terminal the guild sidebar is drawn on, it is hidden below that
+    pub sidebar_min_width: usize,
+    /// Which messages the nickname column is as wide as the longest name of
+    pub name_width: NameWidth,
+    /// Select messages by clicking them
+    pub mouse: bool,
+
+    /// Whether state is saved on exit, turned off by `wipe`
+    pub persist_state: bool,
 
     /// Whether or not to show the guild sidebar
     pub guild_sidebar_visible: bool,
+    /// Whether or not to draw nicknames in their role color
+    pub role_colors: bool,
+    /// Newest message in view of each channel left scrolled back
+    pub anchors: HashMap<ChannelId, MessageId>,
 
     /// Application wide event channel
     pub event_channel: Sender<Event>,
@@ -29,15 +111,28 @@ pub struct Context {
 
     /// Current user
     pub current_user: Option<CurrentUser>,
+    /// Guilds the current user is in, recorded on ready
+    pub guilds: Vec<GuildId>,
+
+    /// Activity set with the `activity` command, reapplied after reconnecting
+    pub activity: Option<Game>,
 
     /// Charset to use throughout the app
     pub char_set: CharSet,
+    /// Colors and styles to use throughout the app
+    pub theme: Theme,
 }
 
 impl Context {
-    pub fn new(prefs: &Preferences, state: &State, event_channel: Sender<Event>) -> Context {
+    pub fn new(
+        prefs: &Preferences,
+        state: &State,
+        theme: Theme,
+        event_channel: Sender<Event>,
+    ) -> Context {
         // Prefs
         let token = prefs.token.clone();
+        let accounts = Accounts::new(&token, &prefs.accounts.clone().unwrap_or_default());
 
         let timestamp_fmt = prefs
             .timestamp_fmt
@@ -46,6 +141,67 @@ impl Context {
 
         let nerd_fonts = prefs.nerd_fonts.unwrap_or(false);
 
+        let truecolor = prefs.truecolor;
+
+        let reconnect_settle = Duration::from_millis(prefs.reconnect_settle_ms.unwrap_or(1500));
+        let mark_read_delay = Duration::from_millis(prefs.mark_read_delay_ms.unwrap_or(3000));
+
+        let name_align = prefs.name_align.unwrap_or(NameAlign::Left);
+
+        let reply_to_current = prefs.reply_to_current.unwrap_or(false);
+
+        let max_reactions = prefs.max_reactions.unwrap_or(10);
+
+        let timezone = prefs
+            .timezone
+            .as_ref()
+            .and_then(|zone| timezone::parse_offset(zone));
+        if timezone.is_none() && prefs.timezone.is_some() {
+            warn!("Ignoring invalid timezone: {:?}", prefs.timezone);
+        }
+        let frame_interval = match prefs.max_fps.unwrap_or(60) {
+            0 => Duration::from_secs(0),
+            fps => Duration::from_secs(1) / fps,
+        };
+
+        let relative_timestamps = prefs.relative_timestamps.unwrap_or(false);
+        let relative_precision = prefs.relative_precision.unwrap_or(RelativeUnit::Minutes);
+
+        let timestamp_column = if prefs.align_timestamps.unwrap_or(false) {
+            Some(if relative_timestamps {
+                RELATIVE_TIMESTAMP_WIDTH
+            } else {
+                timestamp_width(&timestamp_fmt)
+            })
+        } else {
+            None
+        };
+
+        let notify_keywords = prefs
+            .notify_keywords
+            .as_ref()
+            .and_then(|words| keywords::pattern(words));
+        let muted_channels = prefs.muted_channels.clone().unwrap_or_default();
+
+        let quiet_hours = prefs
+            .quiet_hours
+            .as_ref()
+            .and_then(|window| QuietHours::parse(window));
+        if quiet_hours.is_none() && prefs.quiet_hours.is_some() {
+            warn!("Ignoring invalid quiet hours: {:?}", prefs.quiet_hours);
+        }
+
+        let day_separators = prefs.day_separators.unwrap_or(true);
+        let highlight_self = prefs.highlight_self.unwrap_or(false);
+        let emoji = prefs.emoji.unwrap_or(EmojiStyle::Unicode);
+        let sidebar_min_width = prefs.sidebar_min_width.unwrap_or(80);
+        let name_width = prefs.name_width.unwrap_or(NameWidth::All);
+        let mouse = prefs.mouse.unwrap_or(true);
+
+        let timezone_label = timezone.map(|offset| {
+            timezone::label(prefs.timezone_name.as_ref().map(String::as_str), offset)
+        });
+
         let char_set = if nerd_fonts {
             CharSet::nerd()
         } else {
@@ -57,31 +213,147 @@ impl Context {
         let guild = state.guild;
 
         let guild_sidebar_visible = state.guild_sidebar_visible;
+        let role_colors = state.role_colors;
+        let anchors = state
+            .anchors
+            .iter()
+            .map(|anchor| (anchor.channel, anchor.message))
+            .collect();
 
         let current_user = None;
+        let guilds = Vec::new();
+        let activity = None;
 
         Context {
             token,
+            accounts,
             timestamp_fmt,
             nerd_fonts,
+            truecolor,
+            reconnect_settle,
+            mark_read_delay,
+            name_align,
+            reply_to_current,
+            max_reactions,
+            timezone,
+            timezone_label,
+            frame_interval,
+            relative_timestamps,
+            relative_precision,
+            clock: None,
+            timestamp_column,
+            notify_keywords,
+            muted_channels,
+            quiet_hours,
+            day_separators,
+            highlight_self,
+            emoji,
+            sidebar_min_width,
+            name_width,
+            mouse,
+            persist_state: true,
             guild_sidebar_visible,
+            role_colors,
+            anchors,
             event_channel,
             channel,
             guild,
             current_user,
+            guilds,
+            activity,
             char_set,
+            theme,
         }
     }
 
     pub fn save_state(&self) -> Result<(), Error> {
+        if !self.persist_state {
+            return Ok(());
+        }
         self.get_state().save()
     }
 
     pub fn get_state(&self) -> State {
+        let mut anchors: Vec<_> = self
+            .anchors
+            .iter()
+            .map(|(&channel, &message)| Anchor { channel, message })
+            .collect();
+        anchors.sort_by_key(|anchor| anchor.channel);
         State {
             channel: self.channel,
             guild: self.guild,
             guild_sidebar_visible: self.guild_sidebar_visible,
+            role_colors: self.role_colors,
+            anchors,
+        }
+    }
+
+    /// Records the current user and their guilds from the gateway's ready payload
+    pub fn apply_ready(&mut self, ready: &Ready) {
+        self.current_user = Some(ready.user.clone());
+        self.guilds = ready.guilds.iter().map(|guild| guild.id()).collect();
+    }
+
+    /// Current guild, or the guild of the current channel when none was selected
+    pub fn current_guild(&self) -> Option<GuildId> {
+        self.guild
+            .or_else(|| self.channel.and_then(utils::channel_guild_id))
+    }
+
+    /// The current layout and theme, with the current channel scrolled back to `anchor`
+    pub fn get_session(&self, anchor: Option<MessageId>) -> Session {
+        Session {
+            channel: self.channel,
+            guild: self.guild,
+            guild_sidebar_visible: self.guild_sidebar_visible,
+            anchor,
+            theme: Some(self.theme.clone()),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::timestamp_width;
+    use helpers::testing;
+    use serde_json;
+    use serenity::model::gateway::Ready;
+    use serenity::model::id::{GuildId, UserId};
+
+    #[test]
+    fn widest_timestamp() {
+        assert_eq!(timestamp_width("%_I:%M"), 5);
+        assert_eq!(timestamp_width("%-I:%M %p"), 8);
+        assert_eq!(timestamp_width("%H:%M:%S"), 8);
+    }
+
+    #[test]
+    fn ready_records_user_and_guilds() {
+        let ready: Ready = serde_json::from_value(json!({
+            "guilds": [
+                {"id": "5", "unavailable": true},
+                {"id": "6", "unavailable": true},
+            ],
+            "session_id": "session",
+            "shard": null,
+            "user": {
+                "id": "10",
+                "username": "me",
+                "discriminator": "0001",
+                "avatar": null,
+                "bot": false,
+                "email": null,
+                "mfa_enabled": false,
+                "verified": true,
+            },
+            "v": 6,
+        }))
+        .unwrap();
+        let mut context = testing::context();
+        context.apply_ready(&ready);
+
+        assert_eq!(context.current_user.map(|user| user.id), Some(UserId(10)));
+        assert_eq!(context.guilds, vec![GuildId(5), GuildId(6)]);
+    }
+}
@@ -1,5 +1,6 @@
 use serenity::model::channel;
 use serenity::model::event::{MessageUpdateEvent, TypingStartEvent};
+use serenity::model::gateway::Ready;
 use serenity::model::id::{ChannelId, MessageId};
 use termbuf::termion::event::Key;
 
@@ -13,13 +14,35 @@ pub enum Event {
     MessageDeleteBulk(ChannelId, Vec<MessageId>),
     MessageUpdateEvent(Box<MessageUpdateEvent>),
     ChannelUpdateEvent,
-    DiscordReady,
+    DiscordReady(Box<Ready>),
+    /// The gateway session was resumed after a disconnect
+    Resumed,
+    /// The settle window after a reconnect has passed
+    SettleTimeout,
     SetChannel(ChannelId),
+    /// Log in as the configured account at an index
+    SwitchAccount(usize),
     Keypress(Key),
+    /// Left mouse button pressed at a column and row of the screen
+    Click(usize, usize),
     UserMessage(String),
+    /// Send a message replying to a message in a channel
+    Reply(ChannelId, MessageId, String),
+    /// Retry the last message that failed to send
+    Resend,
+    /// Send a message held for being too long in several parts
+    SplitSend,
+    /// Edit the message being composed, or one of the user's messages, in `$EDITOR`
+    ExternalEdit(Option<Box<channel::Message>>),
     UserCommand(String),
     UserTyping,
     TypingStart(TypingStartEvent),
     InternalError(Error),
+    /// Feedback shown in the footer
+    Status(String),
+    /// Desktop notification with a summary and body
+    Notification(String, String),
     WindowSizeChange,
+    /// Forces a redraw, used by timed animations
+    Redraw,
 }
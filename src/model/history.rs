@@ -0,0 +1,204 @@
+use failure::Error;
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use error::*;
+
+/// Most commands kept, the oldest are dropped past it
+pub const MAX_ENTRIES: usize = 500;
+
+/// Commands entered at the command prompt, oldest first
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    entries: Vec<String>,
+    /// Entry recalled with `older` and `newer`, counting back from the newest
+    recall: Option<usize>,
+}
+
+impl CommandHistory {
+    pub fn new() -> CommandHistory {
+        CommandHistory {
+            entries: Vec::new(),
+            recall: None,
+        }
+    }
+
+    pub fn path() -> Result<PathBuf, Error> {
+        let home_dir = env::home_dir().ok_or(HomeDirError)?;
+        Ok(home_dir.join(".config/dex/command_history"))
+    }
+
+    /// History saved by a previous session, empty if there is none
+    pub fn load() -> Result<CommandHistory, Error> {
+        match fs::read_to_string(CommandHistory::path()?) {
+            Ok(text) => Ok(CommandHistory::from_lines(&text)),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(CommandHistory::new()),
+            Err(err) => Err(err)?,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let mut file = File::create(CommandHistory::path()?)?;
+        for entry in &self.entries {
+            writeln!(file, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    /// History with one command per line, oldest first
+    fn from_lines(text: &str) -> CommandHistory {
+        let mut history = CommandHistory::new();
+        for line in text.lines().filter(|line| !line.is_empty()) {
+            history.push(line.to_owned());
+        }
+        history
+    }
+
+    /// Adds a command unless it repeats the newest one, and starts recalling from the newest again
+    pub fn push(&mut self, command: String) {
+        self.recall = None;
+        if self.entries.last() == Some(&command) {
+            return;
+        }
+        self.entries.push(command);
+        if self.entries.len() > MAX_ENTRIES {
+            let excess = self.entries.len() - MAX_ENTRIES;
+            self.entries.drain(..excess);
+        }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recall = None;
+    }
+
+    /// Steps back to the command before the one recalled, staying on the oldest
+    pub fn older(&mut self) -> Option<&str> {
+        let back = self.recall.map_or(0, |back| back + 1);
+        if back >= self.entries.len() {
+            return self.recall.map(|back| self.entry(back));
+        }
+        self.recall = Some(back);
+        Some(self.entry(back))
+    }
+
+    /// Steps forward to the command after the one recalled, an empty command once past the
+    /// newest. `None` when nothing is being recalled.
+    pub fn newer(&mut self) -> Option<&str> {
+        match self.recall {
+            None => None,
+            Some(0) => {
+                self.recall = None;
+                Some("")
+            }
+            Some(back) => {
+                self.recall = Some(back - 1);
+                Some(self.entry(back - 1))
+            }
+        }
+    }
+
+    /// Stops recalling, so `older` starts from the newest command again
+    pub fn reset_recall(&mut self) {
+        self.recall = None;
+    }
+
+    fn entry(&self, back: usize) -> &str {
+        &self.entries[self.entries.len() - 1 - back]
+    }
+}
+
+/// Numbered lines listing the history, as shown by `history`
+pub fn listing(entries: &[String]) -> Vec<String> {
+    let width = entries.len().to_string().len();
+    entries
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| format!("{:>width$}  {}", i + 1, entry, width = width))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{listing, CommandHistory, MAX_ENTRIES};
+
+    #[test]
+    fn history_listing() {
+        let mut history = CommandHistory::new();
+        for i in 0..10 {
+            history.push(format!("cmd{}", i));
+        }
+
+        let lines = listing(history.entries());
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], " 1  cmd0");
+        assert_eq!(lines[9], "10  cmd9");
+    }
+
+    #[test]
+    fn clear_history() {
+        let mut history = CommandHistory::new();
+        history.push("quit".to_owned());
+        history.clear();
+
+        assert!(listing(history.entries()).is_empty());
+    }
+
+    #[test]
+    fn consecutive_duplicates_are_dropped() {
+        let mut history = CommandHistory::new();
+        for command in &["ping", "ping", "tz", "ping"] {
+            history.push(command.to_string());
+        }
+
+        assert_eq!(history.entries(), &["ping", "tz", "ping"]);
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut history = CommandHistory::new();
+        for i in 0..MAX_ENTRIES + 5 {
+            history.push(format!("cmd{}", i));
+        }
+
+        assert_eq!(history.entries().len(), MAX_ENTRIES);
+        assert_eq!(history.entries()[0], "cmd5");
+    }
+
+    #[test]
+    fn recall_older_and_newer() {
+        let mut history = CommandHistory::new();
+        assert_eq!(history.older(), None);
+        for command in &["ping", "tz", "back"] {
+            history.push(command.to_string());
+        }
+
+        assert_eq!(history.newer(), None);
+        assert_eq!(history.older(), Some("back"));
+        assert_eq!(history.older(), Some("tz"));
+        assert_eq!(history.older(), Some("ping"));
+        assert_eq!(history.older(), Some("ping"));
+        assert_eq!(history.newer(), Some("tz"));
+        assert_eq!(history.newer(), Some("back"));
+        assert_eq!(history.newer(), Some(""));
+        assert_eq!(history.newer(), None);
+
+        history.older();
+        history.push("quit".to_owned());
+        assert_eq!(history.older(), Some("quit"));
+    }
+
+    #[test]
+    fn saved_history() {
+        let history = CommandHistory::from_lines("ping\n\ntz\ntz\n");
+
+        assert_eq!(history.entries(), &["ping", "tz"]);
+    }
+}
@@ -0,0 +1,61 @@
+/// Most positions remembered before the oldest is dropped
+const MAX_JUMPS: usize = 50;
+
+/// Positions jumped away from, returned to with `back`
+pub struct JumpStack<T> {
+    positions: Vec<T>,
+}
+
+impl<T> JumpStack<T> {
+    pub fn new() -> JumpStack<T> {
+        JumpStack {
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, position: T) {
+        if self.positions.len() == MAX_JUMPS {
+            self.positions.remove(0);
+        }
+        self.positions.push(position);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        self.positions.pop()
+    }
+
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JumpStack, MAX_JUMPS};
+
+    #[test]
+    fn push_pop() {
+        let mut jumps = JumpStack::new();
+        jumps.push(1);
+        jumps.push(2);
+
+        assert_eq!(jumps.pop(), Some(2));
+        assert_eq!(jumps.pop(), Some(1));
+        assert_eq!(jumps.pop(), None);
+    }
+
+    #[test]
+    fn drops_oldest() {
+        let mut jumps = JumpStack::new();
+        for i in 0..MAX_JUMPS + 1 {
+            jumps.push(i);
+        }
+
+        let mut popped = Vec::new();
+        while let Some(position) = jumps.pop() {
+            popped.push(position);
+        }
+        assert_eq!(popped.len(), MAX_JUMPS);
+        assert_eq!(popped.last(), Some(&1));
+    }
+}
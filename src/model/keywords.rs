@@ -0,0 +1,79 @@
+use regex::{escape, Regex};
+use serenity::model::id::ChannelId;
+
+/// Builds a case-insensitive pattern matching any of the keywords as whole words
+pub fn pattern(keywords: &[String]) -> Option<Regex> {
+    let alternatives: Vec<_> = keywords
+        .iter()
+        .map(|keyword| keyword.trim())
+        .filter(|keyword| !keyword.is_empty())
+        .map(escape)
+        .collect();
+    if alternatives.is_empty() {
+        return None;
+    }
+    // `\b` would not match around keywords that start or end with punctuation, like `c++`
+    Regex::new(&format!(
+        r"(?i)(?:^|\W)(?:{})(?:\W|$)",
+        alternatives.join("|")
+    ))
+    .ok()
+}
+
+/// Whether a message should raise a keyword notification
+pub fn should_notify(
+    pattern: Option<&Regex>,
+    content: &str,
+    channel_id: ChannelId,
+    muted_channels: &[ChannelId],
+) -> bool {
+    match pattern {
+        Some(pattern) => !muted_channels.contains(&channel_id) && pattern.is_match(content),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pattern, should_notify};
+    use serenity::model::id::ChannelId;
+
+    #[test]
+    fn keyword_matches() {
+        let keywords = pattern(&["dex".to_owned(), "c++".to_owned()]);
+        let keywords = keywords.as_ref();
+        let matches = |content| should_notify(keywords, content, ChannelId(1), &[]);
+
+        assert!(matches("anyone using dex?"));
+        assert!(matches("DEX is great"));
+        assert!(matches("I write c++ at work"));
+        // Whole words only
+        assert!(!matches("index"));
+        assert!(!matches("dexterity"));
+    }
+
+    #[test]
+    fn muted_channels_are_skipped() {
+        let keywords = pattern(&["dex".to_owned()]);
+
+        assert!(!should_notify(
+            keywords.as_ref(),
+            "dex",
+            ChannelId(1),
+            &[ChannelId(1)]
+        ));
+        assert!(should_notify(
+            keywords.as_ref(),
+            "dex",
+            ChannelId(2),
+            &[ChannelId(1)]
+        ));
+    }
+
+    #[test]
+    fn no_keywords() {
+        assert!(pattern(&[]).is_none());
+        assert!(pattern(&[" ".to_owned()]).is_none());
+        assert!(!should_notify(None, "dex", ChannelId(1), &[]));
+    }
+}
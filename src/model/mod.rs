@@ -1,15 +1,36 @@
+mod accounts;
 pub mod application;
+mod buffers;
 mod context;
 mod event;
+pub mod history;
+mod jumps;
+pub mod keywords;
 pub mod layout;
 pub mod message;
+mod outbox;
 mod preferences;
+mod quiet;
+pub mod read;
+mod session;
+pub mod settle;
 mod state;
+mod theme;
 
+pub use self::accounts::{Account, Accounts};
 pub use self::application::Application;
+pub use self::buffers::ChannelBuffers;
 pub use self::context::Context;
 pub use self::event::Event;
+pub use self::history::CommandHistory;
+pub use self::jumps::JumpStack;
 pub use self::layout::Rect;
 pub use self::message::MessageItem;
-pub use self::preferences::Preferences;
-pub use self::state::State;
+pub use self::outbox::Outbox;
+pub use self::preferences::{EmojiStyle, NameAlign, NameWidth, Preferences, RelativeUnit};
+pub use self::quiet::QuietHours;
+pub use self::read::ReadMarker;
+pub use self::session::Session;
+pub use self::settle::Settle;
+pub use self::state::{Anchor, State};
+pub use self::theme::{Theme, ThemeStyle};
@@ -0,0 +1,87 @@
+use serenity::model::id::ChannelId;
+
+/// Holds messages that could not be sent as they were, so they can be retried
+pub struct Outbox {
+    failed: Option<(ChannelId, String)>,
+    /// Message over the length limit, waiting for `split-send`
+    oversized: Option<(ChannelId, String)>,
+}
+
+impl Outbox {
+    pub fn new() -> Outbox {
+        Outbox {
+            failed: None,
+            oversized: None,
+        }
+    }
+
+    /// Keeps a message too long to send until it is confirmed with `split-send`
+    pub fn hold(&mut self, channel: ChannelId, content: String) {
+        self.oversized = Some((channel, content));
+    }
+
+    /// Takes the held oversized message to send it in parts
+    pub fn take_held(&mut self) -> Option<(ChannelId, String)> {
+        self.oversized.take()
+    }
+
+    /// Keeps `content` after a failed send, replacing any earlier failure
+    pub fn failed(&mut self, channel: ChannelId, content: String) {
+        self.failed = Some((channel, content));
+    }
+
+    /// Forgets the failed message once something was sent to its channel
+    pub fn sent(&mut self, channel: ChannelId) {
+        if self.failed.as_ref().map(|&(failed, _)| failed) == Some(channel) {
+            self.failed = None;
+        }
+    }
+
+    /// Takes the failed message to send it again
+    pub fn retry(&mut self) -> Option<(ChannelId, String)> {
+        self.failed.take()
+    }
+
+    /// Forgets both the held and the failed message
+    pub fn clear(&mut self) {
+        self.failed = None;
+        self.oversized = None;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Outbox;
+    use serenity::model::id::ChannelId;
+
+    #[test]
+    fn failed_send_is_retried() {
+        let mut outbox = Outbox::new();
+        assert_eq!(outbox.retry(), None);
+
+        outbox.failed(ChannelId(1), "hello".to_owned());
+        assert_eq!(outbox.retry(), Some((ChannelId(1), "hello".to_owned())));
+        assert_eq!(outbox.retry(), None);
+    }
+
+    #[test]
+    fn oversized_held_until_taken() {
+        let mut outbox = Outbox::new();
+        outbox.hold(ChannelId(1), "long".to_owned());
+        assert_eq!(outbox.retry(), None);
+        assert_eq!(outbox.take_held(), Some((ChannelId(1), "long".to_owned())));
+        assert_eq!(outbox.take_held(), None);
+    }
+
+    #[test]
+    fn cleared_on_success() {
+        let mut outbox = Outbox::new();
+        outbox.failed(ChannelId(1), "hello".to_owned());
+        outbox.sent(ChannelId(2));
+        assert!(outbox.retry().is_some());
+
+        outbox.failed(ChannelId(1), "hello".to_owned());
+        outbox.sent(ChannelId(1));
+        assert_eq!(outbox.retry(), None);
+    }
+}
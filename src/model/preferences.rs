@@ -5,13 +5,75 @@ use std::env;
 use std::fs::OpenOptions;
 use std::io::Read;
 
+use serenity::model::id::ChannelId;
+
+use model::Account;
+
 use error::*;
 
+/// Alignment of nicknames within the nickname column
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameAlign {
+    Left,
+    Right,
+}
+
+/// Which messages the width of the nickname column is measured over
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameWidth {
+    /// Every message drawn since starting, the column never shrinks
+    All,
+    /// Only the messages on screen, so a long name that scrolled away stops widening the column
+    Visible,
+}
+
+/// How unicode emoji in messages are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmojiStyle {
+    /// As their `:shortcode:`, for terminals without emoji fonts
+    Text,
+    Unicode,
+}
+
+/// Finest unit shown by relative timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelativeUnit {
+    Seconds,
+    Minutes,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Preferences {
     pub token: String,
     pub timestamp_fmt: Option<String>,
     pub nerd_fonts: Option<bool>,
+    pub truecolor: Option<bool>,
+    pub reconnect_settle_ms: Option<u64>,
+    pub name_align: Option<NameAlign>,
+    pub reply_to_current: Option<bool>,
+    pub max_reactions: Option<usize>,
+    pub timezone: Option<String>,
+    pub timezone_name: Option<String>,
+    pub max_fps: Option<u32>,
+    pub relative_timestamps: Option<bool>,
+    pub relative_precision: Option<RelativeUnit>,
+    pub notify_keywords: Option<Vec<String>>,
+    pub muted_channels: Option<Vec<ChannelId>>,
+    pub align_timestamps: Option<bool>,
+    pub quiet_hours: Option<String>,
+    pub day_separators: Option<bool>,
+    pub highlight_self: Option<bool>,
+    pub mark_read_delay_ms: Option<u64>,
+    pub emoji: Option<EmojiStyle>,
+    pub sidebar_min_width: Option<usize>,
+    pub name_width: Option<NameWidth>,
+    /// Accounts to switch to besides the one of `token`
+    pub accounts: Option<Vec<Account>>,
+    pub mouse: Option<bool>,
 }
 
 impl Preferences {
@@ -0,0 +1,67 @@
+use chrono::NaiveTime;
+
+/// Daily window during which notifications are suppressed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuietHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parses a `HH:MM-HH:MM` window, which may wrap past midnight
+    pub fn parse(window: &str) -> Option<QuietHours> {
+        let mut parts = window.splitn(2, '-');
+        let start = NaiveTime::parse_from_str(parts.next()?.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(parts.next()?.trim(), "%H:%M").ok()?;
+        Some(QuietHours { start, end })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // Wraps midnight, e.g. 22:00-08:00
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::QuietHours;
+    use chrono::NaiveTime;
+
+    fn at(hour: u32, min: u32) -> NaiveTime {
+        NaiveTime::from_hms(hour, min, 0)
+    }
+
+    #[test]
+    fn window_wrapping_midnight() {
+        let quiet = QuietHours::parse("22:00-08:00").unwrap();
+
+        assert!(quiet.contains(at(22, 0)));
+        assert!(quiet.contains(at(23, 59)));
+        assert!(quiet.contains(at(0, 0)));
+        assert!(quiet.contains(at(7, 59)));
+        assert!(!quiet.contains(at(8, 0)));
+        assert!(!quiet.contains(at(12, 0)));
+        assert!(!quiet.contains(at(21, 59)));
+    }
+
+    #[test]
+    fn window_within_day() {
+        let quiet = QuietHours::parse("09:00-17:30").unwrap();
+
+        assert!(quiet.contains(at(9, 0)));
+        assert!(quiet.contains(at(17, 29)));
+        assert!(!quiet.contains(at(17, 30)));
+        assert!(!quiet.contains(at(3, 0)));
+    }
+
+    #[test]
+    fn invalid_windows() {
+        assert!(QuietHours::parse("22:00").is_none());
+        assert!(QuietHours::parse("25:00-08:00").is_none());
+        assert!(QuietHours::parse("late-early").is_none());
+    }
+}
@@ -0,0 +1,106 @@
+use std::time::{Duration, Instant};
+
+use helpers::cooldown;
+
+/// Whether the newest message has been visible long enough, since `visible_since`, to count as read
+pub fn should_mark_read(visible_since: Instant, now: Instant, delay: Duration) -> bool {
+    cooldown::remaining(visible_since, now, delay).is_none()
+}
+
+/// Counts messages in the open channel that have not been read yet, marking them read once the
+/// newest one has stayed visible at the bottom for the configured delay
+pub struct ReadMarker {
+    delay: Duration,
+    visible_since: Option<Instant>,
+    unseen: usize,
+}
+
+impl ReadMarker {
+    pub fn new(delay: Duration) -> ReadMarker {
+        ReadMarker {
+            delay,
+            visible_since: None,
+            unseen: 0,
+        }
+    }
+
+    /// A new message arrived in the open channel, restarting the delay
+    pub fn message_arrived(&mut self) {
+        self.unseen += 1;
+        self.visible_since = None;
+    }
+
+    /// Another channel was opened
+    pub fn reset(&mut self) {
+        self.unseen = 0;
+        self.visible_since = None;
+    }
+
+    pub fn unseen(&self) -> usize {
+        self.unseen
+    }
+
+    /// Records whether the newest message is visible, returns how long to wait when the delay
+    /// starts so the caller can check again once it has passed
+    pub fn observe(&mut self, at_bottom: bool, now: Instant) -> Option<Duration> {
+        if !at_bottom {
+            self.visible_since = None;
+            return None;
+        }
+        if self.unseen == 0 {
+            return None;
+        }
+        match self.visible_since {
+            None => {
+                self.visible_since = Some(now);
+                Some(self.delay)
+            }
+            Some(since) => {
+                if should_mark_read(since, now, self.delay) {
+                    self.reset();
+                }
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{should_mark_read, ReadMarker};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn marks_read_after_delay() {
+        let since = Instant::now();
+        let delay = Duration::from_secs(3);
+
+        assert!(!should_mark_read(since, since, delay));
+        assert!(!should_mark_read(
+            since,
+            since + Duration::from_secs(2),
+            delay
+        ));
+        assert!(should_mark_read(since, since + delay, delay));
+    }
+
+    #[test]
+    fn glance_does_not_mark_read() {
+        let now = Instant::now();
+        let mut marker = ReadMarker::new(Duration::from_secs(3));
+        marker.message_arrived();
+
+        assert_eq!(marker.observe(true, now), Some(Duration::from_secs(3)));
+        // Scrolling away restarts the delay
+        assert_eq!(marker.observe(false, now + Duration::from_secs(1)), None);
+        assert_eq!(
+            marker.observe(true, now + Duration::from_secs(2)),
+            Some(Duration::from_secs(3))
+        );
+        marker.observe(true, now + Duration::from_secs(4));
+        assert_eq!(marker.unseen(), 1);
+
+        marker.observe(true, now + Duration::from_secs(5));
+        assert_eq!(marker.unseen(), 0);
+    }
+}
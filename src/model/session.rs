@@ -0,0 +1,111 @@
+use failure::Error;
+use toml;
+
+use serenity::model::id::{ChannelId, GuildId, MessageId};
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use error::*;
+use model::Theme;
+
+/// A named snapshot of the UI state that can be restored with `load-session`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Session {
+    pub guild: Option<GuildId>,
+    pub channel: Option<ChannelId>,
+    #[serde(default = "_true")]
+    pub guild_sidebar_visible: bool,
+    /// Newest message in view when the channel was scrolled back
+    #[serde(default)]
+    pub anchor: Option<MessageId>,
+    #[serde(default)]
+    pub theme: Option<Theme>,
+}
+
+fn _true() -> bool {
+    true
+}
+
+impl Session {
+    fn path(name: &str) -> Result<PathBuf, Error> {
+        if name.is_empty() || name.contains('/') || name.starts_with('.') {
+            return Err(InvalidSessionName(name.to_owned()))?;
+        }
+        let home_dir = env::home_dir().ok_or(HomeDirError)?;
+        Ok(home_dir
+            .join(".config/dex/sessions")
+            .join(format!("{}.toml", name)))
+    }
+
+    pub fn load(name: &str) -> Result<Session, Error> {
+        let mut file = OpenOptions::new().read(true).open(Session::path(name)?)?;
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+
+        let session: Session = toml::from_str(&buf)?;
+        Ok(session)
+    }
+
+    pub fn save(&self, name: &str) -> Result<(), Error> {
+        let path = Session::path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let data = toml::to_string(self)?;
+        file.write_all(data.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Session;
+    use model::{Theme, ThemeStyle};
+    use serenity::model::id::{ChannelId, GuildId, MessageId};
+    use toml;
+
+    #[test]
+    fn session_round_trip() {
+        let session = Session {
+            guild: Some(GuildId(81384788765712384)),
+            channel: Some(ChannelId(381889573426429952)),
+            guild_sidebar_visible: false,
+            anchor: Some(MessageId(381890411414683648)),
+            theme: Some(Theme {
+                highlight: ThemeStyle::Underline,
+                nickname: Some(33),
+                own_message: ThemeStyle::Bold,
+            }),
+        };
+
+        let data = toml::to_string(&session).unwrap();
+        let loaded: Session = toml::from_str(&data).unwrap();
+
+        assert_eq!(loaded, session);
+    }
+
+    #[test]
+    fn older_sessions_load() {
+        let loaded: Session = toml::from_str("channel = 381889573426429952").unwrap();
+
+        assert_eq!(loaded.anchor, None);
+        assert_eq!(loaded.theme, None);
+        assert!(loaded.guild_sidebar_visible);
+    }
+
+    #[test]
+    fn invalid_session_names() {
+        assert!(Session::path("").is_err());
+        assert!(Session::path("../state").is_err());
+        assert!(Session::path(".hidden").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use model::Event;
+
+/// Holds back gateway events for a short window after reconnecting, so the reloaded channel and
+/// the events that raced with it are applied in one go
+pub struct Settle {
+    until: Instant,
+    events: Vec<Event>,
+}
+
+impl Settle {
+    pub fn new(now: Instant, window: Duration) -> Settle {
+        Settle {
+            until: now + window,
+            events: Vec::new(),
+        }
+    }
+
+    /// Whether an event arriving at `now` falls within the window
+    pub fn should_buffer(&self, now: Instant) -> bool {
+        now < self.until
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Events to apply once the channel has been reloaded, in arrival order
+    pub fn flush(self) -> Vec<Event> {
+        self.events
+    }
+}
+
+/// Whether an event modifies the message buffer and should be held back while settling
+pub fn is_buffered(event: &Event) -> bool {
+    match *event {
+        Event::NewMessage(_)
+        | Event::MessageDelete(..)
+        | Event::MessageDeleteBulk(..)
+        | Event::MessageUpdateEvent(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_buffered, Settle};
+    use model::Event;
+    use serenity::model::id::{ChannelId, MessageId};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn buffers_within_window() {
+        let start = Instant::now();
+        let settle = Settle::new(start, Duration::from_millis(500));
+
+        assert!(settle.should_buffer(start));
+        assert!(settle.should_buffer(start + Duration::from_millis(499)));
+        assert!(!settle.should_buffer(start + Duration::from_millis(500)));
+        assert!(!settle.should_buffer(start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn flush_keeps_order() {
+        let mut settle = Settle::new(Instant::now(), Duration::from_millis(500));
+        settle.push(Event::MessageDelete(ChannelId(1), MessageId(1)));
+        settle.push(Event::MessageDelete(ChannelId(1), MessageId(2)));
+
+        let ids = settle
+            .flush()
+            .into_iter()
+            .map(|event| match event {
+                Event::MessageDelete(_, id) => id,
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![MessageId(1), MessageId(2)]);
+    }
+
+    #[test]
+    fn only_buffer_events_are_held() {
+        assert!(is_buffered(&Event::MessageDelete(
+            ChannelId(1),
+            MessageId(1)
+        )));
+        assert!(!is_buffered(&Event::WindowSizeChange));
+    }
+}
@@ -1,11 +1,12 @@
 use failure::Error;
 use toml;
 
-use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::id::{ChannelId, GuildId, MessageId};
 
 use std::env;
 use std::fs::OpenOptions;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 
 use error::*;
 
@@ -15,6 +16,18 @@ pub struct State {
     pub channel: Option<ChannelId>,
     #[serde(default = "_true")]
     pub guild_sidebar_visible: bool,
+    #[serde(default = "_true")]
+    pub role_colors: bool,
+    /// Where each channel left scrolled back was being read
+    #[serde(default)]
+    pub anchors: Vec<Anchor>,
+}
+
+/// Newest message in view when a channel was left scrolled back
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Anchor {
+    pub channel: ChannelId,
+    pub message: MessageId,
 }
 
 fn _true() -> bool {
@@ -22,13 +35,17 @@ fn _true() -> bool {
 }
 
 impl State {
-    pub fn load() -> Result<State, Error> {
+    pub fn path() -> Result<PathBuf, Error> {
         let home_dir = env::home_dir().ok_or(HomeDirError)?;
+        Ok(home_dir.join(".config/dex/persistent_state.toml"))
+    }
+
+    pub fn load() -> Result<State, Error> {
         let mut file = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
-            .open(home_dir.join(".config/dex/persistent_state.toml"))?;
+            .open(State::path()?)?;
         let mut buf = String::new();
         file.read_to_string(&mut buf)?;
 
@@ -37,11 +54,10 @@ impl State {
     }
 
     pub fn save(&self) -> Result<(), Error> {
-        let home_dir = env::home_dir().ok_or(HomeDirError)?;
         let mut file = OpenOptions::new()
             .write(true)
             .create(true)
-            .open(home_dir.join(".config/dex/persistent_state.toml"))?;
+            .open(State::path()?)?;
 
         let data = toml::to_string(self)?;
         file.write_all(data.as_bytes())?;
@@ -51,7 +67,8 @@ impl State {
 
 #[cfg(test)]
 mod test {
-    use super::State;
+    use super::{Anchor, State};
+    use serenity::model::id::{ChannelId, MessageId};
     use toml;
     #[test]
     fn clean_state() {
@@ -61,5 +78,23 @@ mod test {
         assert!(state.guild.is_none());
         assert!(state.channel.is_none());
         assert_eq!(state.guild_sidebar_visible, true);
+        assert_eq!(state.role_colors, true);
+        assert!(state.anchors.is_empty());
+    }
+
+    #[test]
+    fn anchors_survive_restart() {
+        let mut state: State = toml::from_str("").unwrap();
+        state.channel = Some(ChannelId(7));
+        state.anchors = vec![Anchor {
+            channel: ChannelId(7),
+            message: MessageId(420),
+        }];
+
+        let saved = toml::to_string(&state).unwrap();
+        let restored: State = toml::from_str(&saved).unwrap();
+
+        assert_eq!(restored.channel, Some(ChannelId(7)));
+        assert_eq!(restored.anchors, state.anchors);
     }
 }
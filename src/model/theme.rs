@@ -0,0 +1,139 @@
+use failure::Error;
+use termbuf::Style;
+use toml;
+
+use std::env;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+use error::*;
+
+/// Text styles that can be named in the theme file
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeStyle {
+    Bold,
+    Faint,
+    Italic,
+    Underline,
+    Invert,
+}
+
+impl ThemeStyle {
+    pub fn style(&self) -> Style {
+        match *self {
+            ThemeStyle::Bold => Style::Bold,
+            ThemeStyle::Faint => Style::Faint,
+            ThemeStyle::Italic => Style::Italic,
+            ThemeStyle::Underline => Style::Underline,
+            ThemeStyle::Invert => Style::Invert,
+        }
+    }
+}
+
+/// Colors and styles used throughout the interface
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Theme {
+    /// Style of text matched by `filter`
+    #[serde(default = "default_highlight")]
+    pub highlight: ThemeStyle,
+    /// 256 color palette index for nicknames drawn without a role color
+    #[serde(default)]
+    pub nickname: Option<u8>,
+    /// Style of the current user's messages when `highlight_self` is on
+    #[serde(default = "default_own_message")]
+    pub own_message: ThemeStyle,
+}
+
+fn default_highlight() -> ThemeStyle {
+    ThemeStyle::Invert
+}
+
+fn default_own_message() -> ThemeStyle {
+    ThemeStyle::Bold
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            highlight: default_highlight(),
+            nickname: None,
+            own_message: default_own_message(),
+        }
+    }
+}
+
+impl Theme {
+    /// Loads the theme file, falling back to the default theme if there is none
+    pub fn load() -> Result<Theme, Error> {
+        let home_dir = env::home_dir().ok_or(HomeDirError)?;
+        let mut file = match OpenOptions::new()
+            .read(true)
+            .open(home_dir.join(".config/dex/theme.toml"))
+        {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(Theme::default()),
+            Err(err) => return Err(err)?,
+        };
+        let mut buf = String::new();
+        file.read_to_string(&mut buf)?;
+
+        let theme: Theme = toml::from_str(&buf)?;
+        Ok(theme)
+    }
+
+    /// Writes the theme as TOML, in the same format as the theme file
+    pub fn export(&self, path: &Path) -> Result<(), Error> {
+        let data = toml::to_string(self)?;
+        File::create(path)?.write_all(data.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Theme, ThemeStyle};
+    use toml;
+
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Read;
+    use std::process;
+
+    #[test]
+    fn empty_theme() {
+        let theme: Theme = toml::from_str("").unwrap();
+
+        assert_eq!(theme, Theme::default());
+    }
+
+    #[test]
+    fn highlight_style() {
+        let theme: Theme = toml::from_str("highlight = \"underline\"").unwrap();
+
+        assert_eq!(theme.highlight, ThemeStyle::Underline);
+    }
+
+    #[test]
+    fn export_round_trip() {
+        let theme = Theme {
+            highlight: ThemeStyle::Bold,
+            nickname: Some(208),
+            own_message: ThemeStyle::Italic,
+        };
+        let path = env::temp_dir().join(format!("dex-theme-{}.toml", process::id()));
+
+        theme.export(&path).unwrap();
+        let mut buf = String::new();
+        File::open(&path).unwrap().read_to_string(&mut buf).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let loaded: Theme = toml::from_str(&buf).unwrap();
+        assert_eq!(loaded, theme);
+        // The default theme has no nickname color to write out
+        assert!(Theme::default().export(&path).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+}
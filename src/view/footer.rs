@@ -0,0 +1,207 @@
+use view::terminal::Terminal;
+
+use std::cell::{Cell, RefCell};
+use std::time::{Duration, Instant};
+
+use helpers::cooldown;
+
+use termbuf::TermSize;
+use textwrap::wrap;
+
+const SIDE_PADDING: usize = 3;
+/// Seconds command feedback is shown for
+pub const STATUS_SECS: u64 = 5;
+
+/// Bottom line of the screen, used for command feedback
+pub struct Footer {
+    status: RefCell<Option<String>>,
+    /// When the status is hidden, `None` keeping it until it is cleared
+    status_expiry: Cell<Option<Instant>>,
+    /// End of the current slow mode cooldown
+    slow_mode_until: Cell<Option<Instant>>,
+    /// Name of the configured timezone
+    timezone: RefCell<Option<String>>,
+    show_timezone: Cell<bool>,
+    /// Summary of channels with unread messages
+    unread: RefCell<Option<String>>,
+    /// Messages in the open channel that have not been read
+    unseen: Cell<usize>,
+}
+
+/// Compact list of unread channels, `!` marking those that mention the user
+pub fn unread_summary(channels: &[(String, usize, bool)]) -> Option<String> {
+    if channels.is_empty() {
+        return None;
+    }
+    let badges: Vec<_> = channels
+        .iter()
+        .map(|&(ref name, count, mention)| {
+            format!("#{}({}{})", name, count, if mention { "!" } else { "" })
+        })
+        .collect();
+    Some(badges.join(" "))
+}
+
+/// Whether a status expiring at `expiry` is still shown at `now`
+fn status_shown(expiry: Option<Instant>, now: Instant) -> bool {
+    expiry.map_or(true, |expiry| now < expiry)
+}
+
+fn timezone_segment(timezone: Option<&str>, show: bool) -> Option<String> {
+    match timezone {
+        Some(timezone) if show => Some(format!("[{}]", timezone)),
+        _ => None,
+    }
+}
+
+impl Footer {
+    pub fn new() -> Footer {
+        Footer {
+            status: RefCell::new(None),
+            status_expiry: Cell::new(None),
+            slow_mode_until: Cell::new(None),
+            timezone: RefCell::new(None),
+            show_timezone: Cell::new(true),
+            unread: RefCell::new(None),
+            unseen: Cell::new(0),
+        }
+    }
+
+    pub fn set_timezone(&self, timezone: Option<String>) {
+        *self.timezone.borrow_mut() = timezone;
+    }
+
+    pub fn toggle_timezone(&self) {
+        self.show_timezone.set(!self.show_timezone.get());
+    }
+
+    pub fn set_unread(&self, unread: Option<String>) {
+        *self.unread.borrow_mut() = unread;
+    }
+
+    /// Updates the open channel's unread count, returns whether it changed
+    pub fn set_unseen(&self, unseen: usize) -> bool {
+        self.unseen.replace(unseen) != unseen
+    }
+
+    pub fn set_slow_mode_until(&self, until: Instant) {
+        self.slow_mode_until.set(Some(until));
+    }
+
+    /// Shows feedback for `STATUS_SECS` or until the next keypress
+    pub fn set_status(&self, status: String) {
+        *self.status.borrow_mut() = Some(status);
+        self.status_expiry
+            .set(Some(Instant::now() + Duration::from_secs(STATUS_SECS)));
+    }
+
+    /// Shows a status until it is cleared, for states that last like reconnecting
+    pub fn set_lasting_status(&self, status: String) {
+        *self.status.borrow_mut() = Some(status);
+        self.status_expiry.set(None);
+    }
+
+    pub fn clear_status(&self) {
+        *self.status.borrow_mut() = None;
+    }
+
+    /// Clears the status on a keypress unless it is a lasting one
+    pub fn dismiss_status(&self) {
+        if self.status_expiry.get().is_some() {
+            self.clear_status();
+        }
+    }
+
+    /// Footer text wrapped to the screen, empty when there is nothing to show
+    pub fn lines(&self, size: TermSize) -> Vec<String> {
+        let mut segments = Vec::new();
+        if self.unseen.get() > 0 {
+            segments.push(format!("[{} new]", self.unseen.get()));
+        }
+        if let Some(ref unread) = *self.unread.borrow() {
+            segments.push(unread.clone());
+        }
+        let timezone = self.timezone.borrow();
+        if let Some(timezone) = timezone_segment(
+            timezone.as_ref().map(String::as_str),
+            self.show_timezone.get(),
+        ) {
+            segments.push(timezone);
+        }
+        let now = Instant::now();
+        if let Some(ref status) = *self.status.borrow() {
+            if status_shown(self.status_expiry.get(), now) {
+                segments.push(status.clone());
+            }
+        }
+        if let Some(until) = self.slow_mode_until.get() {
+            if until > now {
+                segments.push(format!(
+                    "[slow mode {}s]",
+                    cooldown::seconds_left(until - now)
+                ));
+            }
+        }
+        let text = segments.join(" ");
+        if text.is_empty() {
+            return Vec::new();
+        }
+        let width = size.width.saturating_sub(SIDE_PADDING * 2).max(1);
+        wrap(&text, width)
+            .into_iter()
+            .map(|line| line.into_owned())
+            .collect()
+    }
+
+    pub fn render(&self, screen: &mut Terminal, size: TermSize) {
+        let lines = self.lines(size);
+        let top = size.height.saturating_sub(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            screen.buf.print(SIDE_PADDING, top + i, line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{status_shown, timezone_segment, unread_summary};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn timezone_label() {
+        assert_eq!(
+            timezone_segment(Some("PST"), true),
+            Some("[PST]".to_owned())
+        );
+        assert_eq!(timezone_segment(Some("PST"), false), None);
+        assert_eq!(timezone_segment(None, true), None);
+    }
+
+    #[test]
+    fn status_expiry() {
+        let now = Instant::now();
+        let expiry = now + Duration::from_secs(5);
+
+        assert!(status_shown(Some(expiry), now));
+        assert!(status_shown(
+            Some(expiry),
+            now + Duration::from_millis(4999)
+        ));
+        assert!(!status_shown(Some(expiry), expiry));
+        // Lasting statuses stay until cleared
+        assert!(status_shown(None, now + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn unread_badges() {
+        let channels = vec![
+            ("general".to_owned(), 2, true),
+            ("dev".to_owned(), 5, false),
+        ];
+        assert_eq!(
+            unread_summary(&channels),
+            Some("#general(2!) #dev(5)".to_owned())
+        );
+        assert_eq!(unread_summary(&[]), None);
+    }
+}
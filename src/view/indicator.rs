@@ -6,8 +6,9 @@ use std::collections::HashMap;
 use std::sync::mpsc::Sender;
 use std::sync::Arc;
 use std::thread;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::Duration;
 
+use helpers::clock::{Clock, SystemClock};
 use model::Event;
 
 use serenity::model::event::TypingStartEvent;
@@ -17,6 +18,13 @@ use termbuf::TermSize;
 
 const BOTTOM_START: usize = 1;
 const SIDE_PADDING: usize = 3;
+/// Seconds someone is shown typing for, Discord repeating the event while they keep typing
+const TYPING_SECS: u64 = 10;
+
+/// Whether someone who started typing at `started`, in seconds since the epoch, still is
+fn still_typing<C: Clock>(started: u64, clock: &C) -> bool {
+    (clock.now().timestamp().max(0) as u64).saturating_sub(started) < TYPING_SECS
+}
 
 pub struct Indicator {
     events: Arc<Mutex<Vec<TypingStartEvent>>>,
@@ -67,20 +75,18 @@ impl Indicator {
         let channel = self.event_channel.clone();
 
         thread::spawn(move || {
-            thread::sleep(Duration::from_secs(11));
-            let start = SystemTime::now();
-            let since_the_epoch = start
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards");
-            let since_the_epoch = since_the_epoch.as_secs();
-
+            thread::sleep(Duration::from_secs(TYPING_SECS + 1));
             events
                 .lock()
-                .retain(|i| (since_the_epoch - i.timestamp) < 10);
-            channel.send(Event::WindowSizeChange).unwrap();
+                .retain(|event| still_typing(event.timestamp, &SystemClock));
+            channel.send(Event::Redraw).unwrap();
         });
     }
 
+    pub fn is_typing(&self) -> bool {
+        !self.events.lock().is_empty()
+    }
+
     pub fn render(&self, screen: &mut Terminal, size: TermSize) {
         let text = self
             .events
@@ -96,3 +102,23 @@ impl Indicator {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::still_typing;
+    use chrono::{Duration, TimeZone, Utc};
+    use helpers::clock::FakeClock;
+
+    #[test]
+    fn typing_expires() {
+        let clock = FakeClock::new(Utc.timestamp(1_000, 0));
+
+        assert!(still_typing(1_000, &clock));
+        clock.advance(Duration::seconds(9));
+        assert!(still_typing(1_000, &clock));
+        clock.advance(Duration::seconds(1));
+        assert!(!still_typing(1_000, &clock));
+        // Started after the local clock, as clocks drift
+        assert!(still_typing(1_020, &clock));
+    }
+}
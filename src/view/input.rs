@@ -8,7 +8,6 @@ use termbuf::TermSize;
 
 use failure::Error;
 
-const BOTTOM_START: usize = 3;
 const SIDE_PADDING: usize = 3;
 
 enum State {
@@ -33,7 +32,9 @@ impl Input {
         }
     }
 
-    pub fn render(&self, screen: &mut Terminal, size: TermSize) {
+    /// Draws the input box with `below` rows left free underneath it
+    pub fn render(&self, screen: &mut Terminal, size: TermSize, below: usize) {
+        let text_y = size.height.saturating_sub(below + 2);
         let saturated_side = (size.width as usize).saturating_sub(SIDE_PADDING * 2);
         let clipped_text = if self.text.len() > saturated_side {
             let clip = self
@@ -53,21 +54,40 @@ impl Input {
 
         screen.buf.draw_box(
             SIDE_PADDING - 1,
-            size.height.saturating_sub(BOTTOM_START + 1),
+            text_y.saturating_sub(1),
             saturated_side,
             1,
         );
 
-        screen.buf.print(
-            SIDE_PADDING,
-            size.height.saturating_sub(BOTTOM_START),
-            &clipped_text,
-        );
+        screen.buf.print(SIDE_PADDING, text_y, &clipped_text);
 
-        screen.buf.set_cursor_position(
-            SIDE_PADDING + clipped_text.len() + 1,
-            size.height.saturating_sub(BOTTOM_START) + 1,
-        );
+        screen
+            .buf
+            .set_cursor_position(SIDE_PADDING + clipped_text.len() + 1, text_y + 1);
+    }
+
+    /// Replaces the message being composed, e.g. to restore a message that could not be sent
+    pub fn set_text(&mut self, text: String) {
+        self.state = State::Message;
+        self.text = text;
+    }
+
+    /// Replaces the text of the command prompt, e.g. with a command recalled from history
+    pub fn set_command(&mut self, command: &str) {
+        self.state = State::Command;
+        self.text = format!(":{}", command);
+    }
+
+    /// Message being composed, or the command being typed
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_command(&self) -> bool {
+        match self.state {
+            State::Command => true,
+            State::Message => false,
+        }
     }
 
     pub fn submit(&mut self) -> Result<(), Error> {
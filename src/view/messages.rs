@@ -1,4 +1,6 @@
-use serenity::model::channel;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+use regex::Regex;
+use serenity::model::channel::{self, MessageReaction, ReactionType};
 use serenity::model::event::MessageUpdateEvent;
 use serenity::model::id::{ChannelId, MessageId, UserId};
 use serenity::prelude::Mutex;
@@ -8,15 +10,23 @@ use termbuf::Color;
 use termbuf::Style;
 use termbuf::TermSize;
 use textwrap::fill;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use std::cell::RefCell;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::io;
+use std::mem;
 use std::sync::Arc;
 
 use discord::utils;
-use model::{Application, Context, MessageItem};
+use helpers::clock::{FakeClock, SystemClock};
+use helpers::{emoji, relative, timezone};
+use model::{
+    Application, ChannelBuffers, Context, EmojiStyle, JumpStack, MessageItem, NameAlign, NameWidth,
+};
+use view::overlay::Span;
 use view::terminal::Terminal;
 
 const LEFT_PADDING: usize = 20;
@@ -25,7 +35,29 @@ const TIME_PADDING: usize = 3;
 const LEFT_START: usize = 5;
 const LEFT_START_EXTENDED: usize = 30;
 const TOP_START: usize = 1;
-const BOTTOM_DIFF: usize = 6;
+
+/// Messages requested per page of history
+const PAGE_SIZE: u64 = 50;
+/// Most messages Discord returns for one request
+const MAX_PAGE_SIZE: u64 = 100;
+/// Messages scrolled per page up/down
+const SCROLL_STEP: usize = 5;
+
+/// Index of the message drawn on a screen row, given the rows each message spans
+fn message_at_row(drawn: &[(usize, usize, usize)], row: usize) -> Option<usize> {
+    drawn
+        .iter()
+        .find(|&&(top, bottom, _)| top <= row && row <= bottom)
+        .map(|&(_, _, index)| index)
+}
+
+/// Where a removed message goes back into `messages`, which are in the order they were sent
+fn restore_position(messages: &[MessageItem], id: MessageId) -> usize {
+    messages
+        .iter()
+        .position(|msg| message_id(msg) > id)
+        .unwrap_or_else(|| messages.len())
+}
 
 fn color_to_8bit(colour: ::serenity::utils::Colour) -> Color {
     let r = (u16::from(colour.r()) * 5 / 255) as u8;
@@ -34,6 +66,334 @@ fn color_to_8bit(colour: ::serenity::utils::Colour) -> Color {
     Color::AnsiValue(16 + 36 * r + 6 * g + b)
 }
 
+/// Sample text in a colour as drawn with truecolor and with the 256 colour fallback
+pub fn colour_samples(sample: &str, colour: Colour) -> Vec<Span> {
+    vec![
+        Span::coloured(
+            format!("{} (truecolor)", sample),
+            Color::Rgb(colour.r(), colour.g(), colour.b()),
+        ),
+        Span::plain("  ".to_owned()),
+        Span::coloured(format!("{} (256 colour)", sample), color_to_8bit(colour)),
+    ]
+}
+
+/// Whether the sidebar is drawn, which it is not on terminals too narrow to leave room for
+/// messages beside it even when the user asked for it
+fn sidebar_fits(visible: bool, width: usize, min_width: usize) -> bool {
+    visible && width >= min_width
+}
+
+/// Colour `step` of `steps` along a red, green, blue gradient
+fn ramp_colour(step: usize, steps: usize) -> Colour {
+    let t = step as f32 / (steps.max(2) - 1) as f32;
+    let r = 255.0 * (1.0 - t);
+    let g = 255.0 * (1.0 - (2.0 * t - 1.0).abs());
+    let b = 255.0 * t;
+    Colour::from_rgb(r.round() as u8, g.round() as u8, b.round() as u8)
+}
+
+/// A gradient of `steps` cells drawn with truecolor and again with the 256 colour fallback,
+/// the two lines looking alike only when the terminal supports truecolor
+pub fn colour_ramp(steps: usize) -> Vec<Vec<Span>> {
+    let ramp =
+        |label: &str, encode: &Fn(Colour) -> Color| {
+            let mut line = vec![Span::plain(format!("{:<12}", label))];
+            line.extend((0..steps).map(|step| {
+                Span::coloured("\u{2588}".to_owned(), encode(ramp_colour(step, steps)))
+            }));
+            line
+        };
+    vec![
+        ramp("truecolor", &|colour| {
+            Color::Rgb(colour.r(), colour.g(), colour.b())
+        }),
+        ramp("256 colour", &color_to_8bit),
+    ]
+}
+
+/// Splits a request for `count` messages into requests Discord accepts
+fn page_sizes(count: u64) -> Vec<u64> {
+    let mut pages = vec![MAX_PAGE_SIZE; (count / MAX_PAGE_SIZE) as usize];
+    if count % MAX_PAGE_SIZE > 0 {
+        pages.push(count % MAX_PAGE_SIZE);
+    }
+    pages
+}
+
+/// A page shorter than requested means the start of the channel was reached
+fn is_end_of_history(requested: u64, received: usize) -> bool {
+    (received as u64) < requested
+}
+
+/// How a reaction's emoji is shown, custom emoji by their `:name:`
+pub fn reaction_label(reaction_type: &ReactionType) -> String {
+    match *reaction_type {
+        ReactionType::Unicode(ref emoji) => emoji.clone(),
+        ReactionType::Custom { ref name, .. } => {
+            format!(":{}:", name.as_ref().map(String::as_str).unwrap_or("?"))
+        }
+    }
+}
+
+/// Whether two reactions are the same emoji, custom emoji being compared by id alone
+fn same_emoji(a: &ReactionType, b: &ReactionType) -> bool {
+    match (a, b) {
+        (&ReactionType::Unicode(ref a), &ReactionType::Unicode(ref b)) => a == b,
+        (&ReactionType::Custom { id: a, .. }, &ReactionType::Custom { id: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+/// Counts the current user's reaction, which is only counted once per emoji
+fn add_own_reaction(reactions: &mut Vec<MessageReaction>, reaction_type: ReactionType) {
+    match reactions
+        .iter()
+        .position(|reaction| same_emoji(&reaction.reaction_type, &reaction_type))
+    {
+        Some(index) => {
+            let reaction = &mut reactions[index];
+            if !reaction.me {
+                reaction.count += 1;
+                reaction.me = true;
+            }
+        }
+        None => reactions.push(MessageReaction {
+            count: 1,
+            me: true,
+            reaction_type,
+        }),
+    }
+}
+
+/// Summary of a message's reactions, showing at most `max` before a `+N` count of the rest
+fn reaction_line(reactions: &[MessageReaction], max: usize) -> String {
+    let mut parts: Vec<_> = reactions
+        .iter()
+        .take(max)
+        .map(|reaction| {
+            format!(
+                "{} {}",
+                reaction_label(&reaction.reaction_type),
+                reaction.count
+            )
+        })
+        .collect();
+    let hidden = reactions.len().saturating_sub(max);
+    if hidden > 0 {
+        parts.push(format!("+{}", hidden));
+    }
+    parts.join("  ")
+}
+
+/// Color to draw a nickname in, the theme's default when role colors are off or unset
+fn nick_colour(
+    role_colour: Option<Colour>,
+    role_colors: bool,
+    truecolor: bool,
+    default: Option<u8>,
+) -> Option<Color> {
+    match role_colour {
+        Some(colour) if role_colors => Some(if truecolor {
+            Color::Rgb(colour.r(), colour.g(), colour.b())
+        } else {
+            color_to_8bit(colour)
+        }),
+        _ => default.map(Color::AnsiValue),
+    }
+}
+
+/// Splits a line wider than `width` columns at grapheme boundaries, for words too long to wrap
+fn hard_break(line: &str, width: usize) -> Vec<String> {
+    if width == 0 {
+        return vec![line.to_owned()];
+    }
+    let mut lines = vec![String::new()];
+    let mut line_width = 0;
+    for grapheme in line.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if line_width + grapheme_width > width && line_width > 0 {
+            lines.push(String::new());
+            line_width = 0;
+        }
+        lines.last_mut().unwrap().push_str(grapheme);
+        line_width += grapheme_width;
+    }
+    lines
+}
+
+fn message_id(item: &MessageItem) -> MessageId {
+    match item {
+        MessageItem::DiscordMessage(msg) => msg.id,
+    }
+}
+
+/// Maps each message id to its position in the buffer
+fn build_index(messages: &[MessageItem]) -> HashMap<MessageId, usize> {
+    messages
+        .iter()
+        .enumerate()
+        .map(|(i, msg)| (message_id(msg), i))
+        .collect()
+}
+
+/// Marker drawn before a message with attachments, with a count when there are several
+fn attachment_indicator(msg: &channel::Message, paper_clip: char) -> Option<String> {
+    match msg.attachments.len() {
+        0 => None,
+        1 => Some(paper_clip.to_string()),
+        count => Some(format!("{}{}", paper_clip, count)),
+    }
+}
+
+/// Markers drawn before a message's content, for being pinned and for its attachments
+fn indicators(msg: &channel::Message, pin: char, paper_clip: char) -> Option<String> {
+    let pinned = if msg.pinned {
+        Some(pin.to_string())
+    } else {
+        None
+    };
+    match (pinned, attachment_indicator(msg, paper_clip)) {
+        (Some(pinned), Some(attachments)) => Some(format!("{} {}", pinned, attachments)),
+        (pinned, attachments) => pinned.or(attachments),
+    }
+}
+
+/// Whether a message mentions the user, which includes replies that ping them
+fn addresses_user(msg: &channel::Message, user_id: UserId) -> bool {
+    msg.mentions.iter().any(|user| user.id == user_id)
+}
+
+/// Day of `timestamp` in `timezone` or the local one
+fn message_date(timestamp: DateTime<FixedOffset>, timezone: Option<FixedOffset>) -> NaiveDate {
+    match timezone {
+        Some(offset) => timestamp.with_timezone(&offset).date().naive_local(),
+        None => timestamp.with_timezone(&Local).date().naive_local(),
+    }
+}
+
+/// Whether a day separator belongs between two consecutive messages
+fn crosses_day(
+    older: DateTime<FixedOffset>,
+    newer: DateTime<FixedOffset>,
+    timezone: Option<FixedOffset>,
+) -> bool {
+    message_date(older, timezone) != message_date(newer, timezone)
+}
+
+/// Rule spanning `width` columns with the date in the middle
+fn day_separator(date: NaiveDate, width: usize) -> String {
+    let label = format!(" {} ", date.format("%A, %b %-d"));
+    let label_len = label.chars().count();
+    if label_len >= width {
+        return label.chars().take(width).collect();
+    }
+    let left = (width - label_len) / 2;
+    format!(
+        "{}{}{}",
+        "─".repeat(left),
+        label,
+        "─".repeat(width - label_len - left)
+    )
+}
+
+/// Whether a message by `author` is drawn in the own message style
+fn is_own_highlighted(author: UserId, current_user: Option<UserId>, enabled: bool) -> bool {
+    enabled && current_user == Some(author)
+}
+
+/// Number shown in the gutter for the message at `index`, the `Fixed` address resolving to it
+pub fn message_number(index: usize) -> usize {
+    index + 1
+}
+
+/// Whether a message's spoilers are shown, a global setting wins over revealing one message
+fn spoilers_visible(global: Option<bool>, revealed: bool) -> bool {
+    global.unwrap_or(revealed)
+}
+
+/// Removes `||spoiler||` markers, blanking out the hidden text unless `visible`
+fn mask_spoilers(content: &str, visible: bool) -> String {
+    let mut masked = String::with_capacity(content.len());
+    let mut parts = content.split("||").peekable();
+    let mut inside = false;
+    while let Some(part) = parts.next() {
+        // An unmatched marker is just text
+        if inside && parts.peek().is_none() {
+            masked.push_str("||");
+            masked.push_str(part);
+            break;
+        }
+        if inside && !visible {
+            masked.extend(part.chars().map(|ch| if ch == '\n' { ch } else { '█' }));
+        } else {
+            masked.push_str(part);
+        }
+        inside = !inside;
+    }
+    masked
+}
+
+/// Column a nickname's column starts at, after the timestamp or a fixed width timestamp column
+fn nick_column(left_start: usize, timestamp_len: usize, fixed: Option<usize>) -> usize {
+    left_start + fixed.unwrap_or(timestamp_len).max(timestamp_len) + 1
+}
+
+/// Column to draw a nickname at, aligned within a column of `column_width` starting at `x`
+fn nick_x(x: usize, nick_len: usize, column_width: usize, align: NameAlign) -> usize {
+    match align {
+        NameAlign::Left => x,
+        NameAlign::Right => x + column_width.saturating_sub(nick_len),
+    }
+}
+
+/// Width of the nickname column, growing from `all_time` with `visible` names, or only fitting the
+/// `visible` ones so a long name that scrolled away stops widening it
+fn name_column_width<I>(all_time: usize, visible: I, mode: NameWidth) -> usize
+where
+    I: IntoIterator<Item = usize>,
+{
+    let visible = visible.into_iter().max().unwrap_or(0);
+    match mode {
+        NameWidth::All => all_time.max(visible),
+        NameWidth::Visible => visible,
+    }
+}
+
+/// Columns of every non-empty `regex` match in `line`, as `(start, end)` character offsets
+fn highlight_spans(regex: &Regex, line: &str) -> Vec<(usize, usize)> {
+    regex
+        .find_iter(line)
+        .filter(|m| m.start() != m.end())
+        .map(|m| {
+            let start = line[..m.start()].chars().count();
+            (start, start + m.as_str().chars().count())
+        })
+        .collect()
+}
+
+/// Parses the value of the `DEX_TRUECOLOR` override
+fn parse_truecolor_override(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "0" | "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Decides if truecolor escapes should be used
+///
+/// A forced value always wins, otherwise `COLORTERM` must advertise `truecolor` or `24bit`
+fn detect_truecolor(colorterm: Option<&str>, force: Option<bool>) -> bool {
+    if let Some(force) = force {
+        return force;
+    }
+    match colorterm.map(|term| term.to_lowercase()) {
+        Some(term) => term == "truecolor" || term == "24bit",
+        None => false,
+    }
+}
+
 pub struct Messages {
     pub messages: RefCell<Vec<MessageItem>>,
     max_name_len: RefCell<usize>,
@@ -41,14 +401,47 @@ pub struct Messages {
     truecolor: bool,
     nickname_cache: RefCell<HashMap<UserId, (String, Option<Colour>)>>,
     show_sidebar: Arc<Mutex<bool>>,
+    /// Set whenever the buffer changes and a redraw is required
+    dirty: Cell<bool>,
+    /// Pattern set with `filter`, highlighted in every rendered message
+    highlight: RefCell<Option<Regex>>,
+    /// Message addressed by `.`, the newest message when unset
+    current: Cell<Option<MessageId>>,
+    /// Messages that arrived for other channels
+    pub background: RefCell<ChannelBuffers>,
+    /// Number of newest messages scrolled past
+    scroll: Cell<usize>,
+    /// Whether the oldest buffered message was drawn in the last render
+    top_visible: Cell<bool>,
+    /// Set once the whole channel history has been loaded
+    reached_start: Cell<bool>,
+    /// Current message and scroll offset before each jump
+    jumps: RefCell<JumpStack<(Option<MessageId>, usize)>>,
+    /// Position of each message in `messages`, for lookups by id
+    index: RefCell<HashMap<MessageId, usize>>,
+    /// Messages whose spoilers were revealed
+    revealed: RefCell<HashSet<MessageId>>,
+    /// Shows or hides every spoiler regardless of `revealed`, set with `spoilers on|off`
+    spoilers: Cell<Option<bool>>,
+    /// Only show messages addressed to this user, set with `reading`
+    reading: Cell<Option<UserId>>,
+    /// Show each message's address in the gutter, set with `numbers`
+    numbers: Cell<bool>,
+    /// First and last screen row of each message in the last render, with its index
+    drawn: RefCell<Vec<(usize, usize, usize)>>,
+    /// Messages removed by each of the user's deletions, put back by `undo_delete`
+    removed: RefCell<JumpStack<Vec<MessageItem>>>,
 }
 
 impl Messages {
-    pub fn new(timestamp_fmt: String, show_sidebar: bool) -> Messages {
-        let truecolor = match env::var("COLORTERM") {
-            Ok(term) => term.to_lowercase() == "truecolor",
-            Err(_) => false,
-        };
+    pub fn new(timestamp_fmt: String, show_sidebar: bool, truecolor: Option<bool>) -> Messages {
+        // The environment takes precedence over the config file
+        let force_truecolor = env::var("DEX_TRUECOLOR")
+            .ok()
+            .and_then(|value| parse_truecolor_override(&value))
+            .or(truecolor);
+        let colorterm = env::var("COLORTERM").ok();
+        let truecolor = detect_truecolor(colorterm.as_ref().map(String::as_str), force_truecolor);
 
         Messages {
             messages: RefCell::new(Vec::new()),
@@ -57,39 +450,337 @@ impl Messages {
             truecolor,
             nickname_cache: RefCell::new(HashMap::new()),
             show_sidebar: Arc::new(Mutex::new(show_sidebar)),
+            dirty: Cell::new(true),
+            highlight: RefCell::new(None),
+            current: Cell::new(None),
+            background: RefCell::new(ChannelBuffers::new()),
+            scroll: Cell::new(0),
+            top_visible: Cell::new(false),
+            reached_start: Cell::new(false),
+            jumps: RefCell::new(JumpStack::new()),
+            index: RefCell::new(HashMap::new()),
+            revealed: RefCell::new(HashSet::new()),
+            spoilers: Cell::new(None),
+            reading: Cell::new(None),
+            numbers: Cell::new(false),
+            drawn: RefCell::new(Vec::new()),
+            removed: RefCell::new(JumpStack::new()),
+        }
+    }
+
+    pub fn set_numbers(&self, numbers: bool) {
+        self.numbers.set(numbers);
+        self.mark_dirty();
+    }
+
+    pub fn set_reading(&self, user: Option<UserId>) {
+        self.reading.set(user);
+        self.scroll.set(0);
+        self.mark_dirty();
+    }
+
+    /// Reveals or hides the spoilers of the current message
+    pub fn toggle_reveal(&self) {
+        let current = self.current_index();
+        let id = match self.messages.borrow().get(current) {
+            Some(msg) => message_id(msg),
+            None => return,
+        };
+        let mut revealed = self.revealed.borrow_mut();
+        if !revealed.remove(&id) {
+            revealed.insert(id);
+        }
+        self.mark_dirty();
+    }
+
+    pub fn set_spoilers(&self, spoilers: Option<bool>) {
+        self.spoilers.set(spoilers);
+        self.mark_dirty();
+    }
+
+    /// Recomputes the id to position map, needed after any change that shifts messages
+    pub fn rebuild_index(&self) {
+        *self.index.borrow_mut() = build_index(&self.messages.borrow());
+    }
+
+    /// Drops every loaded and background message
+    pub fn clear(&self) {
+        self.messages.borrow_mut().clear();
+        *self.background.borrow_mut() = ChannelBuffers::new();
+        self.current.set(None);
+        self.scroll.set(0);
+        self.jumps.borrow_mut().clear();
+        self.removed.borrow_mut().clear();
+        self.rebuild_index();
+        self.mark_dirty();
+    }
+
+    /// Makes a message current, remembering the previous position for `jump_back`
+    pub fn jump_to(&self, index: usize) {
+        self.jumps
+            .borrow_mut()
+            .push((self.current.get(), self.scroll.get()));
+        self.set_current(index);
+    }
+
+    /// Jumps to the message at `index` and scrolls it into view
+    pub fn select(&self, index: usize) {
+        self.jump_to(index);
+        self.scroll_to(index);
+    }
+
+    /// Scrolls so the message at `index` is the newest one shown
+    pub fn scroll_to(&self, index: usize) {
+        let len = self.messages.borrow().len();
+        self.scroll.set(len.saturating_sub(index + 1));
+        self.mark_dirty();
+    }
+
+    /// Newest message in view when scrolled back, `None` while following new messages
+    pub fn anchor(&self) -> Option<MessageId> {
+        let scroll = self.scroll.get();
+        if scroll == 0 {
+            return None;
+        }
+        let messages = self.messages.borrow();
+        let index = messages.len().checked_sub(scroll + 1)?;
+        messages.get(index).map(message_id)
+    }
+
+    /// Index of a loaded message
+    pub fn index_of(&self, id: MessageId) -> Option<usize> {
+        self.index.borrow().get(&id).cloned()
+    }
+
+    /// Scrolls back to an `anchor` taken earlier, false if that message is not loaded
+    pub fn restore_anchor(&self, anchor: MessageId) -> bool {
+        let index = match self.index.borrow().get(&anchor) {
+            Some(&index) => index,
+            None => return false,
+        };
+        self.scroll_to(index);
+        true
+    }
+
+    /// Returns to the position before the last jump, false if there is none
+    pub fn jump_back(&self) -> bool {
+        match self.jumps.borrow_mut().pop() {
+            Some((current, scroll)) => {
+                self.current.set(current);
+                self.scroll.set(scroll);
+                self.mark_dirty();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Scrolls towards older messages, fetching more history once the top is visible.
+    /// Returns false when there is nothing older left to show
+    pub fn scroll_up(&self, app: &Application) -> bool {
+        self.scroll_up_with(|| self.load_older_messages(app))
+    }
+
+    /// `scroll_up`, fetching older history with `load_older`
+    fn scroll_up_with<F: FnOnce() -> usize>(&self, load_older: F) -> bool {
+        if self.top_visible.get() && load_older() == 0 {
+            return false;
+        }
+        let len = self.messages.borrow().len();
+        self.scroll
+            .set((self.scroll.get() + SCROLL_STEP).min(len.saturating_sub(1)));
+        self.mark_dirty();
+        true
+    }
+
+    /// Whether the newest message is on screen
+    pub fn at_bottom(&self) -> bool {
+        self.scroll.get() == 0
+    }
+
+    pub fn scroll_down(&self) {
+        self.scroll
+            .set(self.scroll.get().saturating_sub(SCROLL_STEP));
+        self.mark_dirty();
+    }
+
+    /// Fetches the page of history before the oldest buffered message, returning how many
+    /// messages were added
+    pub fn load_older_messages(&self, app: &Application) -> usize {
+        use serenity::builder::GetMessages;
+
+        if self.reached_start.get() {
+            return 0;
+        }
+        let channel = match app.context.read().channel {
+            Some(channel) => channel,
+            None => return 0,
+        };
+        let oldest = match self.messages.borrow().first() {
+            Some(MessageItem::DiscordMessage(msg)) => msg.id,
+            None => return 0,
+        };
+
+        let retriever = GetMessages::default().before(oldest).limit(PAGE_SIZE);
+        let page = match channel.messages(|_| retriever) {
+            Ok(page) => page,
+            Err(err) => {
+                app.send_err(format_err!("Unable to load older messages: {}", err));
+                return 0;
+            }
+        };
+        if is_end_of_history(PAGE_SIZE, page.len()) {
+            self.reached_start.set(true);
+        }
+
+        let count = page.len();
+        // Pages come newest first
+        {
+            let mut messages = self.messages.borrow_mut();
+            for message in page {
+                messages.insert(0, MessageItem::DiscordMessage(Box::new(message)));
+            }
+        }
+        self.rebuild_index();
+        self.mark_dirty();
+        count
+    }
+
+    /// Keeps a message for a channel that is not open, noting whether it mentions `user`
+    pub fn add_background_msg(&self, msg: MessageItem, user: Option<UserId>) {
+        let mention = match (&msg, user) {
+            (MessageItem::DiscordMessage(msg), Some(user)) => addresses_user(msg, user),
+            (_, None) => false,
+        };
+        if self.background.borrow_mut().push(msg, mention) {
+            // The footer lists unread channels
+            self.mark_dirty();
+        }
+    }
+
+    /// Index of the current message in the buffer
+    pub fn current_index(&self) -> usize {
+        self.current
+            .get()
+            .and_then(|current| self.index.borrow().get(&current).cloned())
+            .unwrap_or_else(|| self.messages.borrow().len().saturating_sub(1))
+    }
+
+    /// Id of the explicitly selected message, if it is still loaded
+    pub fn current_message(&self) -> Option<MessageId> {
+        let current = self.current.get()?;
+        let loaded = self.messages.borrow().iter().any(|msg| match msg {
+            MessageItem::DiscordMessage(msg) => msg.id == current,
+        });
+        if loaded {
+            Some(current)
+        } else {
+            None
         }
     }
 
+    pub fn set_current(&self, index: usize) {
+        let current = self.messages.borrow().get(index).map(|msg| match msg {
+            MessageItem::DiscordMessage(msg) => msg.id,
+        });
+        self.current.set(current);
+        self.mark_dirty();
+    }
+
+    pub fn set_highlight(&self, highlight: Option<Regex>) {
+        *self.highlight.borrow_mut() = highlight;
+        self.mark_dirty();
+    }
+
+    pub fn mark_dirty(&self) {
+        self.dirty.set(true)
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty.get()
+    }
+
+    pub fn clear_dirty(&self) {
+        self.dirty.set(false)
+    }
+
+    /// Names the authors drawn so far are shown as, their guild nickname where they have one
+    pub fn nicknames(&self) -> HashMap<UserId, String> {
+        self.nickname_cache
+            .borrow()
+            .iter()
+            .map(|(&user, &(ref nick, _))| (user, nick.clone()))
+            .collect()
+    }
+
     pub fn set_show_sidebar(&self, state: bool) {
+        if *self.show_sidebar.lock() != state {
+            self.mark_dirty();
+        }
         *self.show_sidebar.lock() = state
     }
 
+    /// Whether colours are drawn in truecolor, as detected or overridden
+    pub fn truecolor(&self) -> bool {
+        self.truecolor
+    }
+
     pub fn showing_sidebar(&self) -> bool {
         *self.show_sidebar.lock()
     }
 
-    pub fn add_msg(&self, msg: MessageItem) {
-        self.messages.borrow_mut().push(msg);
+    /// Column messages are drawn from, right of the sidebar when it is shown
+    fn left_start(&self) -> usize {
+        if self.showing_sidebar() {
+            LEFT_START_EXTENDED
+        } else {
+            LEFT_START
+        }
     }
 
-    pub fn delete_msg(&self, channel_id: ChannelId, message_id: MessageId) {
-        let mut msg_index = None;
-        for (i, msg) in self.messages.borrow().iter().enumerate() {
-            match msg {
-                MessageItem::DiscordMessage(msg) => {
-                    debug!("Deleting message: {}", message_id);
-                    if msg.channel_id == channel_id && msg.id == message_id {
-                        msg_index = Some(i);
-                        break;
-                    }
-                }
+    /// Makes the message drawn at a screen position current, false if there is none
+    pub fn select_at(&self, x: usize, y: usize) -> bool {
+        if x < self.left_start() {
+            return false;
+        }
+        match message_at_row(&self.drawn.borrow(), y) {
+            Some(index) => {
+                self.set_current(index);
+                true
             }
+            None => false,
         }
+    }
+
+    pub fn add_msg(&self, msg: MessageItem) {
+        // Messages can arrive twice, e.g. when events race with a reload
+        let id = message_id(&msg);
+        if self.index.borrow().contains_key(&id) {
+            return;
+        }
+        let mut messages = self.messages.borrow_mut();
+        self.index.borrow_mut().insert(id, messages.len());
+        messages.push(msg);
+        self.mark_dirty();
+    }
+
+    /// Drops a message deleted on Discord, which `undo_delete` does not bring back
+    pub fn delete_msg(&self, channel_id: ChannelId, message_id: MessageId) {
+        debug!("Deleting message: {}", message_id);
+        let msg_index = self.index.borrow().get(&message_id).cloned();
         if let Some(index) = msg_index {
-            self.messages.borrow_mut().remove(index);
+            let in_channel = match self.messages.borrow()[index] {
+                MessageItem::DiscordMessage(ref msg) => msg.channel_id == channel_id,
+            };
+            if in_channel {
+                self.messages.borrow_mut().remove(index);
+                self.rebuild_index();
+                self.mark_dirty();
+            }
         }
     }
 
+    /// Drops messages bulk deleted on Discord, which `undo_delete` does not bring back
     pub fn delete_msg_bulk(&self, channel_id: ChannelId, message_ids: &[MessageId]) {
         debug!(
             "Bulk delete: {}",
@@ -99,53 +790,169 @@ impl Messages {
                 .collect::<Vec<_>>()
                 .join(", ")
         );
-        self.messages.borrow_mut().retain(|msg| match msg {
-            MessageItem::DiscordMessage(msg) => {
-                msg.channel_id != channel_id && !message_ids.contains(&msg.id)
+        self.remove_msgs(channel_id, message_ids);
+    }
+
+    /// Drops messages the user deleted, which `undo_delete` puts back in one step
+    pub fn remove_for_undo(&self, channel_id: ChannelId, message_ids: &[MessageId]) {
+        let removed = self.remove_msgs(channel_id, message_ids);
+        if !removed.is_empty() {
+            self.removed.borrow_mut().push(removed);
+        }
+    }
+
+    fn remove_msgs(&self, channel_id: ChannelId, message_ids: &[MessageId]) -> Vec<MessageItem> {
+        let (removed, kept): (Vec<_>, Vec<_>) = {
+            let mut messages = self.messages.borrow_mut();
+            mem::replace(&mut *messages, Vec::new())
+                .into_iter()
+                .partition(|msg| match msg {
+                    MessageItem::DiscordMessage(msg) => {
+                        msg.channel_id == channel_id && message_ids.contains(&msg.id)
+                    }
+                })
+        };
+        *self.messages.borrow_mut() = kept;
+        self.rebuild_index();
+        self.mark_dirty();
+        removed
+    }
+
+    /// Puts back the messages removed by the last deletion, returning how many. Only the
+    /// buffer is restored, the messages stay deleted on Discord.
+    pub fn undo_delete(&self) -> usize {
+        let removed = match self.removed.borrow_mut().pop() {
+            Some(removed) => removed,
+            None => return 0,
+        };
+        let count = removed.len();
+        {
+            let mut messages = self.messages.borrow_mut();
+            for msg in removed {
+                // By id rather than former index, as messages may have been added or dropped since
+                let position = restore_position(&messages, message_id(&msg));
+                messages.insert(position, msg);
             }
-        });
+        }
+        self.rebuild_index();
+        self.mark_dirty();
+        count
     }
 
     pub fn update_message(&self, update: MessageUpdateEvent) {
-        for mut msg in self.messages.borrow_mut().iter_mut() {
-            match msg {
-                MessageItem::DiscordMessage(ref mut msg) => {
-                    if update.id == msg.id && update.channel_id == msg.channel_id {
-                        debug!("Updated message: {}", msg.id);
-                        utils::update_msg(msg, update);
-                        break;
-                    }
+        let index = match self.index.borrow().get(&update.id) {
+            Some(&index) => index,
+            None => return,
+        };
+        match self.messages.borrow_mut()[index] {
+            MessageItem::DiscordMessage(ref mut msg) => {
+                if update.channel_id == msg.channel_id {
+                    debug!("Updated message: {}", msg.id);
+                    // Also picks up attachments added by the edit
+                    utils::update_msg(msg, update);
+                    self.mark_dirty();
                 }
             }
         }
     }
 
+    /// Shows the current user's edit of a message without waiting for the gateway to echo it
+    pub fn apply_edit(&self, edited: &channel::Message) {
+        let index = match self.index.borrow().get(&edited.id) {
+            Some(&index) => index,
+            None => return,
+        };
+        match self.messages.borrow_mut()[index] {
+            MessageItem::DiscordMessage(ref mut msg) => {
+                msg.content = edited.content.clone();
+                msg.edited_timestamp = edited.edited_timestamp;
+            }
+        }
+        self.mark_dirty();
+    }
+
+    /// Shows a message as pinned or not after pinning or unpinning it
+    pub fn set_pinned(&self, id: MessageId, pinned: bool) {
+        let index = match self.index.borrow().get(&id) {
+            Some(&index) => index,
+            None => return,
+        };
+        match self.messages.borrow_mut()[index] {
+            MessageItem::DiscordMessage(ref mut msg) => msg.pinned = pinned,
+        }
+        self.mark_dirty();
+    }
+
+    /// Shows the current user's reaction on a message without waiting for the gateway
+    pub fn add_reaction(&self, id: MessageId, reaction_type: ReactionType) {
+        let index = match self.index.borrow().get(&id) {
+            Some(&index) => index,
+            None => return,
+        };
+        match self.messages.borrow_mut()[index] {
+            MessageItem::DiscordMessage(ref mut msg) => {
+                add_own_reaction(&mut msg.reactions, reaction_type)
+            }
+        }
+        self.mark_dirty();
+    }
+
     pub fn load_messages(&self, app: &Application) {
         use serenity::builder::GetMessages;
 
         let num = app.view.terminal_size.height;
-        let retriever = GetMessages::default().limit(num as u64);
-        if let Some(channel) = app.context.read().channel {
+        let channel = app.context.read().channel;
+        if let Some(channel) = channel {
             self.messages.borrow_mut().clear();
+            self.index.borrow_mut().clear();
+            self.scroll.set(0);
+            self.jumps.borrow_mut().clear();
+            self.removed.borrow_mut().clear();
+            self.reached_start.set(false);
+            self.mark_dirty();
 
-            for message in channel
-                .messages(|_| retriever)
-                .unwrap()
-                .iter()
-                .rev()
-                .cloned()
-            {
+            // Pages come newest first, each one older than the last
+            let mut fetched: Vec<channel::Message> = Vec::new();
+            for limit in page_sizes(num as u64) {
+                let mut retriever = GetMessages::default().limit(limit);
+                if let Some(oldest) = fetched.last().map(|msg| msg.id) {
+                    retriever = retriever.before(oldest);
+                }
+                let page = match channel.messages(|_| retriever) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        app.send_err(format_err!("Unable to load messages: {}", err));
+                        break;
+                    }
+                };
+                let end = is_end_of_history(limit, page.len());
+                fetched.extend(page);
+                if end {
+                    self.reached_start.set(true);
+                    break;
+                }
+            }
+            for message in fetched.into_iter().rev() {
                 self.add_msg(MessageItem::DiscordMessage(Box::new(message)));
             }
+            // Anything received in the meantime that the fetch missed
+            for message in self.background.borrow_mut().take(channel) {
+                self.add_msg(message);
+            }
+            let anchor = app.context.write().anchors.remove(&channel);
+            if let Some(anchor) = anchor {
+                self.restore_anchor(anchor);
+            }
         }
     }
 
-    fn put_nick(&self, message: &channel::Message, screen: &mut Terminal, x: usize, y: usize) {
+    /// The name a message's author is shown with and their role colour, cached per user
+    fn nick(&self, message: &channel::Message) -> (String, Option<Colour>) {
         let mut cache = self.nickname_cache.borrow_mut();
         let entry = cache.entry(message.author.id);
 
         use std::collections::hash_map::Entry::*;
-        let (nick, colour) = match entry {
+        let nick = match entry {
             Occupied(o) => o.into_mut(),
             Vacant(v) => {
                 if let Some(member) = utils::member(&message) {
@@ -161,27 +968,49 @@ impl Messages {
                 }
             }
         };
+        nick.clone()
+    }
 
-        if nick.len() > *self.max_name_len.borrow() {
-            *self.max_name_len.borrow_mut() = nick.len();
-        }
-        match colour {
-            Some(colour) => {
-                if self.truecolor {
-                    screen
-                        .buf
-                        .string_builder(x, y, nick)
-                        .fg(Color::Rgb(colour.r(), colour.g(), colour.b()))
-                        .draw();
-                } else {
-                    screen
-                        .buf
-                        .string_builder(x, y, nick)
-                        .fg(color_to_8bit(*colour))
-                        .draw();
-                }
+    fn put_nick(
+        &self,
+        message: &channel::Message,
+        screen: &mut Terminal,
+        x: usize,
+        y: usize,
+        context: &Context,
+        style: Option<Style>,
+    ) {
+        let (nick, colour) = self.nick(message);
+        let x = nick_x(
+            x,
+            nick.len(),
+            *self.max_name_len.borrow(),
+            context.name_align,
+        );
+        match (
+            nick_colour(
+                colour,
+                context.role_colors,
+                self.truecolor,
+                context.theme.nickname,
+            ),
+            style,
+        ) {
+            (Some(colour), Some(style)) => {
+                screen
+                    .buf
+                    .string_builder(x, y, nick)
+                    .fg(colour)
+                    .style(style)
+                    .draw();
+            }
+            (Some(colour), None) => {
+                screen.buf.string_builder(x, y, nick).fg(colour).draw();
+            }
+            (None, Some(style)) => {
+                screen.buf.string_builder(x, y, nick).style(style).draw();
             }
-            None => {
+            (None, None) => {
                 screen.buf.print(x, y, &nick);
             }
         }
@@ -191,28 +1020,115 @@ impl Messages {
         &self,
         screen: &mut Terminal,
         size: TermSize,
+        reserved: usize,
         context: &Arc<RwLock<Context>>,
     ) -> Result<(), io::Error> {
-        self.set_show_sidebar(context.read().guild_sidebar_visible);
-
-        let rough_msg_count = size.height;
-        let mut msgs = self.messages.borrow_mut();
-        let msg_diff = msgs.len().saturating_sub(rough_msg_count as usize);
-
-        msgs.drain(0..msg_diff);
+        {
+            let context = context.read();
+            self.set_show_sidebar(sidebar_fits(
+                context.guild_sidebar_visible,
+                size.width as usize,
+                context.sidebar_min_width,
+            ));
+        }
 
-        let mut messages = msgs.clone();
+        // Loaded history is kept, addresses and ranges rely on positions in the buffer
+        let msgs = self.messages.borrow();
 
-        let mut y = size.height.saturating_sub(BOTTOM_DIFF + 1);
-        for mut msg in messages.iter_mut().rev() {
-            match msg {
-                MessageItem::DiscordMessage(msg) => {
-                    if !self.render_discord_msg(msg, &mut y, size, screen, context)? {
+        let mut y = size.height.saturating_sub(reserved + TOP_START + 1);
+        let mut top_visible = true;
+        let reading = self.reading.get();
+        let shown = msgs
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, msg)| match (msg, reading) {
+                (MessageItem::DiscordMessage(msg), Some(user)) => addresses_user(msg, user),
+                (_, None) => true,
+            });
+        let (separators, timezone, current_user, name_width) = {
+            let context = context.read();
+            let current_user = context.current_user.as_ref().map(|user| user.id);
+            (
+                context.day_separators,
+                context.timezone,
+                current_user,
+                context.name_width,
+            )
+        };
+        // Every message takes at least a row, so no more than one per row can be drawn
+        let candidates = msgs
+            .iter()
+            .rev()
+            .filter(|msg| match (msg, reading) {
+                (MessageItem::DiscordMessage(msg), Some(user)) => addresses_user(msg, user),
+                (_, None) => true,
+            })
+            .skip(self.scroll.get())
+            .take(y + 1)
+            .map(|msg| match msg {
+                MessageItem::DiscordMessage(msg) => self.nick(msg).0.len(),
+            });
+        let column_width = name_column_width(*self.max_name_len.borrow(), candidates, name_width);
+        *self.max_name_len.borrow_mut() = column_width;
+        let mut newer = None;
+        let left_start = self.left_start();
+        let mut drawn = self.drawn.borrow_mut();
+        drawn.clear();
+        for (index, msg) in shown.skip(self.scroll.get()) {
+            match *msg {
+                MessageItem::DiscordMessage(ref msg) => {
+                    if let Some(newer) = newer {
+                        if separators && crosses_day(msg.timestamp, newer, timezone) {
+                            let rule = day_separator(message_date(newer, timezone), size.width);
+                            screen
+                                .buf
+                                .string_builder(0, y + TOP_START, &rule)
+                                .style(Style::Faint)
+                                .draw();
+                            if y == 0 {
+                                top_visible = false;
+                                break;
+                            }
+                            y -= 1;
+                        }
+                    }
+                    newer = Some(msg.timestamp);
+                    let bottom = y;
+                    // Drawing wraps the content, which only applies to this frame
+                    let mut msg = msg.clone();
+                    let fits = self.render_discord_msg(
+                        &mut msg,
+                        &mut y,
+                        size,
+                        screen,
+                        context,
+                        current_user,
+                    )?;
+                    // A message that does not fit is drawn down from the top row
+                    let top = if fits { y + 1 } else { 0 };
+                    drawn.push((top + TOP_START, bottom + TOP_START, index));
+                    if !fits {
+                        top_visible = false;
                         break;
-                    };
+                    }
+                    if self.numbers.get() {
+                        // The message's first line is just above where the next one starts
+                        let number = message_number(index).to_string();
+                        screen
+                            .buf
+                            .string_builder(
+                                left_start.saturating_sub(3 + number.len()),
+                                y + 1 + TOP_START,
+                                &number,
+                            )
+                            .style(Style::Faint)
+                            .draw();
+                    }
                 }
             }
         }
+        self.top_visible.set(top_visible);
         Ok(())
     }
 
@@ -223,58 +1139,121 @@ impl Messages {
         size: TermSize,
         screen: &mut Terminal,
         context: &Arc<RwLock<Context>>,
+        current_user: Option<UserId>,
     ) -> Result<bool, io::Error> {
-        // Show an indicator if an attachement is present
-        let content = if !msg.attachments.is_empty() {
-            format!("{} {}", context.read().char_set.paper_clip(), msg.content)
-        } else {
-            msg.content.to_owned()
+        let own_style = {
+            let context = context.read();
+            if is_own_highlighted(msg.author.id, current_user, context.highlight_self) {
+                Some(context.theme.own_message.style())
+            } else {
+                None
+            }
         };
-
-        let left_start = if self.showing_sidebar() {
-            LEFT_START_EXTENDED
-        } else {
-            LEFT_START
+        let visible = spoilers_visible(
+            self.spoilers.get(),
+            self.revealed.borrow().contains(&msg.id),
+        );
+        let body = mask_spoilers(&msg.content, visible);
+        // Shortcodes replace emoji before wrapping, so lines are measured at their drawn width
+        let body = match context.read().emoji {
+            EmojiStyle::Text => emoji::to_text(&body),
+            EmojiStyle::Unicode => body,
+        };
+        let indicator = {
+            let context = context.read();
+            indicators(msg, context.char_set.pin(), context.char_set.paper_clip())
         };
+        let content = match indicator {
+            Some(indicator) => format!("{} {}", indicator, body),
+            None => body,
+        };
+
+        let left_start = self.left_start();
 
-        let wrapped_lines: Vec<String> = content
+        let width = (size.width as usize)
+            .saturating_sub(RIGHT_PADDING + LEFT_PADDING + left_start + TIME_PADDING);
+        let mut wrapped_lines: Vec<String> = content
             .lines()
-            .map(|line| {
-                fill(
-                    line,
-                    (size.width as usize)
-                        .saturating_sub(RIGHT_PADDING + LEFT_PADDING + left_start + TIME_PADDING),
-                )
+            .flat_map(|line| {
+                fill(line, width)
+                    .split('\n')
+                    .flat_map(|line| hard_break(line, width))
+                    .collect::<Vec<_>>()
             })
             .collect();
+        if !msg.reactions.is_empty() {
+            wrapped_lines.push(reaction_line(&msg.reactions, context.read().max_reactions));
+        }
         msg.content = wrapped_lines.join("\n");
 
         let lines: Vec<_> = msg.content.lines().rev().collect();
         for (i, line) in lines.iter().enumerate() {
             if i == (lines.len() - 1) {
-                let timestamp = msg
-                    .timestamp
-                    .with_timezone(&::chrono::offset::Local)
-                    .format(&self.timestamp_fmt)
-                    .to_string();
+                let timestamp = {
+                    let context = context.read();
+                    if context.relative_timestamps {
+                        // `now` stops the clock so screenshots stay the same
+                        let precision = context.relative_precision;
+                        match context.clock {
+                            Some(now) => {
+                                relative::format_age(msg.timestamp, &FakeClock::new(now), precision)
+                            }
+                            None => relative::format_age(msg.timestamp, &SystemClock, precision),
+                        }
+                    } else {
+                        timezone::format(msg.timestamp, context.timezone, &self.timestamp_fmt)
+                    }
+                };
                 let timestamp_len = timestamp.len();
                 let timestamp = timestamp + &if msg.edited_timestamp.is_some() {
                     "*"
                 } else {
                     ""
                 };
-                self.put_nick(&msg, screen, left_start + timestamp_len + 1, *y + TOP_START);
+                let context = context.read();
+                self.put_nick(
+                    &msg,
+                    screen,
+                    nick_column(left_start, timestamp_len, context.timestamp_column),
+                    *y + TOP_START,
+                    &context,
+                    own_style,
+                );
+                let timestamp_style = if self.current.get() == Some(msg.id) {
+                    Style::Invert
+                } else {
+                    Style::Faint
+                };
                 screen
                     .buf
                     .string_builder(left_start.saturating_sub(2), *y + TOP_START, &timestamp)
-                    .style(Style::Faint)
+                    .style(timestamp_style)
                     .draw();
             }
-            screen.buf.print(
-                10 + left_start + *self.max_name_len.borrow(),
-                *y + TOP_START,
-                line,
-            );
+            let body_x = 10 + left_start + *self.max_name_len.borrow();
+            match own_style {
+                Some(style) => screen
+                    .buf
+                    .string_builder(body_x, *y + TOP_START, line)
+                    .style(style)
+                    .draw(),
+                None => screen.buf.print(body_x, *y + TOP_START, line),
+            }
+            if let Some(ref highlight) = *self.highlight.borrow() {
+                let style = context.read().theme.highlight.style();
+                for (start, end) in highlight_spans(highlight, line) {
+                    let text = line
+                        .chars()
+                        .skip(start)
+                        .take(end - start)
+                        .collect::<String>();
+                    screen
+                        .buf
+                        .string_builder(body_x + start, *y + TOP_START, &text)
+                        .style(style)
+                        .draw();
+                }
+            }
             if *y == 0 {
                 return Ok(false);
             }
@@ -283,3 +1262,589 @@ impl Messages {
         Ok(true)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        add_own_reaction, addresses_user, attachment_indicator, build_index, colour_ramp,
+        colour_samples, crosses_day, day_separator, detect_truecolor, hard_break, highlight_spans,
+        indicators, is_end_of_history, is_own_highlighted, mask_spoilers, message_at_row,
+        message_id, name_column_width, nick_colour, nick_column, nick_x, page_sizes,
+        parse_truecolor_override, reaction_line, sidebar_fits, spoilers_visible, Messages,
+    };
+    use chrono::{FixedOffset, NaiveDate};
+    use helpers::testing::{
+        attachment_value, message, message_from_value, message_item, message_value,
+    };
+    use model::{MessageItem, NameAlign, NameWidth};
+    use regex::Regex;
+    use serde_json;
+    use serenity::model::channel::{MessageReaction, ReactionType};
+    use serenity::model::event::MessageUpdateEvent;
+    use serenity::model::id::{ChannelId, EmojiId, MessageId, UserId};
+    use serenity::utils::Colour;
+    use termbuf::Color;
+
+    fn messages() -> Messages {
+        Messages::new("%H:%M".to_owned(), false, Some(false))
+    }
+
+    #[test]
+    fn truecolor_detection() {
+        assert!(detect_truecolor(Some("truecolor"), None));
+        assert!(detect_truecolor(Some("TrueColor"), None));
+        assert!(detect_truecolor(Some("24bit"), None));
+        assert!(!detect_truecolor(Some("xterm"), None));
+        assert!(!detect_truecolor(None, None));
+    }
+
+    #[test]
+    fn truecolor_forced() {
+        assert!(detect_truecolor(None, Some(true)));
+        assert!(detect_truecolor(Some("xterm"), Some(true)));
+        assert!(!detect_truecolor(Some("truecolor"), Some(false)));
+        assert!(!detect_truecolor(Some("24bit"), Some(false)));
+    }
+
+    #[test]
+    fn truecolor_override_values() {
+        assert_eq!(parse_truecolor_override("1"), Some(true));
+        assert_eq!(parse_truecolor_override("Off"), Some(false));
+        assert_eq!(parse_truecolor_override("maybe"), None);
+    }
+
+    #[test]
+    fn mutation_sets_dirty() {
+        let messages = messages();
+        messages.clear_dirty();
+        assert!(!messages.is_dirty());
+
+        messages.add_msg(message_item(1, 10, "hello"));
+        assert!(messages.is_dirty());
+
+        messages.clear_dirty();
+        assert!(!messages.is_dirty());
+
+        messages.delete_msg(ChannelId(1), MessageId(1));
+        assert!(messages.is_dirty());
+    }
+
+    #[test]
+    fn missing_delete_stays_clean() {
+        let messages = messages();
+        messages.add_msg(message_item(1, 10, "hello"));
+        messages.clear_dirty();
+
+        messages.delete_msg(ChannelId(1), MessageId(2));
+        assert!(!messages.is_dirty());
+    }
+
+    #[test]
+    fn highlight_span_columns() {
+        let regex = Regex::new("o+").unwrap();
+
+        assert_eq!(
+            highlight_spans(&regex, "foo bar boo"),
+            vec![(1, 3), (9, 11)]
+        );
+        assert_eq!(highlight_spans(&regex, "ünïcödé foo"), vec![(9, 11)]);
+        assert!(highlight_spans(&Regex::new("x*").unwrap(), "abc").is_empty());
+    }
+
+    #[test]
+    fn current_defaults_to_newest() {
+        let messages = messages();
+        messages.add_msg(message_item(1, 10, "first"));
+        messages.add_msg(message_item(2, 10, "second"));
+        assert_eq!(messages.current_index(), 1);
+        assert_eq!(messages.current_message(), None);
+
+        messages.set_current(0);
+        assert_eq!(messages.current_index(), 0);
+        assert_eq!(messages.current_message(), Some(MessageId(1)));
+
+        messages.delete_msg(ChannelId(1), MessageId(1));
+        assert_eq!(messages.current_index(), 0);
+        assert_eq!(messages.current_message(), None);
+    }
+
+    #[test]
+    fn duplicate_messages_are_skipped() {
+        let messages = messages();
+        messages.add_msg(message_item(1, 10, "hello"));
+        messages.add_msg(message_item(1, 10, "hello"));
+
+        assert_eq!(messages.messages.borrow().len(), 1);
+    }
+
+    #[test]
+    fn anchor_restored_after_restart() {
+        let before = messages();
+        for id in 1..7 {
+            before.add_msg(message_item(id, 10, "hi"));
+        }
+        assert_eq!(before.anchor(), None);
+        before.scroll_to(3);
+        let anchor = before.anchor();
+        assert_eq!(anchor, Some(MessageId(4)));
+
+        // A new session loads the same messages and scrolls back to the anchor
+        let after = messages();
+        for id in 1..7 {
+            after.add_msg(message_item(id, 10, "hi"));
+        }
+        assert!(after.restore_anchor(anchor.unwrap()));
+        assert_eq!(after.anchor(), anchor);
+        // Anchors that are no longer loaded leave the view at the bottom
+        let fresh = messages();
+        fresh.add_msg(message_item(9, 10, "hi"));
+        assert!(!fresh.restore_anchor(MessageId(4)));
+        assert_eq!(fresh.anchor(), None);
+    }
+
+    #[test]
+    fn right_aligned_nick() {
+        assert_eq!(nick_x(10, 3, 8, NameAlign::Right), 15);
+        assert_eq!(nick_x(10, 8, 8, NameAlign::Right), 10);
+        assert_eq!(nick_x(10, 3, 8, NameAlign::Left), 10);
+        // Names wider than the column are never pushed left of the column start
+        assert_eq!(nick_x(10, 12, 8, NameAlign::Right), 10);
+    }
+
+    #[test]
+    fn name_column_widths() {
+        let buffer = vec![
+            message_item(1, 123456, "long ago"),
+            message_item(2, 1, "hi"),
+            message_item(3, 22, "hey"),
+        ];
+        let lengths = |items: &[MessageItem]| -> Vec<usize> {
+            items
+                .iter()
+                .map(|item| match *item {
+                    MessageItem::DiscordMessage(ref msg) => msg.author.name.len(),
+                })
+                .collect()
+        };
+
+        let all_time = name_column_width(0, lengths(&buffer), NameWidth::All);
+        assert_eq!(all_time, "user123456".len());
+        // Scrolled so only the newest two are on screen
+        let visible = lengths(&buffer[1..]);
+        assert_eq!(
+            name_column_width(all_time, visible.clone(), NameWidth::All),
+            all_time
+        );
+        assert_eq!(
+            name_column_width(all_time, visible, NameWidth::Visible),
+            "user22".len()
+        );
+        assert_eq!(name_column_width(all_time, vec![], NameWidth::Visible), 0);
+    }
+
+    #[test]
+    fn colour_sample_variants() {
+        let samples = colour_samples("nick", Colour::new(0xFF_80_00));
+        let coloured: Vec<_> = samples.iter().filter_map(|span| span.fg).collect();
+
+        assert_eq!(coloured.len(), 2);
+        assert_eq!(coloured[0], Color::Rgb(0xFF, 0x80, 0x00));
+        assert_eq!(coloured[1], Color::AnsiValue(16 + 36 * 5 + 6 * 2));
+        assert_eq!(samples[0].text, "nick (truecolor)");
+        assert_eq!(samples[2].text, "nick (256 colour)");
+    }
+
+    #[test]
+    fn colour_ramp_encodings() {
+        let ramp = colour_ramp(16);
+
+        assert_eq!(ramp.len(), 2);
+        for line in &ramp {
+            assert_eq!(line.len(), 1 + 16);
+        }
+        assert_eq!(ramp[0][0].text, "truecolor   ");
+        assert_eq!(ramp[0][1].fg, Some(Color::Rgb(255, 0, 0)));
+        assert_eq!(ramp[0][16].fg, Some(Color::Rgb(0, 0, 255)));
+        assert!(ramp[0][1..].iter().all(|cell| match cell.fg {
+            Some(Color::Rgb(..)) => true,
+            _ => false,
+        }));
+        assert_eq!(ramp[1][1].fg, Some(Color::AnsiValue(16 + 36 * 5)));
+        assert_eq!(ramp[1][16].fg, Some(Color::AnsiValue(16 + 5)));
+        assert!(ramp[1][1..].iter().all(|cell| match cell.fg {
+            Some(Color::AnsiValue(..)) => true,
+            _ => false,
+        }));
+    }
+
+    #[test]
+    fn sidebar_hides_on_narrow_terminals() {
+        assert!(sidebar_fits(true, 120, 80));
+        assert!(sidebar_fits(true, 80, 80));
+        assert!(!sidebar_fits(true, 79, 80));
+        assert!(!sidebar_fits(true, 40, 80));
+        // Never shown when turned off, however wide
+        assert!(!sidebar_fits(false, 200, 80));
+    }
+
+    #[test]
+    fn click_row_to_message() {
+        // Drawn bottom up: message 4 on rows 8-9, message 3 on row 6 below a day separator,
+        // and message 2 cut off at the top
+        let drawn = [(8, 9, 4), (6, 6, 3), (1, 5, 2)];
+
+        assert_eq!(message_at_row(&drawn, 9), Some(4));
+        assert_eq!(message_at_row(&drawn, 8), Some(4));
+        assert_eq!(message_at_row(&drawn, 7), None);
+        assert_eq!(message_at_row(&drawn, 6), Some(3));
+        assert_eq!(message_at_row(&drawn, 1), Some(2));
+        assert_eq!(message_at_row(&drawn, 0), None);
+        assert_eq!(message_at_row(&drawn, 12), None);
+    }
+
+    #[test]
+    fn end_of_history() {
+        assert!(is_end_of_history(50, 12));
+        assert!(is_end_of_history(50, 0));
+        assert!(!is_end_of_history(50, 50));
+    }
+
+    #[test]
+    fn reaction_cap() {
+        let reaction = |reaction_type, count| MessageReaction {
+            count,
+            me: false,
+            reaction_type,
+        };
+        let reactions = vec![
+            reaction(ReactionType::Unicode("👍".to_owned()), 3),
+            reaction(
+                ReactionType::Custom {
+                    animated: false,
+                    id: EmojiId(1),
+                    name: Some("blob".to_owned()),
+                },
+                1,
+            ),
+            reaction(ReactionType::Unicode("🎉".to_owned()), 2),
+            reaction(ReactionType::Unicode("👀".to_owned()), 1),
+        ];
+
+        assert_eq!(reaction_line(&reactions, 2), "👍 3  :blob: 1  +2");
+        assert_eq!(reaction_line(&reactions, 10), "👍 3  :blob: 1  🎉 2  👀 1");
+        assert_eq!(reaction_line(&reactions, 0), "+4");
+    }
+
+    #[test]
+    fn own_reactions() {
+        let thumbs = || ReactionType::Unicode("👍".to_owned());
+        let blob = |name: &str| ReactionType::Custom {
+            animated: false,
+            id: EmojiId(1),
+            name: Some(name.to_owned()),
+        };
+        let mut reactions = vec![MessageReaction {
+            count: 2,
+            me: false,
+            reaction_type: thumbs(),
+        }];
+
+        add_own_reaction(&mut reactions, thumbs());
+        assert_eq!((reactions[0].count, reactions[0].me), (3, true));
+        // Reacting twice with the same emoji does nothing
+        add_own_reaction(&mut reactions, thumbs());
+        assert_eq!(reactions[0].count, 3);
+
+        add_own_reaction(&mut reactions, blob("blob"));
+        add_own_reaction(&mut reactions, blob("renamed"));
+        assert_eq!(reactions.len(), 2);
+        assert_eq!((reactions[1].count, reactions[1].me), (1, true));
+    }
+
+    #[test]
+    fn role_colors_toggle() {
+        let colour = Some(Colour::new(0xFF_80_00));
+
+        assert_eq!(
+            nick_colour(colour, true, true, None),
+            Some(Color::Rgb(0xFF, 0x80, 0x00))
+        );
+        assert_eq!(
+            nick_colour(colour, true, false, None),
+            Some(Color::AnsiValue(16 + 36 * 5 + 6 * 2))
+        );
+        assert_eq!(nick_colour(colour, false, true, None), None);
+        assert_eq!(
+            nick_colour(colour, false, true, Some(7)),
+            Some(Color::AnsiValue(7))
+        );
+        assert_eq!(
+            nick_colour(None, true, true, Some(7)),
+            Some(Color::AnsiValue(7))
+        );
+    }
+
+    #[test]
+    fn hard_breaks_long_words() {
+        assert_eq!(
+            hard_break("https://example.com/abcdef", 10),
+            vec!["https://ex", "ample.com/", "abcdef"]
+        );
+        assert_eq!(hard_break("short", 10), vec!["short"]);
+        assert_eq!(hard_break("", 10), vec![""]);
+        // Wide characters take two columns and are never split
+        assert_eq!(hard_break("日本語です", 4), vec!["日本", "語で", "す"]);
+        // Combining marks stay with their base character
+        assert_eq!(
+            hard_break("e\u{301}e\u{301}e\u{301}", 2),
+            vec!["e\u{301}e\u{301}", "e\u{301}"]
+        );
+    }
+
+    #[test]
+    fn rebuilt_index_matches_scan() {
+        let messages = messages();
+        for id in 1..=8 {
+            messages.add_msg(message_item(id, 10, "hi"));
+        }
+        // Reorder behind the index's back, as a bulk mutation would
+        {
+            let mut buffer = messages.messages.borrow_mut();
+            buffer.swap(0, 5);
+            buffer.swap(2, 7);
+            buffer.reverse();
+            buffer.remove(3);
+        }
+        messages.rebuild_index();
+
+        let buffer = messages.messages.borrow();
+        let index = messages.index.borrow();
+        assert_eq!(index.len(), buffer.len());
+        for msg in buffer.iter() {
+            let id = message_id(msg);
+            let position = buffer.iter().position(|msg| message_id(msg) == id);
+            assert_eq!(index.get(&id).cloned(), position);
+        }
+        assert_eq!(*index, build_index(&buffer));
+    }
+
+    #[test]
+    fn fixed_timestamp_column() {
+        // Without a fixed column the nickname follows the timestamp
+        assert_eq!(nick_column(5, 4, None), 10);
+        assert_eq!(nick_column(5, 8, None), 14);
+        // With one it starts at the same place whatever the timestamp
+        assert_eq!(nick_column(5, 4, Some(8)), 14);
+        assert_eq!(nick_column(5, 2, Some(8)), 14);
+        // Unexpectedly long timestamps still never overlap
+        assert_eq!(nick_column(5, 10, Some(8)), 16);
+    }
+
+    #[test]
+    fn global_spoiler_toggle() {
+        assert!(!spoilers_visible(None, false));
+        assert!(spoilers_visible(None, true));
+        // The global setting overrides revealing a single message
+        assert!(spoilers_visible(Some(true), false));
+        assert!(!spoilers_visible(Some(false), true));
+    }
+
+    #[test]
+    fn spoiler_masking() {
+        assert_eq!(mask_spoilers("a ||secret|| b", false), "a ██████ b");
+        assert_eq!(mask_spoilers("a ||secret|| b", true), "a secret b");
+        assert_eq!(mask_spoilers("||x|| and ||yz||", false), "█ and ██");
+        assert_eq!(mask_spoilers("no spoilers", false), "no spoilers");
+        assert_eq!(mask_spoilers("a || b", false), "a || b");
+    }
+
+    #[test]
+    fn edit_adds_attachments() {
+        let messages = messages();
+        messages.add_msg(message_item(1, 10, "look"));
+        messages.clear_dirty();
+        let update: MessageUpdateEvent = serde_json::from_value(json!({
+            "id": "1",
+            "channel_id": "1",
+            "attachments": [attachment_value(5, "cat.png"), attachment_value(6, "dog.png")],
+        }))
+        .unwrap();
+
+        messages.update_message(update);
+
+        match messages.messages.borrow()[0] {
+            MessageItem::DiscordMessage(ref msg) => {
+                assert_eq!(msg.content, "look");
+                assert_eq!(msg.attachments.len(), 2);
+                assert_eq!(msg.attachments[0].filename, "cat.png");
+                assert_eq!(attachment_indicator(msg, '+'), Some("+2".to_owned()));
+            }
+        }
+        assert!(messages.is_dirty());
+    }
+
+    #[test]
+    fn bulk_delete_keeps_the_rest() {
+        let messages = messages();
+        for id in 1..5 {
+            messages.add_msg(message_item(id, 10, "hi"));
+        }
+
+        messages.delete_msg_bulk(ChannelId(1), &[MessageId(2), MessageId(3)]);
+        let left: Vec<_> = messages.messages.borrow().iter().map(message_id).collect();
+        assert_eq!(left, vec![MessageId(1), MessageId(4)]);
+        assert_eq!(messages.index_of(MessageId(4)), Some(1));
+        // Ids are only removed from their own channel
+        messages.delete_msg_bulk(ChannelId(2), &[MessageId(1)]);
+        assert_eq!(messages.messages.borrow().len(), 2);
+    }
+
+    #[test]
+    fn select_scrolls_to_message() {
+        let messages = messages();
+        for id in 1..11 {
+            messages.add_msg(message_item(id, 10, "hi"));
+        }
+
+        messages.select(5);
+        assert_eq!(messages.current_index(), 5);
+        assert_eq!(messages.current_message(), Some(MessageId(6)));
+        // Scrolled back so it is the newest message shown
+        assert_eq!(messages.scroll.get(), 4);
+        assert_eq!(messages.anchor(), Some(MessageId(6)));
+    }
+
+    #[test]
+    fn page_up_after_returning_to_bottom() {
+        let messages = messages();
+        for id in 1..31 {
+            messages.add_msg(message_item(id, 10, "hi"));
+        }
+
+        assert!(messages.scroll_up_with(|| unreachable!()));
+        // Paged back to the start of the channel, which has no older messages
+        messages.top_visible.set(true);
+        assert!(!messages.scroll_up_with(|| 0));
+
+        messages.scroll_down();
+        assert!(messages.at_bottom());
+        messages.top_visible.set(false);
+
+        // The history paged through is still loaded, so nothing is fetched
+        assert!(messages.scroll_up_with(|| unreachable!()));
+        assert_eq!(messages.messages.borrow().len(), 30);
+        assert_eq!(messages.anchor(), Some(MessageId(25)));
+    }
+
+    #[test]
+    fn undo_deletions() {
+        let messages = messages();
+        for id in 1..6 {
+            messages.add_msg(message_item(id, 10, "hi"));
+        }
+        let ids = |messages: &Messages| -> Vec<MessageId> {
+            messages.messages.borrow().iter().map(message_id).collect()
+        };
+
+        messages.remove_for_undo(ChannelId(1), &[MessageId(5)]);
+        messages.remove_for_undo(ChannelId(1), &[MessageId(2), MessageId(3)]);
+        // Deleted by someone else afterwards, which is not for the user to undo
+        messages.delete_msg(ChannelId(1), MessageId(1));
+        messages.add_msg(message_item(6, 10, "new"));
+        assert_eq!(ids(&messages), vec![MessageId(4), MessageId(6)]);
+
+        // A deletion of several messages is undone as one step
+        assert_eq!(messages.undo_delete(), 2);
+        assert_eq!(
+            ids(&messages),
+            vec![MessageId(2), MessageId(3), MessageId(4), MessageId(6)]
+        );
+        assert_eq!(messages.index_of(MessageId(4)), Some(2));
+
+        assert_eq!(messages.undo_delete(), 1);
+        assert_eq!(ids(&messages)[3], MessageId(5));
+        assert_eq!(messages.undo_delete(), 0);
+        assert!(!ids(&messages).contains(&MessageId(1)));
+    }
+
+    #[test]
+    fn pin_indicator() {
+        let mut value = message_value(1, 2, "hi");
+        value["pinned"] = json!(true);
+        assert_eq!(
+            indicators(&message_from_value(value.clone()), '^', '+'),
+            Some("^".to_owned())
+        );
+        value["attachments"] = json!([attachment_value(5, "cat.png")]);
+        assert_eq!(
+            indicators(&message_from_value(value), '^', '+'),
+            Some("^ +".to_owned())
+        );
+        assert_eq!(indicators(&message(1, 2, "hi"), '^', '+'), None);
+    }
+
+    #[test]
+    fn clamped_pages() {
+        assert_eq!(page_sizes(40), vec![40]);
+        assert_eq!(page_sizes(100), vec![100]);
+        assert_eq!(page_sizes(250), vec![100, 100, 50]);
+        assert!(page_sizes(0).is_empty());
+    }
+
+    #[test]
+    fn messages_addressed_to_me() {
+        let mention = |id, mentioned: u64| {
+            let mut value = message_value(id, 11, "hey");
+            value["mentions"] = json!([{
+                "id": mentioned.to_string(),
+                "username": format!("user{}", mentioned),
+                "discriminator": "0001",
+                "avatar": null,
+            }]);
+            message_from_value(value)
+        };
+
+        assert!(addresses_user(&mention(1, 10), UserId(10)));
+        assert!(!addresses_user(&mention(2, 12), UserId(10)));
+        assert!(!addresses_user(&message(3, 11, "no mentions"), UserId(10)));
+    }
+
+    #[test]
+    fn own_messages_highlighted_when_enabled() {
+        assert!(is_own_highlighted(UserId(10), Some(UserId(10)), true));
+        assert!(!is_own_highlighted(UserId(10), Some(UserId(10)), false));
+        assert!(!is_own_highlighted(UserId(11), Some(UserId(10)), true));
+        assert!(!is_own_highlighted(UserId(10), None, true));
+    }
+
+    #[test]
+    fn day_changes_between_messages() {
+        let at = |timestamp: &str| {
+            let mut value = message_value(1, 10, "hi");
+            value["timestamp"] = json!(timestamp);
+            message_from_value(value).timestamp
+        };
+        let utc = Some(FixedOffset::east(0));
+
+        let evening = at("2018-01-14T22:00:00+00:00");
+        let late = at("2018-01-14T23:59:00+00:00");
+        let morning = at("2018-01-15T08:00:00+00:00");
+        assert!(!crosses_day(evening, late, utc));
+        assert!(crosses_day(late, morning, utc));
+        // Both fall on the 15th three hours ahead
+        assert!(!crosses_day(
+            late,
+            morning,
+            Some(FixedOffset::east(3 * 3600))
+        ));
+    }
+
+    #[test]
+    fn day_separator_fills_width() {
+        let date = NaiveDate::from_ymd(2018, 1, 15);
+        let rule = day_separator(date, 30);
+        assert_eq!(rule.chars().count(), 30);
+        assert!(rule.contains(" Monday, Jan 15 "));
+        assert!(rule.starts_with("─") && rule.ends_with("─"));
+    }
+}
@@ -1,12 +1,19 @@
+mod footer;
 mod guild_list;
 mod indicator;
 mod input;
 mod messages;
+mod overlay;
+mod pinned;
 mod terminal;
 
+use std::cell::Cell;
 use std::sync::mpsc::{self, SyncSender};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use discord::utils;
+use helpers::cooldown;
 use model::Context;
 
 use serenity::prelude::RwLock;
@@ -15,76 +22,228 @@ use termbuf;
 
 use failure::Error;
 
+pub use self::footer::STATUS_SECS;
+pub use self::messages::{colour_ramp, colour_samples, message_number, reaction_label};
+pub use self::overlay::Span;
+
+/// Rows of the input box, including its border
+const INPUT_ROWS: usize = 3;
+
+/// Rows below the input box, shared by the footer and the typing indicator
+fn bottom_rows(footer_lines: usize, typing: bool) -> usize {
+    if typing {
+        footer_lines.max(1)
+    } else {
+        footer_lines
+    }
+}
+
+/// What the event loop should do about drawing
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    /// Nothing changed
+    Idle,
+    Render,
+    /// A redraw is pending but the last one was too recent
+    Wait(Duration),
+}
+
+/// Coalesces redraws so they happen at most once per `frame_interval`
+fn next_frame(
+    dirty: bool,
+    last_render: Option<Instant>,
+    now: Instant,
+    frame_interval: Duration,
+) -> Frame {
+    if !dirty {
+        return Frame::Idle;
+    }
+    match last_render.and_then(|last| cooldown::remaining(last, now, frame_interval)) {
+        Some(delay) => Frame::Wait(delay),
+        None => Frame::Render,
+    }
+}
+
+/// Width messages are laid out at, an override never exceeds the real terminal width
+fn effective_width(actual: usize, width_override: Option<usize>) -> usize {
+    match width_override {
+        Some(width) => width.min(actual),
+        None => actual,
+    }
+}
+
+/// Rows at the bottom of the screen that the message area must leave free
+fn reserved_rows(footer_lines: usize, typing: bool) -> usize {
+    INPUT_ROWS + bottom_rows(footer_lines, typing)
+}
+
 pub struct View {
-    pub terminal: terminal::Terminal,
+    /// Only `None` while the terminal is handed to another program by `suspend`
+    pub terminal: Option<terminal::Terminal>,
     event_listener_killswitch: SyncSender<()>,
     pub message_view: messages::Messages,
     pub input_view: input::Input,
     pub terminal_size: termbuf::TermSize,
     pub indicator: indicator::Indicator,
+    pub footer: footer::Footer,
+    pub overlay: overlay::Overlay,
+    pub pinned: pinned::Pinned,
     pub guild_list: guild_list::GuildList,
     pub context: Arc<RwLock<Context>>,
+    /// Set when something outside of the message buffer needs to be redrawn
+    dirty: bool,
+    last_render: Option<Instant>,
+    frame_interval: Duration,
+    /// Width set with the `width` command to preview wrapping
+    width_override: Cell<Option<usize>>,
 }
 
 impl View {
     pub fn new(context: &Arc<RwLock<Context>>) -> View {
         let locked_ctx = context.read();
 
-        let terminal = terminal::Terminal::new().unwrap();
+        let terminal = terminal::Terminal::new(locked_ctx.mouse).unwrap();
 
         let terminal_size = terminal.buf.size().expect("Unable to get size");
 
         let (killswitch_tx, killswitch_rx) = mpsc::sync_channel(0);
         terminal.listen(locked_ctx.event_channel.clone(), killswitch_rx);
 
-        let message_view = messages::Messages::new(locked_ctx.timestamp_fmt.clone(), false);
+        let message_view = messages::Messages::new(
+            locked_ctx.timestamp_fmt.clone(),
+            false,
+            locked_ctx.truecolor,
+        );
         let input_view = input::Input::new(locked_ctx.event_channel.clone());
         let indicator = indicator::Indicator::new(locked_ctx.event_channel.clone());
         let guild_list = guild_list::GuildList::new();
+        let footer = footer::Footer::new();
+        footer.set_timezone(locked_ctx.timezone_label.clone());
+        let overlay = overlay::Overlay::new();
+        let pinned = pinned::Pinned::new();
 
         View {
-            terminal,
+            terminal: Some(terminal),
             event_listener_killswitch: killswitch_tx,
             message_view,
             input_view,
             terminal_size,
             indicator,
+            footer,
+            overlay,
+            pinned,
             guild_list,
             context: context.clone(),
+            dirty: true,
+            last_render: None,
+            frame_interval: locked_ctx.frame_interval,
+            width_override: Cell::new(None),
         }
     }
 
+    pub fn set_width_override(&self, width: Option<usize>) {
+        self.width_override.set(width);
+    }
+
+    pub fn next_frame(&self, now: Instant) -> Frame {
+        next_frame(
+            self.needs_redraw(),
+            self.last_render,
+            now,
+            self.frame_interval,
+        )
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn needs_redraw(&self) -> bool {
+        self.dirty || self.message_view.is_dirty()
+    }
+
     pub fn present(&mut self) -> Result<(), Error> {
-        self.terminal.buf.clear()?;
+        if !self.needs_redraw() {
+            return Ok(());
+        }
+        let terminal = match self.terminal {
+            Some(ref mut terminal) => terminal,
+            None => return Ok(()),
+        };
+        terminal.buf.clear()?;
+
+        let unread: Vec<_> = self
+            .message_view
+            .background
+            .borrow()
+            .unread_channels()
+            .into_iter()
+            .map(|(channel_id, count, mention)| (utils::channel_name(channel_id), count, mention))
+            .collect();
+        self.footer.set_unread(footer::unread_summary(&unread));
 
-        self.message_view.render(
-            &mut self.terminal,
+        let footer_lines = self.footer.lines(self.terminal_size).len();
+        let typing = self.indicator.is_typing();
+        let reserved = reserved_rows(footer_lines, typing);
+
+        let mut message_size = self.terminal_size;
+        message_size.width = effective_width(message_size.width, self.width_override.get());
+        self.message_view
+            .render(terminal, message_size, reserved, &self.context.clone())?;
+        self.pinned.render(
+            terminal,
+            self.terminal_size,
+            &self.message_view.messages.borrow(),
+        );
+        self.overlay.render(terminal, self.terminal_size, reserved);
+        self.input_view.render(
+            terminal,
             self.terminal_size,
-            &self.context.clone(),
-        )?;
-        self.input_view
-            .render(&mut self.terminal, self.terminal_size);
-        self.indicator
-            .render(&mut self.terminal, self.terminal_size);
+            bottom_rows(footer_lines, typing),
+        );
+        self.indicator.render(terminal, self.terminal_size);
+        self.footer.render(terminal, self.terminal_size);
         if self.message_view.showing_sidebar() {
-            self.guild_list.render(
-                &mut self.terminal,
-                self.terminal_size,
-                &self.context.clone(),
-            );
+            self.guild_list
+                .render(terminal, self.terminal_size, &self.context.clone());
         }
-        self.terminal.buf.flush()?;
+        terminal.buf.flush()?;
+        self.last_render = Some(Instant::now());
+        self.dirty = false;
+        self.message_view.clear_dirty();
         Ok(())
     }
 
     pub fn update_size(&mut self) {
-        self.terminal_size = self
-            .terminal
-            .buf
-            .size()
-            .expect("Unable to get terminal size");
+        if let Some(ref terminal) = self.terminal {
+            self.terminal_size = terminal.buf.size().expect("Unable to get terminal size");
+        }
+        self.mark_dirty();
         self.present().expect("Unable to redraw");
     }
+
+    /// Hands the terminal to another program, such as an editor, while `run` runs
+    pub fn suspend<T, F: FnOnce() -> T>(&mut self, run: F) -> Result<T, Error> {
+        // termion's stdin reader only notices it was stopped on its next read, so the first key
+        // pressed in the other program can be lost
+        let _ = self.event_listener_killswitch.send(());
+        // Dropping the terminal leaves the alternate screen and raw mode
+        self.terminal = None;
+
+        let result = run();
+
+        let (mouse, event_channel) = {
+            let context = self.context.read();
+            (context.mouse, context.event_channel.clone())
+        };
+        let terminal = terminal::Terminal::new(mouse)?;
+        let (killswitch_tx, killswitch_rx) = mpsc::sync_channel(0);
+        terminal.listen(event_channel, killswitch_rx);
+        self.terminal = Some(terminal);
+        self.event_listener_killswitch = killswitch_tx;
+        self.update_size();
+        Ok(result)
+    }
 }
 
 impl Drop for View {
@@ -93,3 +252,48 @@ impl Drop for View {
         let _ = self.event_listener_killswitch.send(());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{effective_width, next_frame, reserved_rows, Frame};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn width_override() {
+        assert_eq!(effective_width(120, None), 120);
+        assert_eq!(effective_width(120, Some(60)), 60);
+        // Clamped to the real terminal
+        assert_eq!(effective_width(120, Some(200)), 120);
+    }
+
+    #[test]
+    fn render_debounce() {
+        let now = Instant::now();
+        let interval = Duration::from_millis(16);
+
+        assert_eq!(next_frame(false, None, now, interval), Frame::Idle);
+        assert_eq!(next_frame(false, Some(now), now, interval), Frame::Idle);
+        assert_eq!(next_frame(true, None, now, interval), Frame::Render);
+        assert_eq!(
+            next_frame(true, Some(now), now + Duration::from_millis(10), interval),
+            Frame::Wait(Duration::from_millis(6))
+        );
+        assert_eq!(
+            next_frame(true, Some(now), now + interval, interval),
+            Frame::Render
+        );
+    }
+
+    #[test]
+    fn reserved_rows_follow_footer() {
+        // Input box only
+        assert_eq!(reserved_rows(0, false), 3);
+        // Typing indicator gets its own line
+        assert_eq!(reserved_rows(0, true), 4);
+        // Status line, shared with the typing indicator
+        assert_eq!(reserved_rows(1, false), 4);
+        assert_eq!(reserved_rows(1, true), 4);
+        // Wrapped status
+        assert_eq!(reserved_rows(3, true), 6);
+    }
+}
@@ -0,0 +1,129 @@
+use view::terminal::Terminal;
+
+use std::cell::{Cell, RefCell};
+
+use termbuf::Color;
+use termbuf::Style;
+use termbuf::TermSize;
+
+const SIDE_PADDING: usize = 4;
+const TOP_START: usize = 1;
+
+/// Piece of an overlay line drawn in a single colour
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub fg: Option<Color>,
+}
+
+impl Span {
+    pub fn plain(text: String) -> Span {
+        Span { text, fg: None }
+    }
+
+    pub fn coloured(text: String, fg: Color) -> Span {
+        Span { text, fg: Some(fg) }
+    }
+}
+
+/// Popup drawn over the message area to show command output, closed with escape
+pub struct Overlay {
+    content: RefCell<Option<(String, Vec<Vec<Span>>)>>,
+    /// Number of lines scrolled past
+    scroll: Cell<usize>,
+}
+
+impl Overlay {
+    pub fn new() -> Overlay {
+        Overlay {
+            content: RefCell::new(None),
+            scroll: Cell::new(0),
+        }
+    }
+
+    pub fn show(&self, title: String, lines: Vec<String>) {
+        let lines = lines
+            .into_iter()
+            .map(|line| vec![Span::plain(line)])
+            .collect();
+        self.show_spans(title, lines);
+    }
+
+    pub fn show_spans(&self, title: String, lines: Vec<Vec<Span>>) {
+        *self.content.borrow_mut() = Some((title, lines));
+        self.scroll.set(0);
+    }
+
+    /// Scrolls the content by `lines`, negative values scroll up
+    pub fn scroll_by(&self, lines: isize) {
+        let len = match *self.content.borrow() {
+            Some((_, ref content)) => content.len(),
+            None => return,
+        };
+        let scroll = self.scroll.get() as isize + lines;
+        self.scroll
+            .set((scroll.max(0) as usize).min(len.saturating_sub(1)));
+    }
+
+    pub fn hide(&self) {
+        *self.content.borrow_mut() = None;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.content.borrow().is_some()
+    }
+
+    pub fn render(&self, screen: &mut Terminal, size: TermSize, reserved: usize) {
+        let content = self.content.borrow();
+        let (title, lines) = match *content {
+            Some((ref title, ref lines)) => (title, lines),
+            None => return,
+        };
+
+        let width = size.width.saturating_sub(SIDE_PADDING * 2);
+        // Leave room for the box's borders above the reserved rows
+        let max_height = size.height.saturating_sub(TOP_START + reserved + 2);
+        if width < 4 || max_height == 0 {
+            return;
+        }
+        let lines = &lines[self.scroll.get().min(lines.len())..];
+        let height = lines.len().max(1).min(max_height);
+
+        // Blank out whatever is underneath
+        let blank = " ".repeat(width);
+        for y in 0..height + 2 {
+            screen.buf.print(SIDE_PADDING - 1, TOP_START + y, &blank);
+        }
+        screen
+            .buf
+            .draw_box(SIDE_PADDING - 1, TOP_START, width.saturating_sub(2), height);
+        screen
+            .buf
+            .string_builder(SIDE_PADDING + 1, TOP_START, &format!(" {} ", title))
+            .style(Style::Bold)
+            .draw();
+
+        let hidden = lines.len().saturating_sub(height);
+        for (i, line) in lines.iter().take(height).enumerate() {
+            let y = TOP_START + 1 + i;
+            if hidden > 0 && i == height - 1 {
+                screen
+                    .buf
+                    .print(SIDE_PADDING, y, &format!("… {} more", hidden + 1));
+                continue;
+            }
+            let mut room = width.saturating_sub(3);
+            let mut x = SIDE_PADDING;
+            for span in line {
+                let text: String = span.text.chars().take(room).collect();
+                let len = text.chars().count();
+                match span.fg {
+                    Some(fg) => screen.buf.string_builder(x, y, &text).fg(fg).draw(),
+                    None => screen.buf.print(x, y, &text),
+                }
+                x += len;
+                room -= len;
+            }
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use view::terminal::Terminal;
+
+use std::cell::Cell;
+
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use termbuf::Style;
+use termbuf::TermSize;
+
+use model::MessageItem;
+
+const RIGHT_PADDING: usize = 2;
+const TOP_START: usize = 1;
+
+/// Newest message in the buffer written by `user_id`
+fn latest_from(messages: &[MessageItem], user_id: UserId) -> Option<&Message> {
+    messages.iter().rev().find_map(|item| match item {
+        MessageItem::DiscordMessage(msg) if msg.author.id == user_id => Some(&**msg),
+        MessageItem::DiscordMessage(_) => None,
+    })
+}
+
+/// Keeps one user's latest message in the top right corner, set with `pinuser`
+pub struct Pinned {
+    user: Cell<Option<UserId>>,
+}
+
+impl Pinned {
+    pub fn new() -> Pinned {
+        Pinned {
+            user: Cell::new(None),
+        }
+    }
+
+    pub fn set_user(&self, user: Option<UserId>) {
+        self.user.set(user);
+    }
+
+    pub fn render(&self, screen: &mut Terminal, size: TermSize, messages: &[MessageItem]) {
+        let msg = match self.user.get().and_then(|user| latest_from(messages, user)) {
+            Some(msg) => msg,
+            None => return,
+        };
+        let width = size.width / 3;
+        if width < 8 {
+            return;
+        }
+        let text: String = format!(
+            "{}: {}",
+            msg.author.name,
+            msg.content.lines().next().unwrap_or_default()
+        )
+        .chars()
+        .take(width)
+        .collect();
+        let box_width = text.chars().count() + 2;
+        let x = size.width.saturating_sub(box_width + RIGHT_PADDING + 1);
+
+        screen.buf.print(x, TOP_START + 1, &" ".repeat(box_width));
+        screen.buf.draw_box(x, TOP_START, box_width, 1);
+        screen
+            .buf
+            .string_builder(x + 1, TOP_START + 1, &text)
+            .style(Style::Bold)
+            .draw();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::latest_from;
+    use helpers::testing::message_item;
+    use serenity::model::id::{MessageId, UserId};
+
+    #[test]
+    fn latest_message_of_user() {
+        let messages = vec![
+            message_item(1, 10, "first"),
+            message_item(2, 11, "other"),
+            message_item(3, 10, "second"),
+            message_item(4, 11, "other again"),
+        ];
+
+        assert_eq!(
+            latest_from(&messages, UserId(10)).map(|msg| msg.id),
+            Some(MessageId(3))
+        );
+        assert_eq!(
+            latest_from(&messages, UserId(11)).map(|msg| msg.id),
+            Some(MessageId(4))
+        );
+        assert!(latest_from(&messages, UserId(12)).is_none());
+    }
+}
@@ -1,8 +1,10 @@
+use std::io::{self, Write};
 use std::sync::mpsc::{Receiver, Sender};
 use std::thread;
 use std::time::Duration;
 
 use termbuf::termion::async_stdin;
+use termbuf::termion::event::{Event as TermEvent, MouseButton, MouseEvent};
 use termbuf::termion::input::TermRead;
 use termbuf::TermBuf;
 
@@ -10,25 +12,47 @@ use model::Event;
 
 use failure::Error;
 
+/// Turns on reporting of mouse buttons, in the SGR encoding
+const ENABLE_MOUSE: &str = "\x1b[?1000h\x1b[?1006h";
+const DISABLE_MOUSE: &str = "\x1b[?1006l\x1b[?1000l";
+
 pub struct Terminal {
     pub buf: TermBuf,
+    /// Whether mouse clicks are reported, which turns off the terminal's own text selection
+    mouse: bool,
 }
 
 impl Terminal {
-    pub fn new() -> Result<Terminal, Error> {
+    pub fn new(mouse: bool) -> Result<Terminal, Error> {
         let mut term = Terminal {
             buf: TermBuf::init()?,
+            mouse,
         };
         term.buf.set_cursor_visible(true)?;
+        if mouse {
+            let mut stdout = io::stdout();
+            stdout.write_all(ENABLE_MOUSE.as_bytes())?;
+            stdout.flush()?;
+        }
         Ok(term)
     }
 
     pub fn listen(&self, event_channel: Sender<Event>, killswitch: Receiver<()>) {
-        thread::spawn(move || loop {
-            let mut keys = async_stdin().keys();
+        thread::spawn(move || {
+            let mut events = async_stdin().events();
             loop {
-                if let Some(Ok(key)) = keys.next() {
-                    event_channel.send(Event::Keypress(key)).unwrap();
+                if let Some(Ok(event)) = events.next() {
+                    match event {
+                        TermEvent::Key(key) => event_channel.send(Event::Keypress(key)).unwrap(),
+                        // Positions are reported from 1
+                        TermEvent::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) => {
+                            let (x, y) = (x.saturating_sub(1), y.saturating_sub(1));
+                            event_channel
+                                .send(Event::Click(x as usize, y as usize))
+                                .unwrap()
+                        }
+                        _ => {}
+                    }
                 } else if killswitch.try_recv().is_ok() {
                     trace!("Input loop received killsignal");
                     break;
@@ -39,3 +63,13 @@ impl Terminal {
         });
     }
 }
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.mouse {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(DISABLE_MOUSE.as_bytes());
+            let _ = stdout.flush();
+        }
+    }
+}